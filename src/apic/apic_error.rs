@@ -0,0 +1,213 @@
+use crate::{ErrorStatus, Mode, xApic};
+
+/// Reads the errors detected by the local APIC since the previous call, and clears them.
+///
+/// The error status register is not updated as errors are detected: a write latches the
+/// errors collected since the previous write into the register (and clears the internal
+/// error state), and the following read returns them. Reading without writing first returns
+/// stale errors.
+pub fn read_and_clear_errors<M: Mode>(apic: &xApic<M>) -> ErrorStatus {
+    apic.clear_error_status();
+    apic.get_error_status()
+}
+
+/// Programs the error local vector to deliver `vector` when the local APIC detects an error,
+/// discarding any errors detected beforehand.
+pub fn enable_error_interrupt<M: Mode>(apic: &xApic<M>, vector: u8) {
+    read_and_clear_errors(apic);
+
+    let mut error_vector = apic.get_error_vector();
+    error_vector.set_vector(vector);
+    error_vector.set_masked(false);
+    apic.set_error_vector(error_vector);
+}
+
+impl ErrorStatus {
+    /// The human-readable name of a single error flag, or `None` if `self` is not exactly one
+    /// known flag.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            Self::SEND_CHECKSUM_ERROR => "send checksum error",
+            Self::RECEIVE_CHECKSUM_ERROR => "receive checksum error",
+            Self::SEND_ACCEPT_ERROR => "send accept error",
+            Self::RECEIVE_ACCEPT_ERROR => "receive accept error",
+            Self::REDIRECTABLE_IPI => "redirectable IPI",
+            Self::SENT_ILLEGAL_VECTOR => "send illegal vector",
+            Self::RECEIVED_ILLEGAL_VECTOR => "receive illegal vector",
+            Self::ILLEGAL_REGISTER_ADDRESS => "illegal register address",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+}
+
+impl core::fmt::Display for ErrorStatus {
+    /// Names each error flag that is set, separated by commas, or "no errors". Reserved bits
+    /// that are set are written last, in hexadecimal.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no errors");
+        }
+
+        let mut separator = "";
+        for (_, error) in self.iter_names() {
+            write!(f, "{separator}{}", error.name().unwrap())?;
+            separator = ", ";
+        }
+
+        let reserved = self.bits() & !Self::all().bits();
+        if reserved != 0 {
+            write!(f, "{separator}{reserved:#X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the occurrences of each error flag.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCounters([u64; 8]);
+
+impl ErrorCounters {
+    /// Creates counters with every count at zero.
+    pub const fn new() -> Self {
+        Self([0; 8])
+    }
+
+    /// Counts one occurrence of each error flag set in `errors`. Reserved bits are ignored.
+    pub fn record(&mut self, errors: ErrorStatus) {
+        for (_, error) in errors.iter_names() {
+            self.0[error.bits().trailing_zeros() as usize] += 1;
+        }
+    }
+
+    /// The number of occurrences of the single error flag `error`, or zero for a reserved bit.
+    pub fn get(&self, error: ErrorStatus) -> u64 {
+        assert!(
+            error.bits().count_ones() == 1,
+            "counters are kept for single error flags"
+        );
+
+        self.0
+            .get(error.bits().trailing_zeros() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The total number of errors recorded.
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Iterates the error flags with a non-zero count, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (ErrorStatus, u64)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(bit, &count)| (ErrorStatus::from_bits_retain(1 << bit), count))
+    }
+}
+
+/// Handler for the error local vector: reads and clears the errors, counts them, and passes
+/// them to an optional hook.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ErrorHandler {
+    counters: ErrorCounters,
+    hook: Option<fn(ErrorStatus)>,
+}
+
+impl ErrorHandler {
+    /// Creates a handler without a hook.
+    pub const fn new() -> Self {
+        Self {
+            counters: ErrorCounters::new(),
+            hook: None,
+        }
+    }
+
+    /// Sets the hook called with the errors of every error interrupt, such as to log them.
+    pub fn set_hook(&mut self, hook: Option<fn(ErrorStatus)>) {
+        self.hook = hook;
+    }
+
+    /// The errors counted so far.
+    pub fn counters(&self) -> &ErrorCounters {
+        &self.counters
+    }
+
+    /// Handles an error interrupt: reads and clears the errors, counts them, calls the hook,
+    /// and signals the end of the interrupt. Returns the errors.
+    pub fn handle_interrupt<M: Mode>(&mut self, apic: &xApic<M>) -> ErrorStatus {
+        let errors = read_and_clear_errors(apic);
+        self.counters.record(errors);
+        if let Some(hook) = self.hook {
+            hook(errors);
+        }
+
+        apic.end_of_interrupt();
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apic::fmt_buffer::FmtBuffer, sim::RegisterFile};
+    use core::fmt::Write;
+
+    #[test]
+    fn names_errors() {
+        let mut buffer = FmtBuffer::<96>::new();
+        write!(
+            buffer,
+            "{}",
+            ErrorStatus::SEND_ACCEPT_ERROR | ErrorStatus::ILLEGAL_REGISTER_ADDRESS
+        )
+        .unwrap();
+        assert_eq!(
+            buffer.as_str(),
+            "send accept error, illegal register address"
+        );
+
+        assert_eq!(ErrorStatus::empty().name(), None);
+
+        let mut buffer = FmtBuffer::<96>::new();
+        write!(buffer, "{}", ErrorStatus::from_bits_retain(1 << 2 | 1 << 9)).unwrap();
+        assert_eq!(buffer.as_str(), "send accept error, 0x200");
+    }
+
+    #[test]
+    fn handles_error_interrupts() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+
+        register_file.raise_error(ErrorStatus::RECEIVE_CHECKSUM_ERROR);
+        enable_error_interrupt(&apic, 0xFE);
+        assert_eq!(apic.get_error_vector().get_vector(), 0xFE);
+        assert_eq!(read_and_clear_errors(&apic), ErrorStatus::empty());
+
+        let mut handler = ErrorHandler::new();
+        register_file.raise_error(ErrorStatus::SEND_ACCEPT_ERROR);
+        assert_eq!(
+            handler.handle_interrupt(&apic),
+            ErrorStatus::SEND_ACCEPT_ERROR
+        );
+        register_file.raise_error(ErrorStatus::SEND_ACCEPT_ERROR | ErrorStatus::REDIRECTABLE_IPI);
+        handler.handle_interrupt(&apic);
+
+        let counters = handler.counters();
+        assert_eq!(counters.get(ErrorStatus::SEND_ACCEPT_ERROR), 2);
+        assert_eq!(counters.get(ErrorStatus::REDIRECTABLE_IPI), 1);
+        assert_eq!(counters.total(), 3);
+        assert_eq!(counters.iter().count(), 2);
+
+        let mut counters = ErrorCounters::new();
+        counters.record(ErrorStatus::from_bits_retain(1 << 2 | 1 << 9));
+        assert_eq!(counters.get(ErrorStatus::SEND_ACCEPT_ERROR), 1);
+        assert_eq!(counters.get(ErrorStatus::from_bits_retain(1 << 9)), 0);
+        assert_eq!(counters.total(), 1);
+    }
+}
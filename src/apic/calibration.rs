@@ -0,0 +1,560 @@
+use core::time::Duration;
+
+use crate::{
+    Mode, TimerDivideConfiguration, hpet::Hpet, local_vector::TimerMode, port::PortIo, xApic,
+};
+use bit_field::BitField;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// A clock of known frequency, used as a reference to measure the local APIC timer.
+pub trait ReferenceClock {
+    /// Busy-waits for approximately `duration`, and returns the time that actually elapsed
+    /// according to the reference clock.
+    fn wait(&mut self, duration: Duration) -> Duration;
+}
+
+/// Errors that can occur while calibrating the local APIC timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationError {
+    /// The timer did not count down during the measurement.
+    TimerStopped,
+
+    /// The timer reached zero during the measurement, so the elapsed count is unknown.
+    TimerExpired,
+
+    /// The reference clock reported that no time elapsed.
+    NoTimeElapsed,
+}
+
+/// Tick frequency of the local APIC timer, for a particular divide configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerFrequency {
+    hz: u64,
+    divide_configuration: TimerDivideConfiguration,
+}
+
+impl TimerFrequency {
+    /// Creates a timer frequency of `hz` ticks per second under `divide_configuration`.
+    ///
+    /// A frequency of zero describes a stopped timer, for which no count ever elapses.
+    pub fn new(hz: u64, divide_configuration: TimerDivideConfiguration) -> Self {
+        Self {
+            hz,
+            divide_configuration,
+        }
+    }
+
+    /// Ticks per second.
+    pub fn hz(&self) -> u64 {
+        self.hz
+    }
+
+    /// The divide configuration the frequency applies to.
+    pub fn divide_configuration(&self) -> TimerDivideConfiguration {
+        self.divide_configuration
+    }
+
+    /// The frequency of the timer under another divide configuration.
+    pub fn with_divide_configuration(
+        &self,
+        divide_configuration: TimerDivideConfiguration,
+    ) -> Self {
+        let base_hz = self.hz * u64::from(self.divide_configuration.divisor());
+
+        Self {
+            hz: base_hz / u64::from(divide_configuration.divisor()),
+            divide_configuration,
+        }
+    }
+
+    /// The number of ticks that elapse in `duration`.
+    pub fn duration_to_counts(&self, duration: Duration) -> u64 {
+        u64::try_from(duration.as_nanos() * u128::from(self.hz) / NANOS_PER_SECOND)
+            .unwrap_or(u64::MAX)
+    }
+
+    /// The time it takes for `counts` ticks to elapse, saturating at the longest
+    /// representable duration (which a stopped timer always takes).
+    pub fn counts_to_duration(&self, counts: u64) -> Duration {
+        let nanos = (u128::from(counts) * NANOS_PER_SECOND)
+            .checked_div(u128::from(self.hz))
+            .unwrap_or(u128::MAX);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// The longest duration that fits in the 32-bit initial count register.
+    pub fn max_duration(&self) -> Duration {
+        self.counts_to_duration(u64::from(u32::MAX))
+    }
+}
+
+/// Determines the local APIC timer frequency from the processor's core crystal clock, as
+/// enumerated by `CPUID` leaf `0x15` (or the bus frequency of leaf `0x16`), without any
+/// measurement. The timer's base clock is the core crystal clock on processors that
+/// enumerate it.
+///
+/// Returns `None` if the processor does not enumerate either frequency. Hypervisors
+/// commonly do not.
+pub fn cpuid_timer_frequency(
+    divide_configuration: TimerDivideConfiguration,
+) -> Option<TimerFrequency> {
+    let max_leaf = core::arch::x86_64::__cpuid(0x0).eax;
+
+    let crystal_hz = (max_leaf >= 0x15)
+        .then(|| core::arch::x86_64::__cpuid(0x15).ecx)
+        .filter(|&hz| hz != 0)
+        .map(u64::from);
+
+    let bus_hz = || {
+        (max_leaf >= 0x16)
+            .then(|| core::arch::x86_64::__cpuid(0x16).ecx.get_bits(..16))
+            .filter(|&mhz| mhz != 0)
+            .map(|mhz| u64::from(mhz) * 1_000_000)
+    };
+
+    let base_hz = crystal_hz.or_else(bus_hz)?;
+
+    Some(
+        TimerFrequency::new(base_hz, TimerDivideConfiguration::DIVIDE_1)
+            .with_divide_configuration(divide_configuration),
+    )
+}
+
+/// Measures the local APIC timer frequency under `divide_configuration`, by counting the
+/// ticks that elapse while `clock` waits for `duration`.
+///
+/// The timer is masked and stopped for the measurement, and its local vector table entry is
+/// restored afterwards. The divide configuration is left as `divide_configuration`.
+pub fn calibrate<M: Mode>(
+    apic: &xApic<M>,
+    clock: &mut impl ReferenceClock,
+    divide_configuration: TimerDivideConfiguration,
+    duration: Duration,
+) -> Result<TimerFrequency, CalibrationError> {
+    let timer_vector = apic.get_timer_vector();
+
+    let mut calibration_vector = timer_vector;
+    calibration_vector.set_masked(true);
+    calibration_vector.set_mode(TimerMode::OneShot);
+    apic.set_timer_vector(calibration_vector);
+    apic.set_timer_divide_configuration(divide_configuration);
+
+    apic.set_timer_initial_count(u32::MAX);
+    let elapsed = clock.wait(duration);
+    let current_count = apic.get_timer_current_count();
+
+    apic.set_timer_initial_count(0);
+    apic.set_timer_vector(timer_vector);
+
+    if current_count == 0 {
+        return Err(CalibrationError::TimerExpired);
+    }
+
+    let counts = u64::from(u32::MAX - current_count);
+    if counts == 0 {
+        return Err(CalibrationError::TimerStopped);
+    }
+
+    if elapsed.is_zero() {
+        return Err(CalibrationError::NoTimeElapsed);
+    }
+
+    let hz = u128::from(counts) * NANOS_PER_SECOND / elapsed.as_nanos();
+
+    Ok(TimerFrequency::new(
+        u64::try_from(hz).unwrap(),
+        divide_configuration,
+    ))
+}
+
+/// Measures the local APIC timer frequency under every divide configuration.
+///
+/// The results are in the order of [`TimerDivideConfiguration::ALL`].
+pub fn calibrate_all<M: Mode>(
+    apic: &xApic<M>,
+    clock: &mut impl ReferenceClock,
+    duration: Duration,
+) -> Result<[TimerFrequency; 8], CalibrationError> {
+    let mut frequencies = [TimerFrequency::new(0, TimerDivideConfiguration::DIVIDE_1);
+        TimerDivideConfiguration::ALL.len()];
+
+    for (frequency, divide_configuration) in
+        frequencies.iter_mut().zip(TimerDivideConfiguration::ALL)
+    {
+        *frequency = calibrate(apic, clock, divide_configuration, duration)?;
+    }
+
+    Ok(frequencies)
+}
+
+/// Channel 2 of the legacy 8254 programmable interval timer, gated through port `0x61`.
+pub struct Pit<P: PortIo>(P);
+
+impl<P: PortIo> Pit<P> {
+    /// Frequency of the programmable interval timer input clock, in Hz.
+    pub const FREQUENCY: u64 = 1_193_182;
+
+    const CHANNEL_2_DATA: u16 = 0x42;
+    const COMMAND: u16 = 0x43;
+    const CHANNEL_2_GATE: u16 = 0x61;
+
+    /// Creates a new reference clock over the programmable interval timer.
+    ///
+    /// # Safety
+    ///
+    /// No other context may use channel 2 of the programmable interval timer, or the PC
+    /// speaker, while the reference clock exists.
+    pub unsafe fn new(ports: P) -> Self {
+        Self(ports)
+    }
+
+    /// Counts down `count` ticks of the programmable interval timer.
+    fn count_down(&mut self, count: u16) {
+        // Safety: The constructor requires exclusive access to channel 2 and its gate.
+        unsafe {
+            // Raise the channel 2 gate, and disconnect the PC speaker.
+            let gate = self.0.read_u8(Self::CHANNEL_2_GATE);
+            self.0.write_u8(Self::CHANNEL_2_GATE, (gate & !0b10) | 0b01);
+
+            // Channel 2, low byte then high byte, mode 0 (interrupt on terminal count).
+            self.0.write_u8(Self::COMMAND, 0b1011_0000);
+            let [low, high] = count.to_le_bytes();
+            self.0.write_u8(Self::CHANNEL_2_DATA, low);
+            self.0.write_u8(Self::CHANNEL_2_DATA, high);
+
+            // Output 2 is raised at terminal count.
+            while !self.0.read_u8(Self::CHANNEL_2_GATE).get_bit(5) {
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
+
+impl<P: PortIo> ReferenceClock for Pit<P> {
+    fn wait(&mut self, duration: Duration) -> Duration {
+        let mut remaining = duration.as_nanos() * u128::from(Self::FREQUENCY) / NANOS_PER_SECOND;
+        let mut ticks = 0u64;
+
+        while remaining > 0 {
+            let count = u16::try_from(remaining).unwrap_or(u16::MAX);
+            self.count_down(count);
+
+            remaining -= u128::from(count);
+            ticks += u64::from(count);
+        }
+
+        Duration::from_nanos(
+            u64::try_from(u128::from(ticks) * NANOS_PER_SECOND / u128::from(Self::FREQUENCY))
+                .unwrap(),
+        )
+    }
+}
+
+/// The ACPI power management timer, a free-running counter at 3.579545 MHz.
+pub struct PmTimer<P: PortIo> {
+    ports: P,
+    port: u16,
+    mask: u32,
+}
+
+impl<P: PortIo> PmTimer<P> {
+    /// Frequency of the power management timer, in Hz.
+    pub const FREQUENCY: u64 = 3_579_545;
+
+    /// Creates a new reference clock over the power management timer at `port`, as found in
+    /// the `PM_TMR_BLK` field of the FADT. `extended` corresponds to the FADT `TMR_VAL_EXT`
+    /// flag, which indicates the counter is 32 bits wide instead of 24.
+    ///
+    /// # Safety
+    ///
+    /// `port` must be the power management timer; reading other ports can have arbitrary
+    /// side effects.
+    pub unsafe fn new(ports: P, port: u16, extended: bool) -> Self {
+        Self {
+            ports,
+            port,
+            mask: if extended { u32::MAX } else { 0x00FF_FFFF },
+        }
+    }
+
+    fn read(&mut self) -> u32 {
+        // Safety: The constructor requires that the port is the power management timer, and
+        //         reading it has no side effects.
+        unsafe { self.ports.read_u32(self.port) & self.mask }
+    }
+}
+
+impl<P: PortIo> ReferenceClock for PmTimer<P> {
+    /// Waits are not limited by the counter wrapping around (every 4.69 seconds for the 24-bit
+    /// counter), as the ticks between consecutive reads are accumulated.
+    fn wait(&mut self, duration: Duration) -> Duration {
+        let target =
+            u64::try_from(duration.as_nanos() * u128::from(Self::FREQUENCY) / NANOS_PER_SECOND)
+                .unwrap_or(u64::MAX);
+        let mut previous = self.read();
+        let mut ticks = 0u64;
+
+        while ticks < target {
+            let current = self.read();
+            ticks += u64::from(current.wrapping_sub(previous) & self.mask);
+            previous = current;
+            core::hint::spin_loop();
+        }
+
+        Duration::from_nanos(
+            u64::try_from(u128::from(ticks) * NANOS_PER_SECOND / u128::from(Self::FREQUENCY))
+                .unwrap(),
+        )
+    }
+}
+
+/// The main counter of a high precision event timer. The counter is started if it is halted.
+pub struct HpetClock<'a, 'b> {
+    hpet: &'b mut Hpet<'a>,
+    period: u32,
+    mask: u64,
+}
+
+impl<'a, 'b> HpetClock<'a, 'b> {
+    /// Creates a new reference clock over the main counter of `hpet`, or returns `None` if
+    /// the HPET reports an invalid period (see [`Hpet::frequency`]).
+    pub fn new(hpet: &'b mut Hpet<'a>) -> Option<Self> {
+        hpet.frequency()?;
+
+        if !hpet.get_enabled() {
+            hpet.set_enabled(true);
+        }
+
+        let period = hpet.period_femtoseconds();
+        // The upper 32 bits of a 32-bit counter read as zero.
+        let mask = if hpet.get_capabilities().get_64bit_counter() {
+            u64::MAX
+        } else {
+            u64::from(u32::MAX)
+        };
+
+        Some(Self { hpet, period, mask })
+    }
+
+    fn read(&self) -> u64 {
+        self.hpet.get_main_counter() & self.mask
+    }
+}
+
+impl ReferenceClock for HpetClock<'_, '_> {
+    /// Waits are not limited by a 32-bit counter wrapping around, as the ticks between
+    /// consecutive reads are accumulated.
+    fn wait(&mut self, duration: Duration) -> Duration {
+        const FEMTOS_PER_NANO: u128 = 1_000_000;
+
+        let period = u128::from(self.period);
+        let target =
+            u64::try_from(duration.as_nanos() * FEMTOS_PER_NANO / period).unwrap_or(u64::MAX);
+        let mut previous = self.read();
+        let mut ticks = 0u64;
+
+        while ticks < target {
+            let current = self.read();
+            ticks = ticks.saturating_add(current.wrapping_sub(previous) & self.mask);
+            previous = current;
+            core::hint::spin_loop();
+        }
+
+        Duration::from_nanos(
+            u64::try_from(u128::from(ticks) * period / FEMTOS_PER_NANO).unwrap_or(u64::MAX),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{RegisterFile, sim};
+
+    struct SimulatedClock<'a> {
+        register_file: &'a RegisterFile,
+        base_hz: u64,
+    }
+
+    impl ReferenceClock for SimulatedClock<'_> {
+        fn wait(&mut self, duration: Duration) -> Duration {
+            let divisor = sim::get_timer_divide_configuration(self.register_file).divisor();
+            let ticks = duration.as_nanos() * u128::from(self.base_hz) / NANOS_PER_SECOND;
+            self.register_file
+                .advance_timer(u32::try_from(ticks / u128::from(divisor)).unwrap_or(u32::MAX));
+
+            duration
+        }
+    }
+
+    struct FakePitPorts {
+        gate: u8,
+        commands: usize,
+        polls: usize,
+    }
+
+    impl PortIo for FakePitPorts {
+        unsafe fn read_u8(&mut self, port: u16) -> u8 {
+            assert_eq!(port, 0x61);
+
+            // Output 2 is raised after a few polls.
+            self.polls += 1;
+            if self.polls.is_multiple_of(4) {
+                self.gate | (1 << 5)
+            } else {
+                self.gate
+            }
+        }
+
+        unsafe fn write_u8(&mut self, port: u16, value: u8) {
+            match port {
+                0x61 => self.gate = value,
+                0x43 => {
+                    assert_eq!(value, 0b1011_0000);
+                    self.commands += 1;
+                }
+                _ => assert_eq!(port, 0x42),
+            }
+        }
+
+        unsafe fn read_u32(&mut self, _: u16) -> u32 {
+            unreachable!()
+        }
+    }
+
+    /// A power management timer that advances by `step` ticks per read, with the reserved
+    /// upper byte of a 24-bit counter set.
+    struct FakePmTimerPorts {
+        counter: u32,
+        step: u32,
+    }
+
+    impl PortIo for FakePmTimerPorts {
+        unsafe fn read_u8(&mut self, _: u16) -> u8 {
+            unreachable!()
+        }
+
+        unsafe fn write_u8(&mut self, _: u16, _: u8) {
+            unreachable!()
+        }
+
+        unsafe fn read_u32(&mut self, port: u16) -> u32 {
+            assert_eq!(port, 0x408);
+
+            self.counter = self.counter.wrapping_add(self.step) & 0x00FF_FFFF;
+            self.counter | 0xFF00_0000
+        }
+    }
+
+    #[test]
+    fn divisors() {
+        let divisors = TimerDivideConfiguration::ALL.map(|configuration| configuration.divisor());
+        assert_eq!(divisors, [1, 2, 4, 8, 16, 32, 64, 128]);
+    }
+
+    #[test]
+    fn frequency_conversions() {
+        let frequency = TimerFrequency::new(25_000_000, TimerDivideConfiguration::DIVIDE_1);
+
+        assert_eq!(
+            frequency.duration_to_counts(Duration::from_millis(1)),
+            25_000
+        );
+        assert_eq!(
+            frequency.counts_to_duration(25_000),
+            Duration::from_millis(1)
+        );
+
+        let divided = frequency.with_divide_configuration(TimerDivideConfiguration::DIVIDE_16);
+        assert_eq!(divided.hz(), 1_562_500);
+        assert_eq!(
+            divided.duration_to_counts(Duration::from_secs(2)),
+            3_125_000
+        );
+
+        let stopped = TimerFrequency::new(0, TimerDivideConfiguration::DIVIDE_1);
+        assert_eq!(stopped.duration_to_counts(Duration::from_secs(1)), 0);
+        assert_eq!(stopped.max_duration(), Duration::from_nanos(u64::MAX));
+    }
+
+    #[test]
+    fn calibrates_against_reference_clock() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let mut clock = SimulatedClock {
+            register_file: &register_file,
+            base_hz: 128_000_000,
+        };
+
+        let frequency = calibrate(
+            &apic,
+            &mut clock,
+            TimerDivideConfiguration::DIVIDE_4,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        assert_eq!(frequency.hz(), 32_000_000);
+        assert!(apic.get_timer_vector().get_masked());
+        assert_eq!(apic.get_timer_initial_count(), 0);
+
+        let frequencies = calibrate_all(&apic, &mut clock, Duration::from_millis(10)).unwrap();
+        for frequency in frequencies {
+            assert_eq!(
+                frequency.hz(),
+                128_000_000 / u64::from(frequency.divide_configuration().divisor())
+            );
+        }
+    }
+
+    #[test]
+    fn calibration_detects_expired_timer() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let mut clock = SimulatedClock {
+            register_file: &register_file,
+            base_hz: 1_000_000_000,
+        };
+
+        assert_eq!(
+            calibrate(
+                &apic,
+                &mut clock,
+                TimerDivideConfiguration::DIVIDE_1,
+                Duration::from_secs(5),
+            ),
+            Err(CalibrationError::TimerExpired)
+        );
+    }
+
+    #[test]
+    fn pit_splits_long_waits() {
+        let ports = FakePitPorts {
+            gate: 0b10,
+            commands: 0,
+            polls: 0,
+        };
+        // Safety: The programmable interval timer is simulated.
+        let mut pit = unsafe { Pit::new(ports) };
+
+        let elapsed = pit.wait(Duration::from_millis(100));
+        assert_eq!(pit.0.commands, 2);
+        assert_eq!(pit.0.gate & 0b11, 0b01);
+        assert!(elapsed.abs_diff(Duration::from_millis(100)) < Duration::from_micros(1));
+    }
+
+    #[test]
+    fn pm_timer_waits_across_wraparound() {
+        let ports = FakePmTimerPorts {
+            counter: 0x00F0_0000,
+            step: 1_000_000,
+        };
+        // Safety: The power management timer is simulated.
+        let mut pm_timer = unsafe { PmTimer::new(ports, 0x408, false) };
+
+        // Longer than the 4.69 second period of the 24-bit counter.
+        let elapsed = pm_timer.wait(Duration::from_secs(10));
+        assert!(elapsed >= Duration::from_secs(10));
+        assert!(elapsed < Duration::from_secs(11));
+    }
+}
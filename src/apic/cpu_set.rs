@@ -0,0 +1,209 @@
+use bit_field::BitField;
+
+use crate::ApicError;
+
+/// A set of processors, identified by their APIC IDs.
+///
+/// The set holds APIC IDs below `64 * N`; the default capacity covers the 8-bit APIC IDs of
+/// xAPIC mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuSet<const N: usize = 4>([u64; N]);
+
+impl<const N: usize> CpuSet<N> {
+    /// The number of APIC IDs the set can hold.
+    pub const CAPACITY: u32 = (N as u32) * 64;
+
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self([0; N])
+    }
+
+    /// Creates a set of every APIC ID the set can hold. When sending interrupts, a full set
+    /// addresses every processor in the system (see [`crate::xApic::send_ipi_many`]).
+    pub const fn all() -> Self {
+        Self([u64::MAX; N])
+    }
+
+    fn position(apic_id: u32) -> (usize, usize) {
+        assert!(
+            apic_id < Self::CAPACITY,
+            "APIC ID exceeds the capacity of the CPU set"
+        );
+
+        let apic_id = usize::try_from(apic_id).unwrap();
+        (apic_id / 64, apic_id % 64)
+    }
+
+    /// Whether `apic_id` is in the set.
+    pub fn contains(&self, apic_id: u32) -> bool {
+        if apic_id >= Self::CAPACITY {
+            return false;
+        }
+
+        let (word, bit) = Self::position(apic_id);
+        self.0[word].get_bit(bit)
+    }
+
+    /// Adds `apic_id` to the set.
+    pub fn insert(&mut self, apic_id: u32) {
+        let (word, bit) = Self::position(apic_id);
+        self.0[word].set_bit(bit, true);
+    }
+
+    /// Adds `apic_id` to the set, or returns an error if it exceeds the capacity of the set.
+    pub fn try_insert(&mut self, apic_id: u32) -> Result<(), ApicError> {
+        if apic_id >= Self::CAPACITY {
+            return Err(ApicError::OutOfRange {
+                field: "APIC ID",
+                value: apic_id,
+            });
+        }
+
+        self.insert(apic_id);
+
+        Ok(())
+    }
+
+    /// Removes `apic_id` from the set.
+    pub fn remove(&mut self, apic_id: u32) {
+        if apic_id < Self::CAPACITY {
+            let (word, bit) = Self::position(apic_id);
+            self.0[word].set_bit(bit, false);
+        }
+    }
+
+    /// Whether the set contains no processors.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    /// Whether the set contains every APIC ID it can hold.
+    pub fn is_full(&self) -> bool {
+        self.0.iter().all(|&word| word == u64::MAX)
+    }
+
+    /// The number of processors in the set.
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Iterates the APIC IDs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + Clone + '_ {
+        self.0.iter().enumerate().flat_map(|(index, &word)| {
+            (0..64)
+                .filter(move |&bit| word.get_bit(bit))
+                .map(move |bit| u32::try_from((index * 64) + bit).unwrap())
+        })
+    }
+}
+
+impl<const N: usize> Default for CpuSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FromIterator<u32> for CpuSet<N> {
+    fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+        let mut cpu_set = Self::new();
+        for apic_id in iter {
+            cpu_set.insert(apic_id);
+        }
+
+        cpu_set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::RegisterFile;
+
+    #[test]
+    fn set_operations() {
+        let mut cpu_set = CpuSet::<2>::new();
+        assert!(cpu_set.is_empty());
+
+        cpu_set.insert(0);
+        cpu_set.insert(65);
+        cpu_set.insert(3);
+        assert_eq!(cpu_set.len(), 3);
+        assert!(cpu_set.contains(65));
+        assert!(!cpu_set.contains(200));
+        assert!(cpu_set.iter().eq([0, 3, 65]));
+
+        cpu_set.remove(3);
+        assert_eq!(cpu_set, [0, 65].into_iter().collect());
+
+        assert_eq!(cpu_set.try_insert(127), Ok(()));
+        assert_eq!(
+            cpu_set.try_insert(128),
+            Err(ApicError::OutOfRange {
+                field: "APIC ID",
+                value: 128
+            })
+        );
+    }
+
+    #[test]
+    fn multicast_uses_shorthands() {
+        let register_file = RegisterFile::new(1, 0x0005_0014);
+        let apic = register_file.apic();
+
+        apic.send_ipi_many(&CpuSet::<4>::all(), 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 1);
+        assert_eq!(
+            register_file
+                .read(crate::Register::INTERRUPT_COMMAND_LOW)
+                .get_bits(18..20),
+            0b10
+        );
+
+        let mut others = CpuSet::<4>::all();
+        others.remove(1);
+        apic.send_ipi_many(&others, 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 2);
+        assert_eq!(
+            register_file
+                .read(crate::Register::INTERRUPT_COMMAND_LOW)
+                .get_bits(18..20),
+            0b11
+        );
+
+        // A set of every online processor is not a broadcast, as the set cannot tell whether
+        // other processors exist.
+        let online: CpuSet = [0, 1, 2, 3].into_iter().collect();
+        apic.send_ipi_many(&online, 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 6);
+
+        let subset: CpuSet = [0, 3].into_iter().collect();
+        apic.send_ipi_many(&subset, 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 8);
+        assert_eq!(
+            register_file.read(crate::Register::INTERRUPT_COMMAND_HIGH),
+            0x0300_0000
+        );
+
+        apic.send_ipi_many(&CpuSet::<4>::new(), 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 8);
+    }
+
+    #[test]
+    fn multicast_waits_for_delivery() {
+        let register_file = RegisterFile::new(1, 0x0005_0014);
+        let apic = register_file.apic();
+        let subset: CpuSet = [0, 2, 3].into_iter().collect();
+
+        register_file.set_delivery_latency(3);
+        apic.send_ipi_many(&subset, 0x40).unwrap();
+        assert_eq!(register_file.interrupts_sent(), 3);
+
+        register_file.set_delivery_latency(usize::MAX);
+        assert_eq!(
+            apic.send_ipi_many(&subset, 0x40),
+            Err(crate::ipi::IpiError::DeliveryTimeout)
+        );
+        // The first command is sent once the previous one is accepted, and never accepted.
+        assert_eq!(register_file.interrupts_sent(), 4);
+    }
+}
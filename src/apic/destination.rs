@@ -0,0 +1,308 @@
+use bit_field::BitField;
+
+use crate::ApicError;
+
+/// Specifies how logical destination addresses are interpreted by the local APICs.
+///
+/// In xAPIC mode the model is selected by the destination format register, and software
+/// assigns each local APIC its logical ID. In x2APIC mode the model is always
+/// [`DestinationModel::x2Apic`], and the logical ID is derived from the APIC ID by hardware.
+///
+/// The logical ID helpers in this module assign xAPIC logical IDs by the following convention:
+/// - Flat: APIC ID `n` (below 8) has the logical ID `1 << n`.
+/// - Cluster: APIC ID `n` (below 60) is member `n % 4` of cluster `n / 4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestinationModel {
+    /// The 8-bit logical destination is a bitmask, addressing up to 8 local APICs.
+    Flat,
+
+    /// The upper 4 bits of the 8-bit logical destination select a cluster (0xF addresses all
+    /// clusters), and the lower 4 bits are a bitmask of processors within the cluster.
+    Cluster,
+
+    /// The upper 16 bits of the 32-bit logical destination select a cluster, and the lower 16
+    /// bits are a bitmask of processors within the cluster.
+    x2Apic,
+}
+
+impl DestinationModel {
+    /// Splits the logical ID of `apic_id` into its cluster and member bitmask.
+    fn split(self, apic_id: u32) -> Option<(u32, u32)> {
+        match self {
+            Self::Flat if apic_id < 8 => Some((0, 1 << apic_id)),
+            Self::Cluster if apic_id < 60 => Some((apic_id / 4, 1 << (apic_id % 4))),
+            Self::x2Apic if apic_id < (1 << 20) => Some((apic_id >> 4, 1 << (apic_id & 0xF))),
+            _ => None,
+        }
+    }
+
+    /// Composes a logical destination from a cluster and member bitmask.
+    fn compose(self, cluster: u32, members: u32) -> u32 {
+        match self {
+            Self::Flat => members,
+            Self::Cluster => (cluster << 4) | members,
+            Self::x2Apic => (cluster << 16) | members,
+        }
+    }
+
+    /// The logical ID of the local APIC with `apic_id`, or `None` if it cannot be addressed
+    /// logically under this model.
+    pub fn logical_id(self, apic_id: u32) -> Option<u32> {
+        self.split(apic_id)
+            .map(|(cluster, members)| self.compose(cluster, members))
+    }
+}
+
+/// Destination format register value. Only present in xAPIC mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DestinationFormat(pub(crate) u32);
+
+impl DestinationFormat {
+    /// Creates a destination format selecting `model`.
+    pub fn new(model: DestinationModel) -> Self {
+        let model = match model {
+            DestinationModel::Flat => 0b1111,
+            DestinationModel::Cluster => 0b0000,
+            DestinationModel::x2Apic => {
+                panic!("the x2APIC destination model cannot be selected in xAPIC mode")
+            }
+        };
+
+        // Bits 0..28 are reserved, and must be written as ones.
+        Self(*0x0FFF_FFFFu32.set_bits(28..32, model))
+    }
+
+    /// Creates a destination format selecting `model`, or returns an error for the x2APIC
+    /// model, which has no destination format.
+    pub fn try_new(model: DestinationModel) -> Result<Self, ApicError> {
+        if model == DestinationModel::x2Apic {
+            return Err(ApicError::UnavailableInMode("x2APIC destination model"));
+        }
+
+        Ok(Self::new(model))
+    }
+
+    /// Gets the destination model.
+    pub fn get_model(&self) -> DestinationModel {
+        match self.0.get_bits(28..32) {
+            0b1111 => DestinationModel::Flat,
+            0b0000 => DestinationModel::Cluster,
+            model => panic!("invalid destination model: {model:#06b}"),
+        }
+    }
+
+    /// Gets the destination model, or an error if the register holds a reserved model.
+    pub fn try_get_model(&self) -> Result<DestinationModel, ApicError> {
+        match self.0.get_bits(28..32) {
+            0b1111 => Ok(DestinationModel::Flat),
+            0b0000 => Ok(DestinationModel::Cluster),
+            value => Err(ApicError::ReservedEncoding {
+                field: "destination model",
+                value,
+            }),
+        }
+    }
+}
+
+/// Logical destination register value: the logical ID of the local APIC.
+///
+/// In xAPIC mode the logical ID is 8 bits wide and is assigned by software. In x2APIC mode it
+/// is 32 bits wide, and is read-only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalDestination(pub(crate) u32);
+
+impl LocalDestination {
+    /// Creates a logical destination register value with the provided `logical_id`.
+    pub fn new(logical_id: u32) -> Self {
+        Self(logical_id)
+    }
+
+    /// Creates a logical destination register value with the logical ID of `apic_id` under
+    /// `model`, or `None` if it cannot be addressed logically under `model`.
+    pub fn for_apic_id(model: DestinationModel, apic_id: u32) -> Option<Self> {
+        model.logical_id(apic_id).map(Self)
+    }
+
+    /// Gets the logical ID of the local APIC.
+    pub fn get_logical_id(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether a local APIC with this logical ID accepts interrupts sent in logical destination
+    /// mode to `destination`, under `model`.
+    pub fn accepts(&self, model: DestinationModel, destination: u32) -> bool {
+        match model {
+            DestinationModel::Flat => (self.0 & destination) != 0,
+
+            DestinationModel::Cluster => {
+                let cluster = destination.get_bits(4..8);
+                (cluster == 0xF || cluster == self.0.get_bits(4..8))
+                    && (self.0.get_bits(0..4) & destination.get_bits(0..4)) != 0
+            }
+
+            DestinationModel::x2Apic => {
+                destination == u32::MAX
+                    || (destination.get_bits(16..32) == self.0.get_bits(16..32)
+                        && (self.0.get_bits(0..16) & destination.get_bits(0..16)) != 0)
+            }
+        }
+    }
+}
+
+/// Groups `apic_ids` into logical destinations under `model`, so that every APIC ID is
+/// addressed with as few interrupt commands as possible.
+///
+/// Each destination addresses one cluster; consecutive APIC IDs of the same cluster are
+/// merged. The minimal number of destinations is produced when `apic_ids` are in ascending
+/// order. APIC IDs that cannot be addressed logically under `model` are yielded as errors.
+///
+/// The destinations are meant for [`crate::InterruptDestination::Processor`], with
+/// [`crate::InterruptDestinationMode::Logical`].
+pub fn logical_destinations<I: IntoIterator<Item = u32>>(
+    model: DestinationModel,
+    apic_ids: I,
+) -> LogicalDestinations<I::IntoIter> {
+    LogicalDestinations {
+        model,
+        apic_ids: apic_ids.into_iter(),
+        pending: None,
+    }
+}
+
+/// Iterator returned by [`logical_destinations`].
+#[derive(Debug, Clone)]
+pub struct LogicalDestinations<I: Iterator<Item = u32>> {
+    model: DestinationModel,
+    apic_ids: I,
+    pending: Option<u32>,
+}
+
+impl<I: Iterator<Item = u32>> Iterator for LogicalDestinations<I> {
+    type Item = Result<u32, u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.pending.take().or_else(|| self.apic_ids.next())?;
+        let Some((cluster, mut members)) = self.model.split(first) else {
+            return Some(Err(first));
+        };
+
+        for apic_id in self.apic_ids.by_ref() {
+            match self.model.split(apic_id) {
+                Some((next_cluster, next_members)) if next_cluster == cluster => {
+                    members |= next_members;
+                }
+
+                _ => {
+                    self.pending = Some(apic_id);
+                    break;
+                }
+            }
+        }
+
+        Some(Ok(self.model.compose(cluster, members)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::RegisterFile;
+
+    #[test]
+    fn logical_ids() {
+        assert_eq!(DestinationModel::Flat.logical_id(3), Some(0b1000));
+        assert_eq!(DestinationModel::Flat.logical_id(8), None);
+        assert_eq!(DestinationModel::Cluster.logical_id(6), Some(0x14));
+        assert_eq!(DestinationModel::Cluster.logical_id(60), None);
+        assert_eq!(DestinationModel::x2Apic.logical_id(0x25), Some(0x0002_0020));
+
+        let format = DestinationFormat::new(DestinationModel::Cluster);
+        assert_eq!(format.0, 0x0FFF_FFFF);
+        assert_eq!(format.get_model(), DestinationModel::Cluster);
+        assert_eq!(
+            DestinationFormat::new(DestinationModel::Flat).get_model(),
+            DestinationModel::Flat
+        );
+
+        assert_eq!(
+            DestinationFormat(0x5FFF_FFFF).try_get_model(),
+            Err(ApicError::ReservedEncoding {
+                field: "destination model",
+                value: 0b0101,
+            })
+        );
+        assert!(DestinationFormat::try_new(DestinationModel::x2Apic).is_err());
+    }
+
+    #[test]
+    fn groups_destinations_by_cluster() {
+        assert!(logical_destinations(DestinationModel::Flat, [0, 2, 7]).eq([Ok(0b1000_0101)]));
+        assert!(
+            logical_destinations(DestinationModel::Cluster, [0, 1, 4, 7, 63]).eq([
+                Ok(0x03),
+                Ok(0x19),
+                Err(63)
+            ])
+        );
+
+        let apic_ids = [0x00, 0x01, 0x0F, 0x10, 0x21, 0x22];
+        let destinations =
+            logical_destinations(DestinationModel::x2Apic, apic_ids).map(Result::unwrap);
+        assert!(
+            destinations
+                .clone()
+                .eq([0x0000_8003, 0x0001_0001, 0x0002_0006])
+        );
+
+        // Every APIC ID is accepted by exactly one destination.
+        for apic_id in apic_ids {
+            let local_destination =
+                LocalDestination::for_apic_id(DestinationModel::x2Apic, apic_id).unwrap();
+            assert_eq!(
+                destinations
+                    .clone()
+                    .filter(|&destination| {
+                        local_destination.accepts(DestinationModel::x2Apic, destination)
+                    })
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn programs_destination_model() {
+        let register_file = RegisterFile::new(9, 0x0005_0014);
+        let apic = register_file.apic();
+
+        assert_eq!(
+            apic.get_destination_format().get_model(),
+            DestinationModel::Flat
+        );
+        assert_eq!(
+            apic.set_destination_model(DestinationModel::Cluster),
+            Ok(())
+        );
+        assert_eq!(
+            apic.get_destination_format().get_model(),
+            DestinationModel::Cluster
+        );
+        assert_eq!(apic.get_local_destination().get_logical_id(), 0x22);
+
+        assert_eq!(
+            apic.set_destination_model(DestinationModel::Flat),
+            Err(ApicError::OutOfRange {
+                field: "logical APIC ID",
+                value: 9
+            })
+        );
+        assert_eq!(
+            apic.set_destination_model(DestinationModel::x2Apic),
+            Err(ApicError::UnavailableInMode("x2APIC destination model"))
+        );
+        assert_eq!(
+            apic.get_destination_format().get_model(),
+            DestinationModel::Cluster
+        );
+    }
+}
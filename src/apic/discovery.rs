@@ -0,0 +1,241 @@
+use super::{get_ia32_apic_base, set_ia32_apic_base};
+use crate::{x1, x2, xApic};
+use bit_field::BitField;
+
+/// Whether the processor has an on-chip local APIC (`CPUID.01H:EDX[9]`).
+pub fn is_apic_supported() -> bool {
+    core::arch::x86_64::__cpuid(0x1).edx.get_bit(9)
+}
+
+/// Whether the processor supports x2APIC mode (`CPUID.01H:ECX[21]`).
+pub fn is_x2apic_supported() -> bool {
+    core::arch::x86_64::__cpuid(0x1).ecx.get_bit(21)
+}
+
+/// Operating state of the local APIC, as selected by the enable (bit 11) and
+/// extended mode (bit 10) flags of the `IA32_APIC_BASE` model-specific register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicState {
+    /// The local APIC is globally disabled.
+    Disabled,
+
+    /// The local APIC is enabled in xAPIC mode, and accessed through memory-mapped registers.
+    xApic,
+
+    /// The local APIC is enabled in x2APIC mode, and accessed through model-specific registers.
+    x2Apic,
+
+    /// Extended mode is set while the local APIC is disabled. This state is never reported by
+    /// conforming hardware, and can not be transitioned to.
+    Invalid,
+}
+
+/// Errors that can occur while changing the operating state of the local APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateTransitionError {
+    /// x2APIC mode was requested, but the processor does not support it.
+    x2ApicUnsupported,
+
+    /// The requested state is not a valid operating state.
+    InvalidState,
+}
+
+/// Value of the `IA32_APIC_BASE` model-specific register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApicBase(u64);
+
+impl ApicBase {
+    /// Reads the `IA32_APIC_BASE` model-specific register of the executing processor.
+    pub fn read() -> Self {
+        Self(get_ia32_apic_base())
+    }
+
+    /// Whether the executing processor is the bootstrap processor.
+    pub fn is_bsp(&self) -> bool {
+        self.0.get_bit(8)
+    }
+
+    /// Current operating state of the local APIC.
+    pub fn state(&self) -> ApicState {
+        match (self.0.get_bit(11), self.0.get_bit(10)) {
+            (false, false) => ApicState::Disabled,
+            (true, false) => ApicState::xApic,
+            (true, true) => ApicState::x2Apic,
+            (false, true) => ApicState::Invalid,
+        }
+    }
+
+    /// Physical base address of the xAPIC register page.
+    pub fn base_address(&self) -> usize {
+        usize::try_from(self.0.get_bits(12..52) << 12).unwrap()
+    }
+
+    /// Gets the raw value of the register.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    fn with_state(self, state: ApicState) -> Self {
+        let mut value = self.0;
+
+        match state {
+            ApicState::Disabled => value.set_bit(11, false).set_bit(10, false),
+            ApicState::xApic => value.set_bit(11, true).set_bit(10, false),
+            ApicState::x2Apic => value.set_bit(11, true).set_bit(10, true),
+            ApicState::Invalid => unreachable!(),
+        };
+
+        Self(value)
+    }
+}
+
+/// Transitions the local APIC of the executing processor to `target`.
+///
+/// Only the state transitions permitted by the IA32 SDM are performed, so reaching `target` may
+/// require passing through intermediate states:
+/// - x2APIC mode can only be entered from xAPIC mode, so a disabled local APIC is first enabled
+///   in xAPIC mode.
+/// - x2APIC mode can only be left by disabling the local APIC, so a transition from x2APIC to
+///   xAPIC mode passes through the disabled state.
+///
+/// # Safety
+///
+/// Disabling the local APIC resets its register state, and changing modes invalidates every
+/// [`xApic`] handle for the previous mode. The caller must ensure that no such handle is used
+/// afterwards, and that no context relies on the previous configuration.
+pub unsafe fn set_apic_state(target: ApicState) -> Result<(), StateTransitionError> {
+    if target == ApicState::Invalid {
+        return Err(StateTransitionError::InvalidState);
+    }
+
+    if target == ApicState::x2Apic && !is_x2apic_supported() {
+        return Err(StateTransitionError::x2ApicUnsupported);
+    }
+
+    let apic_base = ApicBase::read();
+
+    let path: &[ApicState] = match (apic_base.state(), target) {
+        (current, target) if current == target => &[],
+        (ApicState::x2Apic | ApicState::Invalid, ApicState::xApic) => {
+            &[ApicState::Disabled, ApicState::xApic]
+        }
+        (ApicState::Disabled | ApicState::Invalid, ApicState::x2Apic) => {
+            &[ApicState::xApic, ApicState::x2Apic]
+        }
+        (_, ApicState::Disabled) => &[ApicState::Disabled],
+        (_, ApicState::xApic) => &[ApicState::xApic],
+        (_, ApicState::x2Apic) => &[ApicState::x2Apic],
+        (_, ApicState::Invalid) => unreachable!(),
+    };
+
+    for state in path {
+        // Safety: Caller is required to ensure changing the local APIC state is safe.
+        unsafe {
+            set_ia32_apic_base(apic_base.with_state(*state).bits());
+        }
+    }
+
+    Ok(())
+}
+
+/// A local APIC handle for whichever mode the local APIC is operating in.
+pub enum Apic<'a> {
+    x1(xApic<x1::x1<'a>>),
+    x2(xApic<x2::x2>),
+}
+
+impl<'a> Apic<'a> {
+    /// Detects the operating mode of the local APIC of the executing processor, and creates
+    /// a handle for it.
+    ///
+    /// `map_xapic_fn` is only called when the local APIC is in xAPIC mode; it is provided the
+    /// physical base address of the xAPIC register page, and must return a handle to it.
+    ///
+    /// Returns `None` if the processor has no local APIC, or it is disabled.
+    ///
+    /// # Safety
+    ///
+    /// The handle returned by `map_xapic_fn` must map the register page at the provided
+    /// address.
+    pub unsafe fn new(map_xapic_fn: impl FnOnce(usize) -> &'a x1::Mmio) -> Option<Self> {
+        if !is_apic_supported() {
+            return None;
+        }
+
+        let apic_base = ApicBase::read();
+
+        match apic_base.state() {
+            // Safety: `IA32_APIC_BASE` indicates the local APIC is in x2APIC mode.
+            ApicState::x2Apic => Some(Self::x2(unsafe { xApic::new(()) })),
+
+            ApicState::xApic => {
+                let mmio = map_xapic_fn(apic_base.base_address());
+
+                // Safety: `IA32_APIC_BASE` indicates the local APIC is in xAPIC mode, and the
+                //         caller guarantees the mapping is of its register page.
+                Some(Self::x1(unsafe { xApic::new(mmio) }))
+            }
+
+            ApicState::Disabled | ApicState::Invalid => None,
+        }
+    }
+
+    /// Switches the local APIC to x2APIC mode, if it is not already.
+    ///
+    /// # Safety
+    ///
+    /// See [`set_apic_state`].
+    pub unsafe fn into_x2(self) -> Result<xApic<x2::x2>, StateTransitionError> {
+        match self {
+            Self::x2(apic) => Ok(apic),
+
+            Self::x1(_) => {
+                // Safety: Caller is required to uphold the safety requirements.
+                unsafe { set_apic_state(ApicState::x2Apic)? };
+
+                // Safety: The local APIC was just switched to x2APIC mode.
+                Ok(unsafe { xApic::new(()) })
+            }
+        }
+    }
+
+    /// Gets the ID of the local APIC.
+    pub fn get_id(&self) -> u32 {
+        match self {
+            Self::x1(apic) => apic.get_id(),
+            Self::x2(apic) => apic.get_id(),
+        }
+    }
+
+    /// Whether the local APIC is operating in x2APIC mode.
+    pub fn is_x2(&self) -> bool {
+        matches!(self, Self::x2(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apic_base_decoding() {
+        let apic_base = ApicBase(0xFEE0_0900);
+
+        assert!(apic_base.is_bsp());
+        assert_eq!(apic_base.state(), ApicState::xApic);
+        assert_eq!(apic_base.base_address(), crate::xAPIC_BASE_ADDR);
+
+        assert_eq!(ApicBase(0xFEE0_0D00).state(), ApicState::x2Apic);
+        assert_eq!(ApicBase(0xFEE0_0000).state(), ApicState::Disabled);
+        assert_eq!(ApicBase(0xFEE0_0400).state(), ApicState::Invalid);
+    }
+
+    #[test]
+    fn apic_base_state_preserves_other_bits() {
+        let apic_base = ApicBase(0xFEE0_0900).with_state(ApicState::x2Apic);
+        assert_eq!(apic_base.bits(), 0xFEE0_0D00);
+
+        let apic_base = apic_base.with_state(ApicState::Disabled);
+        assert_eq!(apic_base.bits(), 0xFEE0_0100);
+    }
+}
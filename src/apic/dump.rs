@@ -0,0 +1,321 @@
+use bit_field::BitField;
+use core::fmt;
+
+use crate::{
+    ApicSnapshot, ArbitrationPriority, ErrorStatus, InterruptDeliveryMode, Mode, ProcessorPriority,
+    Register, VectorBitmap, Version, xApic,
+};
+
+/// The registers of a local APIC, collected by [`xApic::dump`] for display by a debugger.
+///
+/// The `Display` implementation prints one register per line, with its fields decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApicDump {
+    /// Whether the local APIC is in x2APIC mode.
+    pub extended: bool,
+    pub id: u32,
+    pub version: Version,
+    /// The configuration registers, as saved by [`xApic::save`].
+    pub configuration: ApicSnapshot,
+    /// `None` in x2APIC mode, in which the arbitration priority register does not exist.
+    pub arbitration_priority: Option<ArbitrationPriority>,
+    pub processor_priority: ProcessorPriority,
+    /// The errors latched by the previous write to the error status register; collecting the
+    /// dump does not latch new errors.
+    pub error_status: ErrorStatus,
+    pub in_service: VectorBitmap,
+    pub trigger_mode: VectorBitmap,
+    pub interrupt_request: VectorBitmap,
+    /// Raw value of the low doubleword of the interrupt command register.
+    pub interrupt_command_low: u32,
+    pub timer_current_count: u32,
+}
+
+impl<M: Mode> xApic<M> {
+    /// Collects the registers of the local APIC, without side effects: no errors are latched,
+    /// and the TSC deadline is only read if the timer supports TSC-deadline mode.
+    pub fn dump(&self) -> ApicDump {
+        ApicDump {
+            extended: M::EXTENDED,
+            id: self.get_id(),
+            version: self.get_version(),
+            configuration: self.save(),
+            arbitration_priority: (!M::EXTENDED).then(|| self.get_arbitration_priority()),
+            processor_priority: self.get_processor_priority(),
+            error_status: self.get_error_status(),
+            in_service: self.get_in_service(),
+            trigger_mode: self.get_trigger_mode(),
+            interrupt_request: self.get_interrupt_request(),
+            interrupt_command_low: M::read_register_raw(
+                self.0.clone(),
+                Register::INTERRUPT_COMMAND_LOW,
+            ),
+            timer_current_count: self.get_timer_current_count(),
+        }
+    }
+}
+
+/// Writes `name` and the fields common to every local vector table entry of the raw `value`,
+/// including the delivery mode if the entry has one.
+fn write_local_vector(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    value: u32,
+    has_delivery_mode: bool,
+) -> fmt::Result {
+    write!(
+        f,
+        "{name}: vector {:#04X}, {}, {}",
+        value.get_bits(0..8),
+        if value.get_bit(16) {
+            "masked"
+        } else {
+            "unmasked"
+        },
+        if value.get_bit(12) {
+            "send pending"
+        } else {
+            "idle"
+        },
+    )?;
+
+    if has_delivery_mode {
+        match InterruptDeliveryMode::try_from(value.get_bits(8..11)) {
+            Ok(mode) => write!(f, ", {mode:?}")?,
+            Err(mode) => write!(f, ", reserved delivery mode {mode:#05b}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the fields specific to the local vector table entries of the local interrupt pins.
+fn write_pin(f: &mut fmt::Formatter<'_>, name: &str, value: u32) -> fmt::Result {
+    write_local_vector(f, name, value, true)?;
+    writeln!(
+        f,
+        ", active {}, {}-triggered, remote IRR {}",
+        if value.get_bit(13) { "low" } else { "high" },
+        if value.get_bit(15) { "level" } else { "edge" },
+        u8::from(value.get_bit(14)),
+    )
+}
+
+/// Writes `name` and the vectors set in `bitmap`.
+fn write_vectors(f: &mut fmt::Formatter<'_>, name: &str, bitmap: &VectorBitmap) -> fmt::Result {
+    write!(f, "{name}:")?;
+    if bitmap.is_empty() {
+        return writeln!(f, " none");
+    }
+
+    for vector in bitmap.iter() {
+        write!(f, " {vector:#04X}")?;
+    }
+
+    writeln!(f)
+}
+
+impl fmt::Display for ApicDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let configuration = &self.configuration;
+
+        writeln!(
+            f,
+            "Local APIC {} ({})",
+            self.id,
+            if self.extended { "x2APIC" } else { "xAPIC" }
+        )?;
+        writeln!(
+            f,
+            "Version: {:#04X}, {} LVT entries, EOI broadcast suppression {}",
+            self.version.version(),
+            u16::from(self.version.max_lvt_entry()) + 1,
+            if self.version.can_suppress_eoi_broadcast() {
+                "supported"
+            } else {
+                "unsupported"
+            },
+        )?;
+
+        let task_priority = configuration.task_priority;
+        writeln!(
+            f,
+            "Task priority: class {}, subclass {}",
+            task_priority.get_class(),
+            task_priority.get_subclass()
+        )?;
+        if let Some(arbitration_priority) = self.arbitration_priority {
+            writeln!(
+                f,
+                "Arbitration priority: class {}, subclass {}",
+                arbitration_priority.get_class(),
+                arbitration_priority.get_subclass()
+            )?;
+        }
+        writeln!(
+            f,
+            "Processor priority: class {}, subclass {}",
+            self.processor_priority.get_class(),
+            self.processor_priority.get_subclass()
+        )?;
+
+        write!(
+            f,
+            "Logical destination: {:#X}",
+            configuration.local_destination.get_logical_id()
+        )?;
+        match configuration
+            .destination_format
+            .map(|format| format.try_get_model())
+        {
+            Some(Ok(model)) => writeln!(f, " ({model:?} model)")?,
+            Some(Err(_)) => writeln!(f, " (reserved model)")?,
+            None => writeln!(f)?,
+        }
+
+        let spurious_vector = configuration.spurious_vector;
+        writeln!(
+            f,
+            "Spurious vector: vector {:#04X}, APIC {}, focus processor checking {}, EOI broadcast suppression {}",
+            spurious_vector.get_bits(0..8),
+            if spurious_vector.get_bit(8) {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            if spurious_vector.get_bit(9) {
+                "disabled"
+            } else {
+                "enabled"
+            },
+            if spurious_vector.get_bit(12) {
+                "on"
+            } else {
+                "off"
+            },
+        )?;
+
+        writeln!(f, "Error status: {}", self.error_status)?;
+        write_vectors(f, "In service", &self.in_service)?;
+        write_vectors(f, "Interrupt request", &self.interrupt_request)?;
+        write_vectors(f, "Level-triggered", &self.trigger_mode)?;
+
+        let interrupt_command_low = self.interrupt_command_low;
+        writeln!(
+            f,
+            "Interrupt command: {:#010X}, {}",
+            interrupt_command_low,
+            if interrupt_command_low.get_bit(12) {
+                "send pending"
+            } else {
+                "idle"
+            },
+        )?;
+
+        let timer_vector = configuration.timer_vector;
+        write_local_vector(f, "Timer", u32::from(timer_vector), false)?;
+        match timer_vector.try_get_mode() {
+            Ok(mode) => writeln!(f, ", {mode:?}")?,
+            Err(_) => writeln!(f, ", reserved mode")?,
+        }
+        write!(
+            f,
+            "Timer count: divide by {}, initial {}, current {}",
+            configuration.timer_divide_configuration.divisor(),
+            configuration.timer_initial_count,
+            self.timer_current_count,
+        )?;
+        match configuration.tsc_deadline {
+            Some(tsc_deadline) => writeln!(f, ", deadline {tsc_deadline}")?,
+            None => writeln!(f)?,
+        }
+
+        if let Some(thermal_sensor_vector) = configuration.thermal_sensor_vector {
+            write_local_vector(f, "Thermal sensor", u32::from(thermal_sensor_vector), true)?;
+            writeln!(f)?;
+        }
+        write_local_vector(
+            f,
+            "Performance monitors",
+            u32::from(configuration.performance_monitors_vector),
+            true,
+        )?;
+        writeln!(f)?;
+        if let Some(cmci_vector) = configuration.cmci_vector {
+            write_local_vector(f, "CMCI", u32::from(cmci_vector), true)?;
+            writeln!(f)?;
+        }
+        write_pin(f, "LINT0", u32::from(configuration.lint0_vector))?;
+        write_pin(f, "LINT1", u32::from(configuration.lint1_vector))?;
+        write_local_vector(f, "Error", u32::from(configuration.error_vector), false)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apic::fmt_buffer::FmtBuffer, sim::RegisterFile};
+    use core::fmt::Write;
+
+    #[test]
+    fn displays_decoded_registers() {
+        let register_file = RegisterFile::new(2, 0x0105_0014);
+        let apic = register_file.apic();
+
+        apic.get_spurious_vector().set_apic_enabled(true);
+        let mut timer_vector = apic.get_timer_vector();
+        timer_vector.set_vector(0x20);
+        timer_vector.set_masked(false);
+        apic.set_timer_vector(timer_vector);
+        apic.set_timer_initial_count(1000);
+        register_file.advance_timer(400);
+        register_file.set_in_service(0x31);
+        register_file.set_in_service(0xA2);
+        register_file.raise_error(ErrorStatus::SEND_ACCEPT_ERROR);
+        apic.clear_error_status();
+
+        let mut buffer = FmtBuffer::<2048>::new();
+        write!(buffer, "{}", apic.dump()).unwrap();
+
+        let expected = [
+            "Local APIC 2 (xAPIC)",
+            "Version: 0x14, 6 LVT entries, EOI broadcast suppression supported",
+            "Processor priority: class 10, subclass 0",
+            "Logical destination: 0x0 (Flat model)",
+            "Spurious vector: vector 0xFF, APIC enabled, focus processor checking enabled, EOI broadcast suppression off",
+            "Error status: send accept error",
+            "In service: 0x31 0xA2",
+            "Interrupt request: none",
+            "Timer: vector 0x20, unmasked, idle, OneShot",
+            "Timer count: divide by 2, initial 1000, current 600, deadline 0",
+            "LINT0: vector 0x00, masked, idle, Fixed, active high, edge-triggered, remote IRR 0",
+            "Error: vector 0x00, masked, idle",
+        ];
+        for line in expected {
+            assert!(buffer.lines().any(|dumped| dumped == line), "{line}");
+        }
+        assert!(!buffer.lines().any(|line| line.starts_with("CMCI")));
+    }
+
+    #[test]
+    fn displays_unsupported_registers() {
+        let register_file = RegisterFile::new(0, 0x00FF_0014);
+        register_file.set_tsc_deadline_supported(false);
+        let apic = register_file.apic();
+
+        let dump = apic.dump();
+        assert_eq!(dump.configuration.tsc_deadline, None);
+
+        let mut buffer = FmtBuffer::<2048>::new();
+        write!(buffer, "{dump}").unwrap();
+
+        assert!(buffer.lines().any(|line| line.contains("256 LVT entries")));
+        assert!(
+            buffer
+                .lines()
+                .any(|line| line == "Timer count: divide by 2, initial 0, current 0")
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use core::fmt;
+
+/// Fixed-capacity buffer that tests format into, in place of a `String`.
+pub(crate) struct FmtBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FmtBuffer<N> {
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    /// The text written so far.
+    pub(crate) fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap()
+    }
+
+    pub(crate) fn lines(&self) -> impl Iterator<Item = &str> {
+        self.as_str().lines()
+    }
+}
+
+impl<const N: usize> fmt::Write for FmtBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.bytes
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+use crate::{InterruptTriggerMode, msi::MsiMessage};
+use bit_field::BitField;
+use safe_mmio::{
+    UniqueMmioPointer, field, field_shared,
+    fields::{ReadPure, ReadPureWrite, ReadWrite},
+};
+
+/// The register block of a single HPET comparator.
+#[repr(C)]
+pub struct ComparatorRegisters {
+    configuration: ReadPureWrite<u64>,
+    comparator: ReadPureWrite<u64>,
+    fsb_route: ReadPureWrite<u64>,
+    _reserved: u64,
+}
+
+/// The memory-mapped HPET register block.
+#[repr(C)]
+pub struct Registers {
+    capabilities: ReadPure<u64>,
+    _reserved0: u64,
+    configuration: ReadPureWrite<u64>,
+    _reserved1: u64,
+    interrupt_status: ReadWrite<u64>,
+    _reserved2: [u64; 25],
+    main_counter: ReadPureWrite<u64>,
+    _reserved3: u64,
+    comparators: [ComparatorRegisters; 32],
+}
+
+/// The general capabilities and ID register of an HPET.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(pub(crate) u64);
+
+impl Capabilities {
+    /// Revision of the HPET function implemented. Must not be zero.
+    pub fn get_revision(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..8)).unwrap()
+    }
+
+    /// Number of comparators implemented.
+    pub fn get_comparator_count(&self) -> usize {
+        usize::try_from(self.0.get_bits(8..13)).unwrap() + 1
+    }
+
+    /// Whether the main counter is 64 bits wide.
+    pub fn get_64bit_counter(&self) -> bool {
+        self.0.get_bit(13)
+    }
+
+    /// Whether the HPET supports the legacy replacement route.
+    pub fn get_legacy_replacement_capable(&self) -> bool {
+        self.0.get_bit(15)
+    }
+
+    /// PCI vendor ID of the HPET.
+    pub fn get_vendor_id(&self) -> u16 {
+        u16::try_from(self.0.get_bits(16..32)).unwrap()
+    }
+
+    /// Period of the main counter, in femtoseconds.
+    pub fn get_period_femtoseconds(&self) -> u32 {
+        u32::try_from(self.0.get_bits(32..64)).unwrap()
+    }
+}
+
+impl core::fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Capabilities")
+            .field("Revision", &self.get_revision())
+            .field("Comparator Count", &self.get_comparator_count())
+            .field("64-bit Counter", &self.get_64bit_counter())
+            .field(
+                "Legacy Replacement Capable",
+                &self.get_legacy_replacement_capable(),
+            )
+            .field("Vendor ID", &self.get_vendor_id())
+            .field("Period (fs)", &self.get_period_femtoseconds())
+            .finish()
+    }
+}
+
+/// The configuration and capabilities register of an HPET comparator.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ComparatorConfiguration(pub(crate) u64);
+
+impl ComparatorConfiguration {
+    /// Gets the trigger mode of the interrupts generated by the comparator.
+    pub fn get_trigger_mode(&self) -> InterruptTriggerMode {
+        if self.0.get_bit(1) {
+            InterruptTriggerMode::Level
+        } else {
+            InterruptTriggerMode::Edge
+        }
+    }
+
+    /// Sets the trigger mode of the interrupts generated by the comparator.
+    pub fn set_trigger_mode(&mut self, mode: InterruptTriggerMode) {
+        self.0.set_bit(1, bool::from(mode));
+    }
+
+    /// Gets whether the comparator generates interrupts.
+    pub fn get_enabled(&self) -> bool {
+        self.0.get_bit(2)
+    }
+
+    /// Sets whether the comparator generates interrupts. The comparator still sets its bit in
+    /// the interrupt status register when disabled, for level-triggered interrupts.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.0.set_bit(2, enabled);
+    }
+
+    /// Gets whether the comparator is in periodic mode.
+    pub fn get_periodic(&self) -> bool {
+        self.0.get_bit(3)
+    }
+
+    /// Sets whether the comparator is in periodic mode.
+    pub fn set_periodic(&mut self, periodic: bool) {
+        assert!(
+            !periodic || self.get_periodic_capable(),
+            "comparator does not support periodic mode"
+        );
+
+        self.0.set_bit(3, periodic);
+    }
+
+    /// Whether the comparator supports periodic mode.
+    pub fn get_periodic_capable(&self) -> bool {
+        self.0.get_bit(4)
+    }
+
+    /// Whether the comparator is 64 bits wide.
+    pub fn get_64bit_capable(&self) -> bool {
+        self.0.get_bit(5)
+    }
+
+    /// Sets whether the next write to the comparator register of a periodic comparator sets the
+    /// accumulator (the time of the next interrupt) rather than the period. The bit is cleared
+    /// by the hardware after the write.
+    pub fn set_value_set(&mut self, value_set: bool) {
+        self.0.set_bit(6, value_set);
+    }
+
+    /// Gets whether a 64-bit comparator is forced to operate as a 32-bit comparator.
+    pub fn get_32bit_mode(&self) -> bool {
+        self.0.get_bit(8)
+    }
+
+    /// Sets whether a 64-bit comparator is forced to operate as a 32-bit comparator.
+    pub fn set_32bit_mode(&mut self, enabled: bool) {
+        self.0.set_bit(8, enabled);
+    }
+
+    /// Gets the I/O APIC input that the comparator interrupt is routed to.
+    pub fn get_io_apic_route(&self) -> u8 {
+        u8::try_from(self.0.get_bits(9..14)).unwrap()
+    }
+
+    /// Sets the I/O APIC input that the comparator interrupt is routed to. The input must be
+    /// one of the [`ComparatorConfiguration::get_io_apic_routes`].
+    ///
+    /// Ignored when the legacy replacement route or FSB delivery is enabled.
+    pub fn set_io_apic_route(&mut self, input: u8) {
+        assert!(
+            input < 32 && self.get_io_apic_routes().get_bit(usize::from(input)),
+            "comparator cannot be routed to the I/O APIC input"
+        );
+
+        self.0.set_bits(9..14, u64::from(input));
+    }
+
+    /// Gets whether the comparator interrupt is delivered as a message on the front side bus.
+    pub fn get_fsb_enabled(&self) -> bool {
+        self.0.get_bit(14)
+    }
+
+    /// Sets whether the comparator interrupt is delivered as a message on the front side bus,
+    /// as configured by [`Comparator::set_fsb_route`].
+    pub fn set_fsb_enabled(&mut self, enabled: bool) {
+        assert!(
+            !enabled || self.get_fsb_capable(),
+            "comparator does not support FSB delivery"
+        );
+
+        self.0.set_bit(14, enabled);
+    }
+
+    /// Whether the comparator supports FSB delivery.
+    pub fn get_fsb_capable(&self) -> bool {
+        self.0.get_bit(15)
+    }
+
+    /// Bitmask of the I/O APIC inputs that the comparator interrupt can be routed to.
+    pub fn get_io_apic_routes(&self) -> u32 {
+        u32::try_from(self.0.get_bits(32..64)).unwrap()
+    }
+}
+
+impl core::fmt::Debug for ComparatorConfiguration {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ComparatorConfiguration")
+            .field("Trigger Mode", &self.get_trigger_mode())
+            .field("Enabled", &self.get_enabled())
+            .field("Periodic", &self.get_periodic())
+            .field("Periodic Capable", &self.get_periodic_capable())
+            .field("64-bit Capable", &self.get_64bit_capable())
+            .field("32-bit Mode", &self.get_32bit_mode())
+            .field("I/O APIC Route", &self.get_io_apic_route())
+            .field("FSB Enabled", &self.get_fsb_enabled())
+            .field("FSB Capable", &self.get_fsb_capable())
+            .field(
+                "I/O APIC Routes",
+                &format_args!("{:#X}", self.get_io_apic_routes()),
+            )
+            .finish()
+    }
+}
+
+/// A single comparator of an HPET.
+pub struct Comparator<'a>(UniqueMmioPointer<'a, ComparatorRegisters>);
+
+impl Comparator<'_> {
+    /// Reads the configuration and capabilities of the comparator.
+    pub fn get_configuration(&self) -> ComparatorConfiguration {
+        ComparatorConfiguration(field_shared!(self.0, configuration).read())
+    }
+
+    /// Writes the configuration of the comparator.
+    pub fn set_configuration(&mut self, configuration: ComparatorConfiguration) {
+        field!(self.0, configuration).write(configuration.0);
+    }
+
+    /// Reads the comparator value, which is the main counter value of the next interrupt.
+    pub fn get_comparator(&self) -> u64 {
+        field_shared!(self.0, comparator).read()
+    }
+
+    /// Writes the comparator value.
+    ///
+    /// For a periodic comparator, the write sets the period, unless the value set bit of the
+    /// configuration was set beforehand (see [`ComparatorConfiguration::set_value_set`]).
+    pub fn set_comparator(&mut self, value: u64) {
+        field!(self.0, comparator).write(value);
+    }
+
+    /// Reads the message that the comparator sends when FSB delivery is enabled.
+    pub fn get_fsb_route(&self) -> MsiMessage {
+        let route = field_shared!(self.0, fsb_route).read();
+
+        MsiMessage {
+            address: route.get_bits(32..64),
+            data: u32::try_from(route.get_bits(0..32)).unwrap(),
+        }
+    }
+
+    /// Sets the message that the comparator sends when FSB delivery is enabled.
+    pub fn set_fsb_route(&mut self, message: MsiMessage) {
+        assert!(
+            message.is_valid(),
+            "message does not target the local APICs"
+        );
+
+        let mut route = 0u64;
+        route.set_bits(0..32, u64::from(message.data));
+        route.set_bits(32..64, message.address);
+        field!(self.0, fsb_route).write(route);
+    }
+}
+
+/// A high precision event timer, accessed through its memory-mapped register block.
+pub struct Hpet<'a>(UniqueMmioPointer<'a, Registers>);
+
+impl<'a> Hpet<'a> {
+    /// Creates a new HPET over the mapped register block `registers`.
+    pub fn new(registers: UniqueMmioPointer<'a, Registers>) -> Self {
+        Self(registers)
+    }
+
+    /// Reads the general capabilities of the HPET.
+    pub fn get_capabilities(&self) -> Capabilities {
+        Capabilities(field_shared!(self.0, capabilities).read())
+    }
+
+    /// Period of the main counter, in femtoseconds.
+    pub fn period_femtoseconds(&self) -> u32 {
+        self.get_capabilities().get_period_femtoseconds()
+    }
+
+    /// Frequency of the main counter, in Hz, or `None` if the reported period is zero or
+    /// longer than the 100 nanoseconds allowed by the specification.
+    pub fn frequency(&self) -> Option<u64> {
+        const MAX_PERIOD_FEMTOSECONDS: u32 = 100_000_000;
+
+        match self.period_femtoseconds() {
+            0 => None,
+            period if period > MAX_PERIOD_FEMTOSECONDS => None,
+            period => Some(1_000_000_000_000_000 / u64::from(period)),
+        }
+    }
+
+    fn set_configuration_bit(&mut self, bit: usize, value: bool) {
+        let value = *field_shared!(self.0, configuration)
+            .read()
+            .set_bit(bit, value);
+        field!(self.0, configuration).write(value);
+    }
+
+    /// Whether the main counter is running.
+    pub fn get_enabled(&self) -> bool {
+        field_shared!(self.0, configuration).read().get_bit(0)
+    }
+
+    /// Starts or halts the main counter.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.set_configuration_bit(0, enabled);
+    }
+
+    /// Whether the legacy replacement route is enabled.
+    pub fn get_legacy_replacement(&self) -> bool {
+        field_shared!(self.0, configuration).read().get_bit(1)
+    }
+
+    /// Enables or disables the legacy replacement route, which routes comparator 0 in place of
+    /// the PIT (IRQ 0, or I/O APIC input 2) and comparator 1 in place of the RTC (IRQ 8).
+    pub fn set_legacy_replacement(&mut self, enabled: bool) {
+        assert!(
+            !enabled || self.get_capabilities().get_legacy_replacement_capable(),
+            "HPET does not support the legacy replacement route"
+        );
+
+        self.set_configuration_bit(1, enabled);
+    }
+
+    /// Reads the main counter.
+    pub fn get_main_counter(&self) -> u64 {
+        field_shared!(self.0, main_counter).read()
+    }
+
+    /// Writes the main counter. The counter must be halted.
+    pub fn set_main_counter(&mut self, value: u64) {
+        assert!(
+            !self.get_enabled(),
+            "the main counter must be halted to be written"
+        );
+
+        field!(self.0, main_counter).write(value);
+    }
+
+    /// Whether the level-triggered interrupt of the comparator `index` is active.
+    pub fn get_interrupt_active(&mut self, index: usize) -> bool {
+        field!(self.0, interrupt_status).read().get_bit(index)
+    }
+
+    /// Acknowledges the level-triggered interrupt of the comparator `index`.
+    pub fn clear_interrupt(&mut self, index: usize) {
+        assert!(index < 32, "HPETs have at most 32 comparators");
+
+        field!(self.0, interrupt_status).write(1 << index);
+    }
+
+    /// Gets the comparator `index`.
+    pub fn comparator(&mut self, index: usize) -> Comparator<'_> {
+        assert!(
+            index < self.get_capabilities().get_comparator_count(),
+            "comparator is not implemented"
+        );
+
+        Comparator(field!(self.0, comparators).take(index).unwrap())
+    }
+
+    /// Arms the comparator `index` to interrupt once, `ticks` main counter ticks from now.
+    ///
+    /// The comparator must already be routed; this only sets it to non-periodic mode and enables
+    /// it. A 32-bit comparator wraps, so `ticks` must be small enough that the deadline is not
+    /// missed while it is being written.
+    pub fn arm_oneshot(&mut self, index: usize, ticks: u64) {
+        let deadline = self.get_main_counter().wrapping_add(ticks);
+
+        let mut comparator = self.comparator(index);
+        let mut configuration = comparator.get_configuration();
+        configuration.set_periodic(false);
+        configuration.set_enabled(true);
+        comparator.set_configuration(configuration);
+        comparator.set_comparator(deadline);
+    }
+
+    /// Starts the comparator `index` interrupting every `period` main counter ticks.
+    ///
+    /// The main counter is halted while the comparator is programmed, so that the first
+    /// interrupt is not missed.
+    pub fn start_periodic(&mut self, index: usize, period: u64) {
+        let enabled = self.get_enabled();
+        self.set_enabled(false);
+        let deadline = self.get_main_counter().wrapping_add(period);
+
+        let mut comparator = self.comparator(index);
+        let mut configuration = comparator.get_configuration();
+        configuration.set_periodic(true);
+        configuration.set_enabled(true);
+        configuration.set_value_set(true);
+        comparator.set_configuration(configuration);
+        comparator.set_comparator(deadline);
+        comparator.set_comparator(period);
+
+        self.set_enabled(enabled);
+    }
+
+    /// Disables the interrupts of the comparator `index`.
+    pub fn stop(&mut self, index: usize) {
+        let mut comparator = self.comparator(index);
+        let mut configuration = comparator.get_configuration();
+        configuration.set_enabled(false);
+        comparator.set_configuration(configuration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::{HpetClock, ReferenceClock};
+    use core::time::Duration;
+
+    fn registers() -> Registers {
+        Registers {
+            // 100 MHz (10 ns period), 3 comparators, 64-bit, legacy replacement capable, vendor 0x8086.
+            capabilities: ReadPure(0x0098_9680_8086_A201),
+            _reserved0: 0,
+            configuration: ReadPureWrite(0),
+            _reserved1: 0,
+            interrupt_status: ReadWrite(0),
+            _reserved2: [0; 25],
+            main_counter: ReadPureWrite(1000),
+            _reserved3: 0,
+            comparators: core::array::from_fn(|_| ComparatorRegisters {
+                configuration: ReadPureWrite(0x00F0_0000_0000_8030),
+                comparator: ReadPureWrite(u64::MAX),
+                fsb_route: ReadPureWrite(0),
+                _reserved: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn reads_capabilities() {
+        let mut registers = registers();
+        let hpet = Hpet::new(UniqueMmioPointer::from(&mut registers));
+
+        let capabilities = hpet.get_capabilities();
+        assert_eq!(capabilities.get_revision(), 1);
+        assert_eq!(capabilities.get_comparator_count(), 3);
+        assert!(capabilities.get_64bit_counter());
+        assert!(capabilities.get_legacy_replacement_capable());
+        assert_eq!(capabilities.get_vendor_id(), 0x8086);
+        assert_eq!(hpet.frequency(), Some(100_000_000));
+
+        registers.capabilities = ReadPure(0x0000_0000_8086_A201);
+        let hpet = Hpet::new(UniqueMmioPointer::from(&mut registers));
+        assert_eq!(hpet.frequency(), None);
+    }
+
+    #[test]
+    fn clock_requires_valid_period() {
+        let mut invalid = registers();
+        invalid.capabilities = ReadPure(0x0000_0000_8086_A201);
+        let mut hpet = Hpet::new(UniqueMmioPointer::from(&mut invalid));
+        assert!(HpetClock::new(&mut hpet).is_none());
+        assert!(!hpet.get_enabled());
+
+        // 32-bit counter.
+        let mut registers = registers();
+        registers.capabilities = ReadPure(0x0098_9680_8086_8201);
+        let mut hpet = Hpet::new(UniqueMmioPointer::from(&mut registers));
+        let mut clock = HpetClock::new(&mut hpet).unwrap();
+        assert_eq!(clock.wait(Duration::ZERO), Duration::ZERO);
+        assert!(hpet.get_enabled());
+    }
+
+    #[test]
+    fn programs_comparators() {
+        let mut registers = registers();
+        let mut hpet = Hpet::new(UniqueMmioPointer::from(&mut registers));
+
+        let mut comparator = hpet.comparator(1);
+        let mut configuration = comparator.get_configuration();
+        assert_eq!(configuration.get_io_apic_routes(), 0x00F0_0000);
+        configuration.set_io_apic_route(20);
+        configuration.set_trigger_mode(InterruptTriggerMode::Level);
+        comparator.set_configuration(configuration);
+        comparator.set_fsb_route(MsiMessage::new(2, 0x40));
+        assert_eq!(comparator.get_fsb_route(), MsiMessage::new(2, 0x40));
+
+        hpet.arm_oneshot(1, 500);
+        hpet.start_periodic(2, 250);
+        hpet.set_enabled(true);
+
+        let comparator = &registers.comparators[1];
+        assert_eq!(comparator.configuration.0, 0x00F0_0000_0000_A836);
+        assert_eq!(comparator.comparator.0, 1500);
+        assert_eq!(comparator.fsb_route.0, 0xFEE0_2000_0000_0040);
+
+        let comparator = &registers.comparators[2];
+        assert_eq!(comparator.configuration.0, 0x00F0_0000_0000_807C);
+        assert_eq!(comparator.comparator.0, 250);
+        assert_eq!(registers.configuration.0, 0x1);
+    }
+}
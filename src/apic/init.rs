@@ -0,0 +1,281 @@
+use core::marker::PhantomData;
+
+use crate::{
+    InterruptDeliveryMode, Mode, TaskPriority,
+    local_vector::{Kind, LocalVector, Pin, PinPolarity},
+    madt::{LocalApicNmi, LocalInterruptPin},
+    xApic,
+};
+
+/// Value of every local vector table entry after reset (masked, vector 0).
+const LOCAL_VECTOR_RESET: u32 = 1 << 16;
+
+/// The vector and mask of a local vector table entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LvtConfig {
+    pub vector: u8,
+    pub masked: bool,
+}
+
+impl LvtConfig {
+    /// An entry delivering `vector`.
+    pub const fn unmasked(vector: u8) -> Self {
+        Self {
+            vector,
+            masked: false,
+        }
+    }
+
+    /// A masked entry, holding `vector`.
+    pub const fn masked(vector: u8) -> Self {
+        Self {
+            vector,
+            masked: true,
+        }
+    }
+
+    /// The entry, as programmed from its reset value.
+    fn to_local_vector<K: Kind>(self) -> LocalVector<K> {
+        let mut local_vector = LocalVector(LOCAL_VECTOR_RESET, PhantomData);
+        local_vector.set_vector(self.vector);
+        local_vector.set_masked(self.masked);
+        local_vector
+    }
+}
+
+/// How a local interrupt pin is programmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintMode {
+    /// The pin is masked.
+    Masked,
+
+    /// The pin delivers external (8259-compatible) interrupts, in virtual wire mode.
+    External,
+
+    /// The pin delivers non-maskable interrupts, signalled with the provided polarity.
+    Nmi(PinPolarity),
+
+    /// The pin delivers a fixed interrupt.
+    Fixed(LvtConfig),
+}
+
+impl LintMode {
+    /// The pin's entry, as programmed from its reset value.
+    fn to_local_vector<K: Pin>(self) -> LocalVector<K> {
+        let mut local_vector = LocalVector(LOCAL_VECTOR_RESET, PhantomData);
+        match self {
+            Self::Masked => {}
+
+            Self::External => {
+                local_vector.set_delivery_mode(InterruptDeliveryMode::External);
+                local_vector.set_masked(false);
+            }
+
+            Self::Nmi(polarity) => {
+                local_vector.set_delivery_mode(InterruptDeliveryMode::NonMaskable);
+                local_vector.set_pin_polarity(polarity);
+                local_vector.set_masked(false);
+            }
+
+            Self::Fixed(config) => local_vector = config.to_local_vector(),
+        }
+
+        local_vector
+    }
+}
+
+/// Configuration of the local APIC, as programmed by [`xApic::initialize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitConfig {
+    /// The vector delivered for spurious interrupts.
+    pub spurious_vector: u8,
+    pub timer: LvtConfig,
+    pub thermal_sensor: LvtConfig,
+    pub performance_monitors: LvtConfig,
+    pub cmci: LvtConfig,
+    pub error: LvtConfig,
+    pub lint0: LintMode,
+    pub lint1: LintMode,
+    /// Whether to suppress end-of-interrupt broadcasts to the I/O APICs, if the local APIC
+    /// supports it (see [`crate::Version::can_suppress_eoi_broadcast`]).
+    pub suppress_eoi_broadcast: bool,
+}
+
+impl InitConfig {
+    /// A configuration with every local vector table entry masked.
+    pub const fn new(spurious_vector: u8) -> Self {
+        Self {
+            spurious_vector,
+            timer: LvtConfig::masked(spurious_vector),
+            thermal_sensor: LvtConfig::masked(spurious_vector),
+            performance_monitors: LvtConfig::masked(spurious_vector),
+            cmci: LvtConfig::masked(spurious_vector),
+            error: LvtConfig::masked(spurious_vector),
+            lint0: LintMode::Masked,
+            lint1: LintMode::Masked,
+            suppress_eoi_broadcast: false,
+        }
+    }
+
+    /// Programs the local interrupt pins for the bootstrap processor (if `bootstrap`) or an
+    /// application processor with ACPI processor UID `acpi_uid`, from the local APIC NMI
+    /// structures of the MADT. Structures that apply to other processors are ignored, so every
+    /// structure of the MADT may be passed.
+    ///
+    /// Pins named by an NMI structure deliver non-maskable interrupts. Otherwise, LINT0 of the
+    /// bootstrap processor delivers external interrupts (so that the 8259 PICs keep working in
+    /// virtual wire mode), and every other pin is masked: only one processor may receive
+    /// external interrupts.
+    pub fn with_lint_policy<'a>(
+        mut self,
+        bootstrap: bool,
+        acpi_uid: u32,
+        nmis: impl IntoIterator<Item = &'a LocalApicNmi>,
+    ) -> Self {
+        self.lint0 = if bootstrap {
+            LintMode::External
+        } else {
+            LintMode::Masked
+        };
+        self.lint1 = LintMode::Masked;
+
+        for nmi in nmis.into_iter().filter(|nmi| nmi.applies_to(acpi_uid)) {
+            let mode = LintMode::Nmi(nmi.polarity.unwrap_or(PinPolarity::ActiveHigh));
+            match nmi.pin {
+                LocalInterruptPin::LINT0 => self.lint0 = mode,
+                LocalInterruptPin::LINT1 => self.lint1 = mode,
+            }
+        }
+
+        self
+    }
+}
+
+impl<M: Mode> xApic<M> {
+    /// Initializes the local APIC to a known state from `config`:
+    ///
+    /// - The local APIC is software-disabled (masking every local vector table entry), and the
+    ///   task priority is zeroed.
+    /// - The spurious vector is set, end-of-interrupt broadcasts are suppressed as configured,
+    ///   and the local APIC is software-enabled.
+    /// - The timer is stopped, and every local vector table entry implemented by the local APIC
+    ///   is programmed from its reset value.
+    /// - Errors detected before or during initialization are discarded.
+    ///
+    /// # Safety
+    ///
+    /// Initialization discards the configuration of the local APIC, including the interrupts
+    /// that other software may be relying on; the caller must ensure that it is prepared for
+    /// interrupt delivery to be reconfigured.
+    pub unsafe fn initialize(&self, config: &InitConfig) {
+        let inner = || self.0.clone();
+        let version = self.get_version();
+
+        M::set_spurious_apic_software_enabled(inner(), false);
+        M::set_task_priority(inner(), TaskPriority::default());
+
+        M::set_spurious_vector(inner(), config.spurious_vector);
+        M::set_spurious_eoi_broadcast_suppression(
+            inner(),
+            config.suppress_eoi_broadcast && version.can_suppress_eoi_broadcast(),
+        );
+        M::set_spurious_apic_software_enabled(inner(), true);
+
+        M::clear_error_status(inner());
+        M::clear_error_status(inner());
+
+        M::set_timer_initial_count(inner(), 0);
+        M::set_timer_vector(inner(), config.timer.to_local_vector());
+        if version.max_lvt_entry() >= 5 {
+            M::set_thermal_sensor_vector(inner(), config.thermal_sensor.to_local_vector());
+        }
+        M::set_performance_monitors_vector(inner(), config.performance_monitors.to_local_vector());
+        if version.max_lvt_entry() >= 6 {
+            M::set_cmci_vector(inner(), config.cmci.to_local_vector());
+        }
+        M::set_lint0_vector(inner(), config.lint0.to_local_vector());
+        M::set_lint1_vector(inner(), config.lint1.to_local_vector());
+        M::set_error_vector(inner(), config.error.to_local_vector());
+
+        M::clear_error_status(inner());
+        M::clear_error_status(inner());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{local_vector::TimerMode, sim::RegisterFile};
+    use bit_field::BitField;
+
+    #[test]
+    fn initializes_local_apic() {
+        let register_file = RegisterFile::new(0, 0x0105_0014);
+        let apic = register_file.apic();
+        apic.set_task_priority(TaskPriority::from_class(4));
+        apic.set_timer_initial_count(1000);
+
+        let nmi = LocalApicNmi {
+            acpi_uid: None,
+            pin: LocalInterruptPin::LINT1,
+            polarity: Some(PinPolarity::ActiveLow),
+            trigger_mode: None,
+        };
+        let other_nmi = LocalApicNmi {
+            acpi_uid: Some(1),
+            pin: LocalInterruptPin::LINT0,
+            ..nmi
+        };
+        let config = InitConfig {
+            timer: LvtConfig::unmasked(0x20),
+            error: LvtConfig::unmasked(0xFE),
+            suppress_eoi_broadcast: true,
+            ..InitConfig::new(0xFF)
+        }
+        .with_lint_policy(true, 0, [&nmi, &other_nmi]);
+        // Safety: The simulated local APIC has no hardware side effects.
+        unsafe { apic.initialize(&config) };
+
+        assert_eq!(apic.get_task_priority(), TaskPriority::default());
+        assert_eq!(apic.get_timer_current_count(), 0);
+
+        let spurious = apic.get_spurious_vector();
+        assert_eq!(spurious.get_vector(), 0xFF);
+        assert!(spurious.get_apic_enabled());
+        assert!(spurious.get_eoi_broadcast_suppression());
+
+        let timer_vector = apic.get_timer_vector();
+        assert_eq!(timer_vector.get_vector(), 0x20);
+        assert_eq!(timer_vector.get_mode(), TimerMode::OneShot);
+        assert!(!timer_vector.get_masked());
+        assert!(!apic.get_error_vector().get_masked());
+
+        let lint0_vector = apic.get_lint0_vector();
+        assert!(!lint0_vector.get_masked());
+        assert_eq!(u32::from(lint0_vector).get_bits(8..11), 0b111);
+
+        let lint1_vector = apic.get_lint1_vector();
+        assert!(!lint1_vector.get_masked());
+        assert_eq!(u32::from(lint1_vector).get_bits(8..11), 0b100);
+        assert_eq!(lint1_vector.get_pin_polarity(), PinPolarity::ActiveLow);
+    }
+
+    #[test]
+    fn masks_lint0_of_application_processors() {
+        let config = InitConfig::new(0xFF).with_lint_policy(false, 1, []);
+
+        assert_eq!(config.lint0, LintMode::Masked);
+        assert_eq!(config.lint1, LintMode::Masked);
+
+        let nmi = LocalApicNmi {
+            acpi_uid: Some(1),
+            pin: LocalInterruptPin::LINT0,
+            polarity: None,
+            trigger_mode: None,
+        };
+        let config = InitConfig::new(0xFF).with_lint_policy(false, 1, [&nmi]);
+
+        assert_eq!(config.lint0, LintMode::Nmi(PinPolarity::ActiveHigh));
+        assert_eq!(config.lint1, LintMode::Masked);
+    }
+}
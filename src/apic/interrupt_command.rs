@@ -2,6 +2,8 @@ use core::num::NonZeroU8;
 
 use bit_field::BitField;
 
+use crate::ApicError;
+
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterruptDeliveryMode {
@@ -80,6 +82,23 @@ impl From<InterruptDeliveryMode> for u32 {
     }
 }
 
+impl TryFrom<u32> for InterruptDeliveryMode {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0b000 => Ok(Self::Fixed),
+            0b001 => Ok(Self::LowPriority),
+            0b010 => Ok(Self::SystemManagement),
+            0b100 => Ok(Self::NonMaskable),
+            0b101 => Ok(Self::Init),
+            0b110 => Ok(Self::StartUp),
+            0b111 => Ok(Self::External),
+            value => Err(value),
+        }
+    }
+}
+
 /// Specifies the destination mode of an interrupt.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterruptDestinationMode {
@@ -180,18 +199,20 @@ pub enum InterruptDestination {
     /// Support for this destination shorthand in conjunction with the lowest-priority delivery mode is model
     /// specific. For Pentium 4 and Intel Xeon processors, when this shorthand is used together with lowest
     /// priority delivery mode, the inter-process interrupt may be redirected back to the issuing processor.
-    AllExclusingSelf,
+    AllExcludingSelf,
 }
 
 /// Allows software running on the processor to specify and send inter-processor
 /// interrupts to other processors in the system.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct InterruptCommand {
     high: u32,
     low: u32,
 }
 
 impl InterruptCommand {
+    /// Creates an interrupt command from its raw fields. Invalid combinations of fields panic;
+    /// [`crate::ipi::Ipi`] prevents them at compile time instead.
     pub fn new(
         vector: Option<NonZeroU8>,
         destination: InterruptDestination,
@@ -235,7 +256,7 @@ impl InterruptCommand {
             InterruptDestination::Processor { id } => {
                 assert!(
                     assert_mode != InterruptAssertMode::Deassert,
-                    "\"all including self\" interrupt destination should be specified with INIT de-assert"
+                    "INIT de-assert cannot be sent to a single processor, use the \"all including self\" destination"
                 );
 
                 high = id;
@@ -244,7 +265,7 @@ impl InterruptCommand {
             InterruptDestination::OnlySelf => {
                 assert!(
                     assert_mode != InterruptAssertMode::Deassert,
-                    "\"all including self\" interrupt destination should be specified with INIT de-assert"
+                    "INIT de-assert cannot be sent to self, use the \"all including self\" destination"
                 );
 
                 low.set_bits(18..20, 0b01);
@@ -254,10 +275,10 @@ impl InterruptCommand {
                 low.set_bits(18..20, 0b10);
             }
 
-            InterruptDestination::AllExclusingSelf => {
+            InterruptDestination::AllExcludingSelf => {
                 assert!(
                     assert_mode != InterruptAssertMode::Deassert,
-                    "\"all including self\" interrupt destination should be specified with INIT de-assert"
+                    "INIT de-assert cannot be sent to \"all excluding self\", use the \"all including self\" destination"
                 );
 
                 low.set_bits(18..20, 0b11);
@@ -267,6 +288,57 @@ impl InterruptCommand {
         Self { high, low }
     }
 
+    /// Creates an interrupt command from its raw fields, or returns an error for the invalid
+    /// combinations of fields that [`Self::new`] panics on.
+    pub fn try_new(
+        vector: Option<NonZeroU8>,
+        destination: InterruptDestination,
+        delivery_mode: InterruptDeliveryMode,
+        destination_mode: InterruptDestinationMode,
+        trigger_mode: InterruptTriggerMode,
+        assert_mode: InterruptAssertMode,
+    ) -> Result<Self, ApicError> {
+        if assert_mode == InterruptAssertMode::Deassert {
+            if delivery_mode != InterruptDeliveryMode::Init {
+                return Err(ApicError::InvalidCombination(
+                    "de-assert with a delivery mode other than INIT",
+                ));
+            }
+
+            if trigger_mode != InterruptTriggerMode::Level {
+                return Err(ApicError::InvalidCombination(
+                    "INIT de-assert with edge trigger mode",
+                ));
+            }
+
+            if !matches!(destination, InterruptDestination::AllIncludingSelf) {
+                return Err(ApicError::InvalidCombination(
+                    "INIT de-assert with a destination other than \"all including self\"",
+                ));
+            }
+        }
+
+        if vector.is_some()
+            && matches!(
+                delivery_mode,
+                InterruptDeliveryMode::SystemManagement | InterruptDeliveryMode::Init
+            )
+        {
+            return Err(ApicError::InvalidCombination(
+                "vector with SMI or INIT delivery mode",
+            ));
+        }
+
+        Ok(Self::new(
+            vector,
+            destination,
+            delivery_mode,
+            destination_mode,
+            trigger_mode,
+            assert_mode,
+        ))
+    }
+
     pub fn new_init(apic_id: u32) -> Self {
         Self::new(
             None,
@@ -278,6 +350,18 @@ impl InterruptCommand {
         )
     }
 
+    /// Creates an INIT level de-assert command, which is broadcast to all processors.
+    pub fn new_init_deassert() -> Self {
+        Self::new(
+            None,
+            InterruptDestination::AllIncludingSelf,
+            InterruptDeliveryMode::Init,
+            InterruptDestinationMode::Physical,
+            InterruptTriggerMode::Level,
+            InterruptAssertMode::Deassert,
+        )
+    }
+
     pub fn new_sipi(vector: u8, apic_id: u32) -> Self {
         Self::new(
             NonZeroU8::new(vector),
@@ -297,3 +381,62 @@ impl InterruptCommand {
         self.low
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_combinations() {
+        let command = InterruptCommand::try_new(
+            None,
+            InterruptDestination::AllIncludingSelf,
+            InterruptDeliveryMode::Init,
+            InterruptDestinationMode::Physical,
+            InterruptTriggerMode::Level,
+            InterruptAssertMode::Deassert,
+        );
+        assert!(command.is_ok());
+
+        for (vector, destination, delivery_mode, trigger_mode, assert_mode) in [
+            (
+                None,
+                InterruptDestination::AllIncludingSelf,
+                InterruptDeliveryMode::Fixed,
+                InterruptTriggerMode::Level,
+                InterruptAssertMode::Deassert,
+            ),
+            (
+                None,
+                InterruptDestination::AllIncludingSelf,
+                InterruptDeliveryMode::Init,
+                InterruptTriggerMode::Edge,
+                InterruptAssertMode::Deassert,
+            ),
+            (
+                None,
+                InterruptDestination::OnlySelf,
+                InterruptDeliveryMode::Init,
+                InterruptTriggerMode::Level,
+                InterruptAssertMode::Deassert,
+            ),
+            (
+                NonZeroU8::new(0x40),
+                InterruptDestination::OnlySelf,
+                InterruptDeliveryMode::SystemManagement,
+                InterruptTriggerMode::Edge,
+                InterruptAssertMode::Assert,
+            ),
+        ] {
+            let command = InterruptCommand::try_new(
+                vector,
+                destination,
+                delivery_mode,
+                InterruptDestinationMode::Physical,
+                trigger_mode,
+                assert_mode,
+            );
+            assert!(matches!(command, Err(ApicError::InvalidCombination(_))));
+        }
+    }
+}
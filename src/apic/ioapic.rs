@@ -0,0 +1,438 @@
+use core::fmt;
+
+use crate::{
+    ApicError, InterruptDeliveryMode, InterruptDestinationMode, InterruptTriggerMode,
+    local_vector::PinPolarity,
+};
+use bit_field::BitField;
+use safe_mmio::{
+    UniqueMmioPointer, field,
+    fields::{ReadPureWrite, ReadWrite},
+};
+
+const ID: u32 = 0x00;
+const VERSION: u32 = 0x01;
+const ARBITRATION: u32 = 0x02;
+const REDIRECTION_TABLE_BASE: u32 = 0x10;
+
+/// The I/O APIC register window. Registers are accessed indirectly, by writing the register
+/// index to the select register, then reading or writing the data window.
+#[repr(C)]
+pub struct Registers {
+    select: ReadPureWrite<u32>,
+    _reserved: [u32; 3],
+    window: ReadWrite<u32>,
+}
+
+/// Specifies the version of an I/O APIC device, and the size of its redirection table.
+#[derive(Clone, Copy)]
+pub struct IoApicVersion(u32);
+
+impl IoApicVersion {
+    /// Version of the I/O APIC device.
+    pub fn version(&self) -> u8 {
+        u8::try_from(self.0.get_bits(..8)).unwrap()
+    }
+
+    /// The number of redirection table entries, less 1.
+    pub fn max_redirection_entry(&self) -> u8 {
+        u8::try_from(self.0.get_bits(16..24)).unwrap()
+    }
+}
+
+impl fmt::Debug for IoApicVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoApicVersion")
+            .field("Version", &self.version())
+            .field("Maximum Redirection Entry", &self.max_redirection_entry())
+            .finish()
+    }
+}
+
+/// An entry of the I/O APIC redirection table, describing how an interrupt on an input
+/// pin is delivered to the local APICs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectionEntry(u64);
+
+impl Default for RedirectionEntry {
+    /// A masked, edge-triggered, active-high entry with a fixed delivery mode.
+    fn default() -> Self {
+        Self(1 << 16)
+    }
+}
+
+impl RedirectionEntry {
+    /// Gets the interrupt vector number.
+    pub fn get_vector(&self) -> u8 {
+        u8::try_from(self.0.get_bits(..8)).unwrap()
+    }
+
+    /// Sets the interrupt vector number.
+    pub fn set_vector(&mut self, vector: u8) {
+        assert!(vector > 15, "interrupts vectors 0..=15 are reserved");
+
+        self.0.set_bits(..8, u64::from(vector));
+    }
+
+    /// Sets the interrupt vector number, or returns an error if `vector` is reserved.
+    pub fn try_set_vector(&mut self, vector: u8) -> Result<(), ApicError> {
+        if vector < 16 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        self.0.set_bits(..8, u64::from(vector));
+
+        Ok(())
+    }
+
+    /// Gets the type of interrupt to be sent to the processor.
+    pub fn get_delivery_mode(&self) -> InterruptDeliveryMode {
+        self.try_get_delivery_mode().unwrap()
+    }
+
+    /// Gets the type of interrupt to be sent to the processor, or an error if the entry holds
+    /// a reserved delivery mode.
+    pub fn try_get_delivery_mode(&self) -> Result<InterruptDeliveryMode, ApicError> {
+        let value = u32::try_from(self.0.get_bits(8..11)).unwrap();
+
+        InterruptDeliveryMode::try_from(value).map_err(|value| ApicError::ReservedEncoding {
+            field: "delivery mode",
+            value,
+        })
+    }
+
+    /// Specifies the type of interrupt to be sent to the processor.
+    ///
+    /// Note: The start-up delivery mode is not supported by the I/O APIC.
+    pub fn set_delivery_mode(&mut self, mode: InterruptDeliveryMode) {
+        assert!(
+            mode != InterruptDeliveryMode::StartUp,
+            "start-up delivery mode is not supported by the I/O APIC"
+        );
+
+        self.0.set_bits(8..11, u64::from(u32::from(mode)));
+    }
+
+    /// Specifies the type of interrupt to be sent to the processor, or returns an error for
+    /// the start-up delivery mode.
+    pub fn try_set_delivery_mode(&mut self, mode: InterruptDeliveryMode) -> Result<(), ApicError> {
+        if mode == InterruptDeliveryMode::StartUp {
+            return Err(ApicError::Unsupported("start-up delivery mode"));
+        }
+
+        self.0.set_bits(8..11, u64::from(u32::from(mode)));
+
+        Ok(())
+    }
+
+    /// Gets how the destination field is interpreted.
+    pub fn get_destination_mode(&self) -> InterruptDestinationMode {
+        if self.0.get_bit(11) {
+            InterruptDestinationMode::Logical
+        } else {
+            InterruptDestinationMode::Physical
+        }
+    }
+
+    /// Sets how the destination field is interpreted.
+    pub fn set_destination_mode(&mut self, mode: InterruptDestinationMode) {
+        self.0.set_bit(11, bool::from(mode));
+    }
+
+    /// Gets the delivery status of the interrupt.
+    ///
+    /// - `true` indicates that an interrupt from this pin is pending delivery to the local APIC.
+    /// - `false` indicates there is currently no activity for this pin.
+    pub fn get_delivery_status(&self) -> bool {
+        self.0.get_bit(12)
+    }
+
+    /// Gets the polarity of the interrupt input pin.
+    pub fn get_pin_polarity(&self) -> PinPolarity {
+        if self.0.get_bit(13) {
+            PinPolarity::ActiveLow
+        } else {
+            PinPolarity::ActiveHigh
+        }
+    }
+
+    /// Sets the polarity of the interrupt input pin.
+    pub fn set_pin_polarity(&mut self, polarity: PinPolarity) {
+        self.0.set_bit(13, bool::from(polarity));
+    }
+
+    /// For level-triggered interrupts, whether the interrupt has been accepted by a local APIC
+    /// (`true`), and not yet acknowledged with an end-of-interrupt message. Undefined for
+    /// edge-triggered interrupts.
+    pub fn get_remote_irr(&self) -> bool {
+        self.0.get_bit(14)
+    }
+
+    /// Gets the trigger mode of the interrupt input pin.
+    pub fn get_trigger_mode(&self) -> InterruptTriggerMode {
+        if self.0.get_bit(15) {
+            InterruptTriggerMode::Level
+        } else {
+            InterruptTriggerMode::Edge
+        }
+    }
+
+    /// Sets the trigger mode of the interrupt input pin.
+    pub fn set_trigger_mode(&mut self, mode: InterruptTriggerMode) {
+        self.0.set_bit(15, bool::from(mode));
+    }
+
+    /// Whether the interrupt is masked.
+    pub fn get_masked(&self) -> bool {
+        self.0.get_bit(16)
+    }
+
+    /// Masks or unmasks the interrupt based on `masked`.
+    pub fn set_masked(&mut self, masked: bool) {
+        self.0.set_bit(16, masked);
+    }
+
+    /// Gets the destination of the interrupt.
+    ///
+    /// - In physical destination mode, bits 0..=3 contain the APIC ID of the destination.
+    /// - In logical destination mode, the destination is a set of processors.
+    pub fn get_destination(&self) -> u8 {
+        u8::try_from(self.0.get_bits(56..64)).unwrap()
+    }
+
+    /// Sets the destination of the interrupt.
+    pub fn set_destination(&mut self, destination: u8) {
+        self.0.set_bits(56..64, u64::from(destination));
+    }
+}
+
+impl From<RedirectionEntry> for u64 {
+    fn from(value: RedirectionEntry) -> Self {
+        value.0
+    }
+}
+
+impl From<u64> for RedirectionEntry {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+/// An I/O APIC, accessed through its memory-mapped register window.
+pub struct IoApic<'a>(UniqueMmioPointer<'a, Registers>);
+
+impl<'a> IoApic<'a> {
+    /// Creates a new I/O APIC over the mapped register window `registers`.
+    pub fn new(registers: UniqueMmioPointer<'a, Registers>) -> Self {
+        Self(registers)
+    }
+
+    fn read(&mut self, register: u32) -> u32 {
+        field!(self.0, select).write(register);
+        field!(self.0, window).read()
+    }
+
+    fn write(&mut self, register: u32, value: u32) {
+        field!(self.0, select).write(register);
+        field!(self.0, window).write(value);
+    }
+
+    /// Gets the ID of the I/O APIC.
+    pub fn get_id(&mut self) -> u8 {
+        u8::try_from(self.read(ID).get_bits(24..28)).unwrap()
+    }
+
+    /// Sets the ID of the I/O APIC.
+    pub fn set_id(&mut self, id: u8) {
+        assert!(id < 16, "I/O APIC IDs are 4 bits wide");
+
+        let value = *self.read(ID).set_bits(24..28, u32::from(id));
+        self.write(ID, value);
+    }
+
+    /// Sets the ID of the I/O APIC, or returns an error if `id` does not fit in 4 bits.
+    pub fn try_set_id(&mut self, id: u8) -> Result<(), ApicError> {
+        if id >= 16 {
+            return Err(ApicError::OutOfRange {
+                field: "I/O APIC ID",
+                value: u32::from(id),
+            });
+        }
+
+        self.set_id(id);
+
+        Ok(())
+    }
+
+    /// Gets the version of the I/O APIC.
+    pub fn get_version(&mut self) -> IoApicVersion {
+        IoApicVersion(self.read(VERSION))
+    }
+
+    /// Gets the bus arbitration priority of the I/O APIC.
+    pub fn get_arbitration_id(&mut self) -> u8 {
+        u8::try_from(self.read(ARBITRATION).get_bits(24..28)).unwrap()
+    }
+
+    /// The number of redirection table entries, which is the number of input pins.
+    pub fn redirection_entry_count(&mut self) -> u16 {
+        u16::from(self.get_version().max_redirection_entry()) + 1
+    }
+
+    fn redirection_register(&mut self, pin: u8) -> Result<u32, ApicError> {
+        if u16::from(pin) >= self.redirection_entry_count() {
+            return Err(ApicError::OutOfRange {
+                field: "redirection table pin",
+                value: u32::from(pin),
+            });
+        }
+
+        Ok(REDIRECTION_TABLE_BASE + (u32::from(pin) * 2))
+    }
+
+    /// Gets the redirection table entry for `pin`.
+    pub fn get_redirection_entry(&mut self, pin: u8) -> RedirectionEntry {
+        self.try_get_redirection_entry(pin)
+            .expect("pin is out of range of the redirection table")
+    }
+
+    /// Gets the redirection table entry for `pin`, or an error if the I/O APIC has no such pin.
+    pub fn try_get_redirection_entry(&mut self, pin: u8) -> Result<RedirectionEntry, ApicError> {
+        let register = self.redirection_register(pin)?;
+        let low = u64::from(self.read(register));
+        let high = u64::from(self.read(register + 1));
+
+        Ok(RedirectionEntry((high << 32) | low))
+    }
+
+    /// Sets the redirection table entry for `pin`.
+    ///
+    /// The pin is masked while the entry is updated, so an interrupt is never delivered
+    /// using a partially written entry.
+    pub fn set_redirection_entry(&mut self, pin: u8, entry: RedirectionEntry) {
+        self.try_set_redirection_entry(pin, entry)
+            .expect("pin is out of range of the redirection table");
+    }
+
+    /// Sets the redirection table entry for `pin`, or returns an error if the I/O APIC has no
+    /// such pin.
+    pub fn try_set_redirection_entry(
+        &mut self,
+        pin: u8,
+        entry: RedirectionEntry,
+    ) -> Result<(), ApicError> {
+        let register = self.redirection_register(pin)?;
+        let low = u32::try_from(entry.0.get_bits(..32)).unwrap();
+        let high = u32::try_from(entry.0.get_bits(32..)).unwrap();
+
+        let masked_low = *self.read(register).set_bit(16, true);
+        self.write(register, masked_low);
+        self.write(register + 1, high);
+        self.write(register, low);
+
+        Ok(())
+    }
+
+    /// Masks or unmasks `pin` based on `masked`.
+    pub fn set_masked(&mut self, pin: u8, masked: bool) {
+        self.try_set_masked(pin, masked)
+            .expect("pin is out of range of the redirection table");
+    }
+
+    /// Masks or unmasks `pin` based on `masked`, or returns an error if the I/O APIC has no
+    /// such pin.
+    pub fn try_set_masked(&mut self, pin: u8, masked: bool) -> Result<(), ApicError> {
+        let register = self.redirection_register(pin)?;
+        let low = *self.read(register).set_bit(16, masked);
+        self.write(register, low);
+
+        Ok(())
+    }
+
+    /// Masks `pin`.
+    pub fn mask(&mut self, pin: u8) {
+        self.set_masked(pin, true);
+    }
+
+    /// Unmasks `pin`.
+    pub fn unmask(&mut self, pin: u8) {
+        self.set_masked(pin, false);
+    }
+
+    /// Whether a level-triggered interrupt on `pin` has been accepted by a local APIC, and
+    /// is awaiting an end-of-interrupt message.
+    pub fn get_remote_irr(&mut self, pin: u8) -> bool {
+        self.get_redirection_entry(pin).get_remote_irr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirection_entry_encoding() {
+        let mut entry = RedirectionEntry::default();
+        assert!(entry.get_masked());
+
+        entry.set_vector(0x41);
+        entry.set_delivery_mode(InterruptDeliveryMode::LowPriority);
+        entry.set_destination_mode(InterruptDestinationMode::Logical);
+        entry.set_pin_polarity(PinPolarity::ActiveLow);
+        entry.set_trigger_mode(InterruptTriggerMode::Level);
+        entry.set_masked(false);
+        entry.set_destination(0x0F);
+
+        assert_eq!(u64::from(entry), 0x0F00_0000_0000_A941);
+        assert_eq!(entry.get_vector(), 0x41);
+        assert_eq!(
+            entry.get_delivery_mode(),
+            InterruptDeliveryMode::LowPriority
+        );
+        assert_eq!(
+            entry.get_destination_mode(),
+            InterruptDestinationMode::Logical
+        );
+        assert_eq!(entry.get_pin_polarity(), PinPolarity::ActiveLow);
+        assert_eq!(entry.get_trigger_mode(), InterruptTriggerMode::Level);
+        assert_eq!(entry.get_destination(), 0x0F);
+    }
+
+    #[test]
+    fn redirection_entry_read_only_bits() {
+        let entry = RedirectionEntry::from(0x0000_0000_0000_5000);
+
+        assert!(entry.get_delivery_status());
+        assert!(entry.get_remote_irr());
+        assert!(!entry.get_masked());
+    }
+
+    #[test]
+    fn fallible_accessors() {
+        let mut entry = RedirectionEntry::from(0x0000_0000_0000_0341);
+        assert_eq!(
+            entry.try_get_delivery_mode(),
+            Err(ApicError::ReservedEncoding {
+                field: "delivery mode",
+                value: 0b011,
+            })
+        );
+        assert_eq!(
+            entry.try_set_vector(0x0F),
+            Err(ApicError::ReservedVector(0x0F))
+        );
+        assert_eq!(
+            entry.try_set_delivery_mode(InterruptDeliveryMode::StartUp),
+            Err(ApicError::Unsupported("start-up delivery mode"))
+        );
+        assert_eq!(
+            entry.try_set_delivery_mode(InterruptDeliveryMode::Fixed),
+            Ok(())
+        );
+        assert_eq!(
+            entry.try_get_delivery_mode(),
+            Ok(InterruptDeliveryMode::Fixed)
+        );
+        assert_eq!(entry.get_vector(), 0x41);
+    }
+}
@@ -0,0 +1,350 @@
+use core::{marker::PhantomData, num::NonZeroU8};
+
+use crate::{
+    InterruptAssertMode, InterruptCommand, InterruptDeliveryMode, InterruptDestination,
+    InterruptDestinationMode, InterruptTriggerMode, Mode,
+};
+
+/// Errors detected while building an inter-processor interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpiError {
+    /// Vectors 0..=15 are reserved, and cannot be sent.
+    ReservedVector(u8),
+
+    /// The destination does not fit in the 8-bit destination field of xAPIC mode.
+    DestinationOutOfRange(u32),
+
+    /// Lowest-priority delivery is not supported in x2APIC mode.
+    LowestPriorityUnsupported,
+
+    /// INIT level de-assert is not supported in x2APIC mode.
+    InitDeassertUnsupported,
+
+    /// The local APIC did not accept the previous interrupt command in time, so the next one
+    /// could not be sent.
+    DeliveryTimeout,
+}
+
+/// The delivery mode of an inter-processor interrupt.
+pub trait Kind {
+    const DELIVERY_MODE: InterruptDeliveryMode;
+    const TRIGGER_MODE: InterruptTriggerMode;
+    const ASSERT_MODE: InterruptAssertMode = InterruptAssertMode::Assert;
+}
+
+/// Delivery modes that can be sent to a single processor by its APIC ID.
+pub trait Physical: Kind {}
+
+/// Delivers the vector as a fixed interrupt.
+#[derive(Debug, Clone, Copy)]
+pub struct Fixed;
+impl Kind for Fixed {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::Fixed;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Edge;
+}
+impl Physical for Fixed {}
+
+/// Delivers the vector to the processor executing at the lowest priority among the
+/// destinations. Only supported in logical destination mode.
+#[derive(Debug, Clone, Copy)]
+pub struct LowestPriority;
+impl Kind for LowestPriority {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::LowPriority;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Edge;
+}
+
+/// Delivers a system management interrupt.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemManagement;
+impl Kind for SystemManagement {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::SystemManagement;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Edge;
+}
+impl Physical for SystemManagement {}
+
+/// Delivers a non-maskable interrupt.
+#[derive(Debug, Clone, Copy)]
+pub struct NonMaskable;
+impl Kind for NonMaskable {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::NonMaskable;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Edge;
+}
+impl Physical for NonMaskable {}
+
+/// Delivers an INIT request.
+#[derive(Debug, Clone, Copy)]
+pub struct Init;
+impl Kind for Init {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::Init;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Level;
+}
+impl Physical for Init {}
+
+/// Delivers an INIT level de-assert, which synchronizes the arbitration IDs of every local
+/// APIC. Always broadcast to all processors including self.
+#[derive(Debug, Clone, Copy)]
+pub struct InitDeassert;
+impl Kind for InitDeassert {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::Init;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Level;
+    const ASSERT_MODE: InterruptAssertMode = InterruptAssertMode::Deassert;
+}
+
+/// Delivers a start-up interrupt, with the page number of the start-up routine as its
+/// vector.
+#[derive(Debug, Clone, Copy)]
+pub struct StartUp;
+impl Kind for StartUp {
+    const DELIVERY_MODE: InterruptDeliveryMode = InterruptDeliveryMode::StartUp;
+    const TRIGGER_MODE: InterruptTriggerMode = InterruptTriggerMode::Edge;
+}
+impl Physical for StartUp {}
+
+/// Destination state of an [`Ipi`] that has not been addressed.
+#[derive(Debug, Clone, Copy)]
+pub struct Unaddressed;
+
+/// Destination state of an [`Ipi`] that has been addressed, and can be built.
+#[derive(Debug, Clone, Copy)]
+pub struct Addressed;
+
+#[derive(Debug, Clone, Copy)]
+enum Destination {
+    Physical(u32),
+    Logical(u32),
+    OnlySelf,
+    AllIncludingSelf,
+    AllExcludingSelf,
+}
+
+/// Builder of an inter-processor interrupt with delivery mode `K`.
+///
+/// Only the destinations that are valid for the delivery mode are exposed, so invalid
+/// combinations (such as a start-up interrupt to self, or a vector with an INIT) do not
+/// compile. Checks that depend on values, or on the mode of the local APIC, are reported by
+/// [`Ipi::build`].
+#[derive(Debug, Clone, Copy)]
+pub struct Ipi<K: Kind, D = Unaddressed> {
+    vector: u8,
+    destination: Option<Destination>,
+    _marker: PhantomData<(K, D)>,
+}
+
+impl Ipi<Fixed> {
+    /// A fixed interrupt delivering `vector`.
+    pub fn fixed(vector: u8) -> Self {
+        Self::new(vector)
+    }
+}
+
+impl Ipi<LowestPriority> {
+    /// A lowest-priority interrupt delivering `vector`.
+    pub fn lowest_priority(vector: u8) -> Self {
+        Self::new(vector)
+    }
+}
+
+impl Ipi<SystemManagement> {
+    /// A system management interrupt.
+    pub fn smi() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Ipi<NonMaskable> {
+    /// A non-maskable interrupt.
+    pub fn nmi() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Ipi<Init> {
+    /// An INIT request.
+    pub fn init() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Ipi<StartUp> {
+    /// A start-up interrupt, starting execution at physical page `page` (address divided by
+    /// 4096), which must be below 1 MiB.
+    pub fn startup(page: u8) -> Self {
+        Self::new(page)
+    }
+}
+
+impl Ipi<InitDeassert, Addressed> {
+    /// An INIT level de-assert, broadcast to all processors including self.
+    pub fn init_deassert() -> Self {
+        Self {
+            vector: 0,
+            destination: Some(Destination::AllIncludingSelf),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Kind> Ipi<K> {
+    fn new(vector: u8) -> Self {
+        Self {
+            vector,
+            destination: None,
+            _marker: PhantomData,
+        }
+    }
+
+    fn address(self, destination: Destination) -> Ipi<K, Addressed> {
+        Ipi {
+            vector: self.vector,
+            destination: Some(destination),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sends the interrupt in logical destination mode to `destination` (see
+    /// [`crate::logical_destinations`]).
+    pub fn to_logical(self, destination: u32) -> Ipi<K, Addressed> {
+        self.address(Destination::Logical(destination))
+    }
+
+    /// Sends the interrupt to all processors except self.
+    pub fn all_excluding_self(self) -> Ipi<K, Addressed> {
+        self.address(Destination::AllExcludingSelf)
+    }
+}
+
+impl<K: Physical> Ipi<K> {
+    /// Sends the interrupt to the processor with `apic_id`.
+    pub fn to(self, apic_id: u32) -> Ipi<K, Addressed> {
+        self.address(Destination::Physical(apic_id))
+    }
+}
+
+impl Ipi<Fixed> {
+    /// Sends the interrupt to self.
+    pub fn to_self(self) -> Ipi<Fixed, Addressed> {
+        self.address(Destination::OnlySelf)
+    }
+
+    /// Sends the interrupt to all processors including self.
+    pub fn all_including_self(self) -> Ipi<Fixed, Addressed> {
+        self.address(Destination::AllIncludingSelf)
+    }
+}
+
+impl<K: Kind> Ipi<K, Addressed> {
+    /// Builds the interrupt command for a local APIC in mode `M`.
+    pub fn build<M: Mode>(self) -> Result<InterruptCommand, IpiError> {
+        let delivery_mode = K::DELIVERY_MODE;
+        let destination = self.destination.unwrap();
+
+        if matches!(
+            delivery_mode,
+            InterruptDeliveryMode::Fixed | InterruptDeliveryMode::LowPriority
+        ) && self.vector < 16
+        {
+            return Err(IpiError::ReservedVector(self.vector));
+        }
+
+        if M::EXTENDED && delivery_mode == InterruptDeliveryMode::LowPriority {
+            return Err(IpiError::LowestPriorityUnsupported);
+        }
+
+        if M::EXTENDED && K::ASSERT_MODE == InterruptAssertMode::Deassert {
+            return Err(IpiError::InitDeassertUnsupported);
+        }
+
+        let (destination, destination_mode) = match destination {
+            Destination::Physical(id) | Destination::Logical(id) if !M::EXTENDED && id > 0xFF => {
+                return Err(IpiError::DestinationOutOfRange(id));
+            }
+
+            Destination::Physical(id) => (
+                InterruptDestination::Processor { id },
+                InterruptDestinationMode::Physical,
+            ),
+            Destination::Logical(id) => (
+                InterruptDestination::Processor { id },
+                InterruptDestinationMode::Logical,
+            ),
+            Destination::OnlySelf => (
+                InterruptDestination::OnlySelf,
+                InterruptDestinationMode::Physical,
+            ),
+            Destination::AllIncludingSelf => (
+                InterruptDestination::AllIncludingSelf,
+                InterruptDestinationMode::Physical,
+            ),
+            Destination::AllExcludingSelf => (
+                InterruptDestination::AllExcludingSelf,
+                InterruptDestinationMode::Physical,
+            ),
+        };
+
+        Ok(InterruptCommand::new(
+            NonZeroU8::new(self.vector),
+            destination,
+            delivery_mode,
+            destination_mode,
+            K::TRIGGER_MODE,
+            K::ASSERT_MODE,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sim::sim, x2::x2};
+    use bit_field::BitField;
+
+    #[test]
+    fn builds_interrupt_commands() {
+        let fixed = Ipi::fixed(0x40).to(3).build::<sim>().unwrap();
+        assert_eq!(fixed.high(), 3);
+        assert_eq!(fixed.low(), 0x4040);
+
+        let nmi = Ipi::nmi().all_excluding_self().build::<sim>().unwrap();
+        assert_eq!(nmi.low().get_bits(8..11), 0b100);
+        assert_eq!(nmi.low().get_bits(18..20), 0b11);
+
+        let init_deassert = Ipi::init_deassert().build::<sim>().unwrap();
+        assert_eq!(
+            init_deassert.low(),
+            InterruptCommand::new_init_deassert().low()
+        );
+
+        let sipi = Ipi::startup(0x08).to(1).build::<sim>().unwrap();
+        assert_eq!(sipi.low(), InterruptCommand::new_sipi(0x08, 1).low());
+
+        let logical = Ipi::lowest_priority(0x50)
+            .to_logical(0x0F)
+            .build::<sim>()
+            .unwrap();
+        assert!(logical.low().get_bit(11));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(
+            Ipi::fixed(0x0E).to_self().build::<sim>().unwrap_err(),
+            IpiError::ReservedVector(0x0E)
+        );
+        assert_eq!(
+            Ipi::fixed(0x40).to(0x100).build::<sim>().unwrap_err(),
+            IpiError::DestinationOutOfRange(0x100)
+        );
+        assert!(Ipi::fixed(0x40).to(0x100).build::<x2>().is_ok());
+        assert_eq!(
+            Ipi::lowest_priority(0x40)
+                .to_logical(1)
+                .build::<x2>()
+                .unwrap_err(),
+            IpiError::LowestPriorityUnsupported
+        );
+        assert_eq!(
+            Ipi::init_deassert().build::<x2>().unwrap_err(),
+            IpiError::InitDeassertUnsupported
+        );
+    }
+}
@@ -1,37 +1,50 @@
-use crate::{InterruptDeliveryMode, Mode};
+use crate::{ApicError, InterruptDeliveryMode};
 use bit_field::BitField;
 use core::marker::PhantomData;
 
 pub trait Kind {}
 pub trait Deliverable: Kind {}
+/// Local vector table entries of the local interrupt pins.
+pub trait Pin: Deliverable {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct Timer;
 impl Kind for Timer {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct CMCI;
 impl Kind for CMCI {}
 impl Deliverable for CMCI {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct LINT0;
 impl Kind for LINT0 {}
+impl Deliverable for LINT0 {}
+impl Pin for LINT0 {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct LINT1;
 impl Kind for LINT1 {}
+impl Deliverable for LINT1 {}
+impl Pin for LINT1 {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct Error;
 impl Kind for Error {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct PerformanceMonitors;
 impl Kind for PerformanceMonitors {}
 impl Deliverable for PerformanceMonitors {}
 
+#[derive(Debug, Clone, Copy)]
 pub struct ThermalSensor;
 impl Kind for ThermalSensor {}
 impl Deliverable for ThermalSensor {}
 
-#[derive(Debug, Clone)]
-pub struct LocalVector<'a, M: Mode, K: Kind>(pub(crate) M::Inner, pub(crate) PhantomData<&'a K>);
-impl<M: Mode, K: Kind> LocalVector<'_, M, K> {
+#[derive(Debug, Clone, Copy)]
+pub struct LocalVector<K: Kind>(pub(crate) u32, pub(crate) PhantomData<K>);
+impl<K: Kind> LocalVector<K> {
     /// Gets the delivery status of the interrupt.
     ///
     /// - `true` indicates that an interrupt from this source has been delivered to the
@@ -70,14 +83,43 @@ impl<M: Mode, K: Kind> LocalVector<'_, M, K> {
         u8::try_from(vector).unwrap()
     }
 
+    /// Gets the interrupt vector number, or an error if the entry holds a reserved vector.
+    pub fn try_get_vector(&self) -> Result<u8, ApicError> {
+        let vector = u8::try_from(self.0.get_bits(0..8)).unwrap();
+        if vector < 16 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        Ok(vector)
+    }
+
     /// Sets the interrupt vector number.
     pub fn set_vector(&mut self, vector: u8) {
         assert!(vector > 15, "interrupts vectors 0..=15 are reserved");
 
         self.0.set_bits(0..8, u32::from(vector));
     }
+
+    /// Sets the interrupt vector number, or returns an error if `vector` is reserved.
+    pub fn try_set_vector(&mut self, vector: u8) -> Result<(), ApicError> {
+        if vector < 16 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        self.0.set_bits(0..8, u32::from(vector));
+
+        Ok(())
+    }
 }
 
+impl<K: Kind> PartialEq for LocalVector<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Kind> Eq for LocalVector<K> {}
+
 impl<K: Kind> From<LocalVector<K>> for u32 {
     fn from(value: LocalVector<K>) -> Self {
         value.0
@@ -98,6 +140,22 @@ impl<K: Deliverable> LocalVector<K> {
     }
 }
 
+impl<K: Pin> LocalVector<K> {
+    /// Gets the polarity of the interrupt pin.
+    pub fn get_pin_polarity(&self) -> PinPolarity {
+        if self.0.get_bit(13) {
+            PinPolarity::ActiveLow
+        } else {
+            PinPolarity::ActiveHigh
+        }
+    }
+
+    /// Sets the polarity of the interrupt pin.
+    pub fn set_pin_polarity(&mut self, polarity: PinPolarity) {
+        self.0.set_bit(13, bool::from(polarity));
+    }
+}
+
 /// Specifies the polarity of an interrupt pin.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PinPolarity {
@@ -105,6 +163,15 @@ pub enum PinPolarity {
     ActiveLow,
 }
 
+impl From<PinPolarity> for bool {
+    fn from(value: PinPolarity) -> Self {
+        match value {
+            PinPolarity::ActiveHigh => false,
+            PinPolarity::ActiveLow => true,
+        }
+    }
+}
+
 /// Various valid modes for APIC timer to operate.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TimerMode {
@@ -148,16 +215,50 @@ impl LocalVector<Timer> {
         TimerMode::try_from(self.0.get_bits(17..19)).unwrap()
     }
 
+    /// Gets the mode that the timer is currently operating in, or an error if the entry holds
+    /// the reserved mode encoding.
+    pub fn try_get_mode(&self) -> Result<TimerMode, ApicError> {
+        TimerMode::try_from(self.0.get_bits(17..19)).map_err(|value| ApicError::ReservedEncoding {
+            field: "timer mode",
+            value,
+        })
+    }
+
     /// Sets the mode for the timer to operate in.
+    ///
+    /// Support for TSC-deadline mode is checked when the entry is written to the local APIC
+    /// (see [`crate::xApic::set_timer_vector`]).
     pub fn set_mode(&mut self, mode: TimerMode) {
-        // Safety: `cpuid` instruction is almost definitely supported.
-        let is_tsc_deadline_supported = unsafe { core::arch::x86_64::__cpuid(0x1).ecx.get_bit(24) };
+        self.0.set_bits(17..19, u32::from(mode));
+    }
+}
 
-        assert!(
-            mode != TimerMode::TscDeadline || is_tsc_deadline_supported,
-            "TSC deadline mode is not supported by this APIC"
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_reserved_vectors_and_modes() {
+        let mut local_vector: LocalVector<Timer> = LocalVector(1 << 16, PhantomData);
+        assert_eq!(
+            local_vector.try_get_vector(),
+            Err(ApicError::ReservedVector(0))
+        );
+        assert_eq!(
+            local_vector.try_set_vector(0x0F),
+            Err(ApicError::ReservedVector(0x0F))
         );
+        assert_eq!(local_vector.try_set_vector(0x20), Ok(()));
+        assert_eq!(local_vector.try_get_vector(), Ok(0x20));
 
-        self.0.set_bits(17..19, u32::from(mode));
+        assert_eq!(local_vector.try_get_mode(), Ok(TimerMode::OneShot));
+        local_vector.0.set_bits(17..19, 0b11);
+        assert_eq!(
+            local_vector.try_get_mode(),
+            Err(ApicError::ReservedEncoding {
+                field: "timer mode",
+                value: 0b11
+            })
+        );
     }
 }
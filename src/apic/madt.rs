@@ -0,0 +1,635 @@
+use crate::{InterruptTriggerMode, local_vector::PinPolarity};
+use bit_field::BitField;
+
+const HEADER_LENGTH: usize = 44;
+
+/// Errors that can occur while parsing a multiple APIC description table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MadtError {
+    /// The table signature is not `APIC`.
+    InvalidSignature,
+
+    /// The length in the table header is shorter than the header, or longer than the
+    /// provided bytes.
+    InvalidLength,
+
+    /// The bytes of the table do not sum to zero.
+    InvalidChecksum,
+
+    /// An interrupt controller structure is truncated, or shorter than its type requires.
+    MalformedEntry { entry_type: u8 },
+
+    /// The table describes more structures of one kind than the topology can hold.
+    CapacityExceeded,
+}
+
+/// Identifies a local interrupt pin of a local APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalInterruptPin {
+    LINT0,
+    LINT1,
+}
+
+/// A processor local APIC or local x2APIC structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Processor {
+    /// ACPI processor UID, used to match the processor with its local APIC NMI structures.
+    pub acpi_uid: u32,
+
+    /// The processor's local APIC ID.
+    pub apic_id: u32,
+
+    /// Whether the processor is usable.
+    pub enabled: bool,
+
+    /// Whether a disabled processor can be enabled at runtime.
+    pub online_capable: bool,
+}
+
+/// An I/O APIC structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApicDescriptor {
+    /// The I/O APIC's ID.
+    pub id: u8,
+
+    /// Physical address of the I/O APIC register window.
+    pub address: u32,
+
+    /// The global system interrupt number of the I/O APIC's first input pin.
+    pub gsi_base: u32,
+}
+
+/// An interrupt source override structure, describing how an ISA interrupt is routed to a
+/// global system interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptSourceOverride {
+    /// The bus the interrupt source is on (`0` for ISA).
+    pub bus: u8,
+
+    /// The bus-relative interrupt source (IRQ).
+    pub source: u8,
+
+    /// The global system interrupt the source is routed to.
+    pub gsi: u32,
+
+    /// Polarity of the interrupt, or `None` if it conforms to the bus specification.
+    pub polarity: Option<PinPolarity>,
+
+    /// Trigger mode of the interrupt, or `None` if it conforms to the bus specification.
+    pub trigger_mode: Option<InterruptTriggerMode>,
+}
+
+/// A local APIC NMI or local x2APIC NMI structure, describing which local interrupt pin
+/// non-maskable interrupts are connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalApicNmi {
+    /// ACPI processor UID of the processor the structure applies to, or `None` if it applies
+    /// to all processors.
+    pub acpi_uid: Option<u32>,
+
+    /// The local interrupt pin the non-maskable interrupt is connected to.
+    pub pin: LocalInterruptPin,
+
+    /// Polarity of the interrupt, or `None` if it conforms to the bus specification.
+    pub polarity: Option<PinPolarity>,
+
+    /// Trigger mode of the interrupt, or `None` if it conforms to the bus specification.
+    pub trigger_mode: Option<InterruptTriggerMode>,
+}
+
+impl LocalApicNmi {
+    /// Whether this structure applies to the processor with ACPI processor UID `acpi_uid`.
+    pub fn applies_to(&self, acpi_uid: u32) -> bool {
+        self.acpi_uid.is_none_or(|uid| uid == acpi_uid)
+    }
+}
+
+/// An interrupt controller structure of the multiple APIC description table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MadtEntry {
+    /// Type 0 (processor local APIC) or type 9 (processor local x2APIC).
+    Processor(Processor),
+
+    /// Type 1.
+    IoApic(IoApicDescriptor),
+
+    /// Type 2.
+    InterruptSourceOverride(InterruptSourceOverride),
+
+    /// Type 4 (local APIC NMI) or type 10 (local x2APIC NMI).
+    LocalApicNmi(LocalApicNmi),
+
+    /// Type 5.
+    LocalApicAddressOverride { address: u64 },
+
+    /// Any structure type that is not interpreted by this parser.
+    Unknown { entry_type: u8 },
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Option<u8> {
+    bytes.get(offset).copied()
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Decodes the polarity and trigger mode of MPS INTI flags.
+fn decode_inti_flags(flags: u16) -> (Option<PinPolarity>, Option<InterruptTriggerMode>) {
+    let polarity = match flags.get_bits(0..2) {
+        0b01 => Some(PinPolarity::ActiveHigh),
+        0b11 => Some(PinPolarity::ActiveLow),
+        _ => None,
+    };
+
+    let trigger_mode = match flags.get_bits(2..4) {
+        0b01 => Some(InterruptTriggerMode::Edge),
+        0b11 => Some(InterruptTriggerMode::Level),
+        _ => None,
+    };
+
+    (polarity, trigger_mode)
+}
+
+fn decode_lint(lint: u8) -> Option<LocalInterruptPin> {
+    match lint {
+        0 => Some(LocalInterruptPin::LINT0),
+        1 => Some(LocalInterruptPin::LINT1),
+        _ => None,
+    }
+}
+
+impl MadtEntry {
+    fn parse(entry_type: u8, bytes: &[u8]) -> Option<Self> {
+        let entry = match entry_type {
+            0 => {
+                let flags = read_u32(bytes, 4)?;
+
+                Self::Processor(Processor {
+                    acpi_uid: u32::from(read_u8(bytes, 2)?),
+                    apic_id: u32::from(read_u8(bytes, 3)?),
+                    enabled: flags.get_bit(0),
+                    online_capable: flags.get_bit(1),
+                })
+            }
+
+            1 => Self::IoApic(IoApicDescriptor {
+                id: read_u8(bytes, 2)?,
+                address: read_u32(bytes, 4)?,
+                gsi_base: read_u32(bytes, 8)?,
+            }),
+
+            2 => {
+                let (polarity, trigger_mode) = decode_inti_flags(read_u16(bytes, 8)?);
+
+                Self::InterruptSourceOverride(InterruptSourceOverride {
+                    bus: read_u8(bytes, 2)?,
+                    source: read_u8(bytes, 3)?,
+                    gsi: read_u32(bytes, 4)?,
+                    polarity,
+                    trigger_mode,
+                })
+            }
+
+            4 => {
+                let acpi_uid = read_u8(bytes, 2)?;
+                let (polarity, trigger_mode) = decode_inti_flags(read_u16(bytes, 3)?);
+
+                Self::LocalApicNmi(LocalApicNmi {
+                    acpi_uid: (acpi_uid != 0xFF).then_some(u32::from(acpi_uid)),
+                    pin: decode_lint(read_u8(bytes, 5)?)?,
+                    polarity,
+                    trigger_mode,
+                })
+            }
+
+            5 => Self::LocalApicAddressOverride {
+                address: read_u64(bytes, 4)?,
+            },
+
+            9 => {
+                let flags = read_u32(bytes, 8)?;
+
+                Self::Processor(Processor {
+                    acpi_uid: read_u32(bytes, 12)?,
+                    apic_id: read_u32(bytes, 4)?,
+                    enabled: flags.get_bit(0),
+                    online_capable: flags.get_bit(1),
+                })
+            }
+
+            10 => {
+                let (polarity, trigger_mode) = decode_inti_flags(read_u16(bytes, 2)?);
+                let acpi_uid = read_u32(bytes, 4)?;
+
+                Self::LocalApicNmi(LocalApicNmi {
+                    acpi_uid: (acpi_uid != 0xFFFF_FFFF).then_some(acpi_uid),
+                    pin: decode_lint(read_u8(bytes, 8)?)?,
+                    polarity,
+                    trigger_mode,
+                })
+            }
+
+            entry_type => Self::Unknown { entry_type },
+        };
+
+        Some(entry)
+    }
+}
+
+/// Iterator over the interrupt controller structures of a [`Madt`].
+#[derive(Debug, Clone)]
+pub struct Entries<'a>(&'a [u8]);
+
+impl Iterator for Entries<'_> {
+    type Item = Result<MadtEntry, MadtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry_type = *self.0.first()?;
+        let malformed = MadtError::MalformedEntry { entry_type };
+
+        let length = match self.0.get(1) {
+            Some(&length) if length >= 2 && usize::from(length) <= self.0.len() => {
+                usize::from(length)
+            }
+            _ => {
+                self.0 = &[];
+                return Some(Err(malformed));
+            }
+        };
+
+        let (bytes, remaining) = self.0.split_at(length);
+        self.0 = remaining;
+
+        Some(MadtEntry::parse(entry_type, bytes).ok_or(malformed))
+    }
+}
+
+/// A parsed multiple APIC description table (ACPI signature `APIC`).
+#[derive(Debug, Clone, Copy)]
+pub struct Madt<'a> {
+    local_apic_address: u32,
+    flags: u32,
+    entries: &'a [u8],
+}
+
+impl<'a> Madt<'a> {
+    /// Parses the table in `bytes`, which must begin with the ACPI system description table
+    /// header. The signature, length and checksum of the table are validated.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, MadtError> {
+        if bytes.get(..4) != Some(b"APIC") {
+            return Err(MadtError::InvalidSignature);
+        }
+
+        let length = read_u32(bytes, 4)
+            .and_then(|length| usize::try_from(length).ok())
+            .ok_or(MadtError::InvalidLength)?;
+        if length < HEADER_LENGTH || length > bytes.len() {
+            return Err(MadtError::InvalidLength);
+        }
+
+        let table = &bytes[..length];
+        if table.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(MadtError::InvalidChecksum);
+        }
+
+        Ok(Self {
+            local_apic_address: read_u32(table, 36).unwrap(),
+            flags: read_u32(table, 40).unwrap(),
+            entries: &table[HEADER_LENGTH..],
+        })
+    }
+
+    /// Physical address of the local APIC register page, as specified in the table header.
+    ///
+    /// Note: This may be superseded by a local APIC address override structure.
+    pub fn local_apic_address(&self) -> u32 {
+        self.local_apic_address
+    }
+
+    /// Whether the system also has dual 8259-compatible interrupt controllers, which must be
+    /// disabled before the I/O APICs are used.
+    pub fn has_legacy_pics(&self) -> bool {
+        self.flags.get_bit(0)
+    }
+
+    /// Iterates over the interrupt controller structures of the table.
+    pub fn entries(&self) -> Entries<'a> {
+        Entries(self.entries)
+    }
+
+    /// Collects the interrupt controller structures of the table into an [`ApicTopology`],
+    /// which can hold up to `N` structures of each kind.
+    pub fn topology<const N: usize>(&self) -> Result<ApicTopology<N>, MadtError> {
+        let mut topology = ApicTopology {
+            local_apic_address: u64::from(self.local_apic_address),
+            has_legacy_pics: self.has_legacy_pics(),
+            processors: List::new(),
+            io_apics: List::new(),
+            overrides: List::new(),
+            nmis: List::new(),
+        };
+
+        for entry in self.entries() {
+            match entry? {
+                MadtEntry::Processor(processor) => topology.processors.push(processor)?,
+                MadtEntry::IoApic(io_apic) => topology.io_apics.push(io_apic)?,
+                MadtEntry::InterruptSourceOverride(source_override) => {
+                    topology.overrides.push(source_override)?;
+                }
+                MadtEntry::LocalApicNmi(nmi) => topology.nmis.push(nmi)?,
+                MadtEntry::LocalApicAddressOverride { address } => {
+                    topology.local_apic_address = address;
+                }
+                MadtEntry::Unknown { .. } => {}
+            }
+        }
+
+        Ok(topology)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct List<T, const N: usize> {
+    items: [Option<T>; N],
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> List<T, N> {
+    fn new() -> Self {
+        Self {
+            items: [None; N],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, item: T) -> Result<(), MadtError> {
+        let slot = self
+            .items
+            .get_mut(self.len)
+            .ok_or(MadtError::CapacityExceeded)?;
+        *slot = Some(item);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items[..self.len].iter().flatten()
+    }
+}
+
+/// The APIC topology of the system, as described by the multiple APIC description table.
+#[derive(Debug, Clone)]
+pub struct ApicTopology<const N: usize> {
+    local_apic_address: u64,
+    has_legacy_pics: bool,
+    processors: List<Processor, N>,
+    io_apics: List<IoApicDescriptor, N>,
+    overrides: List<InterruptSourceOverride, N>,
+    nmis: List<LocalApicNmi, N>,
+}
+
+impl<const N: usize> ApicTopology<N> {
+    /// Physical address of the local APIC register page, with any address override applied.
+    pub fn local_apic_address(&self) -> u64 {
+        self.local_apic_address
+    }
+
+    /// Whether the system also has dual 8259-compatible interrupt controllers.
+    pub fn has_legacy_pics(&self) -> bool {
+        self.has_legacy_pics
+    }
+
+    /// The processors of the system, in table order. By convention, the bootstrap processor
+    /// is listed first.
+    pub fn processors(&self) -> impl Iterator<Item = &Processor> {
+        self.processors.iter()
+    }
+
+    /// The I/O APICs of the system.
+    pub fn io_apics(&self) -> impl Iterator<Item = &IoApicDescriptor> {
+        self.io_apics.iter()
+    }
+
+    /// The I/O APIC which handles global system interrupt `gsi`, given that it has
+    /// `pin_count` input pins.
+    pub fn io_apic_for_gsi(
+        &self,
+        gsi: u32,
+        pin_count: impl Fn(&IoApicDescriptor) -> u32,
+    ) -> Option<&IoApicDescriptor> {
+        self.io_apics().find(|io_apic| {
+            let gsi_end = io_apic.gsi_base.saturating_add(pin_count(io_apic));
+            (io_apic.gsi_base..gsi_end).contains(&gsi)
+        })
+    }
+
+    /// The interrupt source overrides of the system.
+    pub fn interrupt_source_overrides(&self) -> impl Iterator<Item = &InterruptSourceOverride> {
+        self.overrides.iter()
+    }
+
+    /// The global system interrupt that ISA interrupt `irq` is routed to.
+    pub fn isa_irq_to_gsi(&self, irq: u8) -> u32 {
+        self.interrupt_source_overrides()
+            .find(|source_override| source_override.bus == 0 && source_override.source == irq)
+            .map_or(u32::from(irq), |source_override| source_override.gsi)
+    }
+
+    /// The local APIC NMI structures of the system.
+    pub fn local_apic_nmis(&self) -> impl Iterator<Item = &LocalApicNmi> {
+        self.nmis.iter()
+    }
+
+    /// The local APIC NMI structures that apply to the processor with ACPI processor UID
+    /// `acpi_uid`.
+    pub fn local_apic_nmis_for(&self, acpi_uid: u32) -> impl Iterator<Item = &LocalApicNmi> {
+        self.local_apic_nmis()
+            .filter(move |nmi| nmi.applies_to(acpi_uid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TableBuilder {
+        bytes: [u8; 256],
+        len: usize,
+    }
+
+    impl TableBuilder {
+        fn new(local_apic_address: u32, flags: u32) -> Self {
+            let mut builder = Self {
+                bytes: [0; 256],
+                len: 0,
+            };
+
+            builder.extend(b"APIC");
+            builder.extend(&[0; 4]);
+            builder.extend(&[4, 0]);
+            builder.extend(b"LINUIZ");
+            builder.extend(b"APICTEST");
+            builder.extend(&[0; 12]);
+            builder.extend(&local_apic_address.to_le_bytes());
+            builder.extend(&flags.to_le_bytes());
+
+            builder
+        }
+
+        fn extend(&mut self, bytes: &[u8]) -> &mut Self {
+            self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+            self
+        }
+
+        fn finish(&mut self) -> &[u8] {
+            let length = u32::try_from(self.len).unwrap();
+            self.bytes[4..8].copy_from_slice(&length.to_le_bytes());
+
+            let sum = self.bytes[..self.len]
+                .iter()
+                .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            self.bytes[9] = 0u8.wrapping_sub(sum);
+
+            &self.bytes[..self.len]
+        }
+    }
+
+    fn sample_table() -> TableBuilder {
+        let mut builder = TableBuilder::new(0xFEE0_0000, 1);
+        // Processor local APIC: UID 0, ID 0, enabled.
+        builder.extend(&[0, 8, 0, 0, 1, 0, 0, 0]);
+        // Processor local APIC: UID 1, ID 2, online capable.
+        builder.extend(&[0, 8, 1, 2, 2, 0, 0, 0]);
+        // Processor local x2APIC: ID 0x100, UID 2, enabled.
+        builder.extend(&[9, 16, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+        // I/O APIC: ID 4, address 0xFEC00000, GSI base 0.
+        builder.extend(&[1, 12, 4, 0, 0x00, 0x00, 0xC0, 0xFE, 0, 0, 0, 0]);
+        // Interrupt source override: ISA IRQ 0 -> GSI 2, conforming.
+        builder.extend(&[2, 10, 0, 0, 2, 0, 0, 0, 0, 0]);
+        // Interrupt source override: ISA IRQ 9 -> GSI 9, level-triggered, active-low.
+        builder.extend(&[2, 10, 0, 9, 9, 0, 0, 0, 0x0F, 0]);
+        // Local APIC NMI: all processors, LINT1, edge-triggered, active-high.
+        builder.extend(&[4, 6, 0xFF, 0x05, 0, 1]);
+        // Local x2APIC NMI: UID 2, LINT0.
+        builder.extend(&[10, 12, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        // Unknown structure type.
+        builder.extend(&[0x7F, 4, 0, 0]);
+        // Local APIC address override.
+        builder.extend(&[5, 12, 0, 0, 0x00, 0x00, 0xE0, 0xFE, 0x01, 0, 0, 0]);
+
+        builder
+    }
+
+    #[test]
+    fn parses_topology() {
+        let mut builder = sample_table();
+        let madt = Madt::parse(builder.finish()).unwrap();
+
+        assert_eq!(madt.local_apic_address(), 0xFEE0_0000);
+        assert!(madt.has_legacy_pics());
+
+        let topology = madt.topology::<8>().unwrap();
+        assert_eq!(topology.local_apic_address(), 0x1_FEE0_0000);
+
+        assert!(topology.processors().eq(&[
+            Processor {
+                acpi_uid: 0,
+                apic_id: 0,
+                enabled: true,
+                online_capable: false
+            },
+            Processor {
+                acpi_uid: 1,
+                apic_id: 2,
+                enabled: false,
+                online_capable: true
+            },
+            Processor {
+                acpi_uid: 2,
+                apic_id: 0x100,
+                enabled: true,
+                online_capable: false
+            },
+        ]));
+
+        let io_apic = topology.io_apics().next().unwrap();
+        assert_eq!(io_apic.id, 4);
+        assert_eq!(io_apic.address, 0xFEC0_0000);
+        assert_eq!(topology.io_apic_for_gsi(23, |_| 24), Some(io_apic));
+        assert_eq!(topology.io_apic_for_gsi(24, |_| 24), None);
+        assert_eq!(topology.io_apic_for_gsi(24, |_| u32::MAX), Some(io_apic));
+
+        assert_eq!(topology.isa_irq_to_gsi(0), 2);
+        assert_eq!(topology.isa_irq_to_gsi(1), 1);
+
+        let sci_override = topology.interrupt_source_overrides().nth(1).unwrap();
+        assert_eq!(sci_override.polarity, Some(PinPolarity::ActiveLow));
+        assert_eq!(sci_override.trigger_mode, Some(InterruptTriggerMode::Level));
+
+        let nmi = topology.local_apic_nmis_for(0).next().unwrap();
+        assert_eq!(nmi.pin, LocalInterruptPin::LINT1);
+        assert_eq!(nmi.polarity, Some(PinPolarity::ActiveHigh));
+        assert_eq!(nmi.trigger_mode, Some(InterruptTriggerMode::Edge));
+        assert_eq!(topology.local_apic_nmis_for(2).count(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_tables() {
+        let mut builder = sample_table();
+        let bytes = builder.finish();
+
+        let mut corrupted = [0; 256];
+        corrupted[..bytes.len()].copy_from_slice(bytes);
+        corrupted[50] ^= 0xFF;
+        assert_eq!(
+            Madt::parse(&corrupted[..bytes.len()]).unwrap_err(),
+            MadtError::InvalidChecksum
+        );
+
+        assert_eq!(
+            Madt::parse(&bytes[..bytes.len() - 1]).unwrap_err(),
+            MadtError::InvalidLength
+        );
+        assert_eq!(
+            Madt::parse(b"FACP").unwrap_err(),
+            MadtError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let mut builder = TableBuilder::new(0xFEE0_0000, 0);
+        // I/O APIC structure that is too short for its type.
+        builder.extend(&[1, 6, 4, 0, 0, 0]);
+        let madt = Madt::parse(builder.finish()).unwrap();
+
+        assert_eq!(
+            madt.topology::<8>().unwrap_err(),
+            MadtError::MalformedEntry { entry_type: 1 }
+        );
+
+        let mut builder = sample_table();
+        let madt = Madt::parse(builder.finish()).unwrap();
+        assert_eq!(
+            madt.topology::<2>().unwrap_err(),
+            MadtError::CapacityExceeded
+        );
+    }
+}
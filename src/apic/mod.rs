@@ -2,13 +2,60 @@ use bit_field::BitField;
 use core::{arch::asm, fmt, marker::PhantomData};
 use local_vector::*;
 
+pub mod apic_error;
+pub mod calibration;
+pub mod hpet;
+pub mod ioapic;
+pub mod ipi;
 pub mod local_vector;
+pub mod madt;
+pub mod msi;
+pub mod pic8259;
+pub mod port;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod smp;
+pub mod timer;
+pub mod trace;
 pub mod x1;
 pub mod x2;
 
+mod cpu_set;
+pub use cpu_set::*;
+
+mod destination;
+pub use destination::*;
+
+mod discovery;
+pub use discovery::*;
+
+mod dump;
+pub use dump::*;
+
+#[cfg(test)]
+mod fmt_buffer;
+
+mod init;
+pub use init::*;
+
 mod interrupt_command;
 pub use interrupt_command::*;
 
+mod priority;
+pub use priority::*;
+
+mod register;
+pub use register::*;
+
+mod snapshot;
+pub use snapshot::*;
+
+mod vector_allocator;
+pub use vector_allocator::*;
+
+mod vector_bitmap;
+pub use vector_bitmap::*;
+
 /// Gets the value of the `IA32_APIC_BASE` model-specific register.
 fn get_ia32_apic_base() -> u64 {
     let value_low: u64;
@@ -29,7 +76,7 @@ fn get_ia32_apic_base() -> u64 {
 
 /// Sets the value of the `IA32_APIC_BASE` model-specific register.
 unsafe fn set_ia32_apic_base(value: u64) {
-    let value_low = value & 0xFFFF;
+    let value_low = value & 0xFFFF_FFFF;
     let value_high = value >> 32;
 
     unsafe {
@@ -43,8 +90,43 @@ unsafe fn set_ia32_apic_base(value: u64) {
     }
 }
 
+/// Gets the value of the `IA32_TSC_DEADLINE` model-specific register.
+fn get_ia32_tsc_deadline() -> u64 {
+    let value_low: u64;
+    let value_high: u64;
+
+    unsafe {
+        asm!(
+            "rdmsr",
+            in("ecx") 0x6E0,
+            out("edx") value_high,
+            out("eax") value_low,
+            options(nostack, nomem, preserves_flags)
+        );
+    }
+
+    (value_high << 32) | value_low
+}
+
+/// Sets the value of the `IA32_TSC_DEADLINE` model-specific register.
+unsafe fn set_ia32_tsc_deadline(value: u64) {
+    let value_low = value & 0xFFFF_FFFF;
+    let value_high = value >> 32;
+
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") 0x6E0,
+            in("edx") value_high,
+            in("eax") value_low,
+            options(nostack, nomem, preserves_flags)
+        );
+    }
+}
+
 /// Specifies the version of an APIC device, the number of local vector
 /// table entries, and whether software can suppress end-of-interrupt broadcasts.
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Version(pub(crate) u32);
 
 impl Version {
@@ -90,7 +172,7 @@ impl fmt::Debug for Version {
 
 bitflags! {
     #[repr(transparent)]
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct ErrorStatus: u32 {
         const SEND_CHECKSUM_ERROR = 1 << 0;
         const RECEIVE_CHECKSUM_ERROR = 1 << 1;
@@ -103,6 +185,69 @@ bitflags! {
     }
 }
 
+/// Remote read register value: the contents of a register read from another local APIC with
+/// a remote read interrupt command. Only present in xAPIC mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteRead(pub(crate) u32);
+
+impl RemoteRead {
+    /// The value of the remotely read register.
+    pub fn get_value(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Errors reported by the fallible `try_*` variants of setters and decoders, in place of the
+/// panics of the infallible variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    /// Vectors 0..=15 are reserved.
+    ReservedVector(u8),
+
+    /// A field holds, or would be set to, a reserved encoding.
+    ReservedEncoding { field: &'static str, value: u32 },
+
+    /// A value does not fit in its field.
+    OutOfRange { field: &'static str, value: u32 },
+
+    /// Fields hold values that are valid on their own, but not in combination.
+    InvalidCombination(&'static str),
+
+    /// The processor or device does not support the feature.
+    Unsupported(&'static str),
+
+    /// The register or feature is not available in the current mode of the local APIC.
+    UnavailableInMode(&'static str),
+
+    /// The register is not present, or is not readable, in the current mode of the local APIC.
+    IllegalRead(Register),
+
+    /// The register is not present, or is not writable, in the current mode of the local APIC.
+    IllegalWrite(Register),
+
+    /// The local APIC did not accept an interrupt command in time.
+    DeliveryTimeout,
+}
+
+impl From<ipi::IpiError> for ApicError {
+    fn from(value: ipi::IpiError) -> Self {
+        match value {
+            ipi::IpiError::ReservedVector(vector) => Self::ReservedVector(vector),
+            ipi::IpiError::DestinationOutOfRange(destination) => Self::OutOfRange {
+                field: "destination",
+                value: destination,
+            },
+            ipi::IpiError::LowestPriorityUnsupported => {
+                Self::UnavailableInMode("lowest-priority delivery")
+            }
+            ipi::IpiError::InitDeassertUnsupported => {
+                Self::UnavailableInMode("INIT level de-assert")
+            }
+            ipi::IpiError::DeliveryTimeout => Self::DeliveryTimeout,
+        }
+    }
+}
+
 /// A special situation may occur when a processor raises its task priority to be greater
 /// than or equal to the level of the interrupt for which the processor INTR signal is
 /// currently being asserted. If at the time the INTA cycle is issued, the interrupt that
@@ -166,7 +311,7 @@ impl<M: Mode> SpuriousInterrupt<'_, M> {
 
 bitflags! {
     #[repr(transparent)]
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct TimerDivideConfiguration: u32 {
         const DIVIDE_1      = 0b1011;
         const DIVIDE_2      = 0b0000;
@@ -179,22 +324,43 @@ bitflags! {
     }
 }
 
+impl TimerDivideConfiguration {
+    /// Every divide configuration, from the smallest divisor to the largest.
+    pub const ALL: [Self; 8] = [
+        Self::DIVIDE_1,
+        Self::DIVIDE_2,
+        Self::DIVIDE_4,
+        Self::DIVIDE_8,
+        Self::DIVIDE_16,
+        Self::DIVIDE_32,
+        Self::DIVIDE_64,
+        Self::DIVIDE_128,
+    ];
+
+    /// The value the timer's base clock is divided by.
+    pub fn divisor(&self) -> u32 {
+        // Bit 2 is reserved, and bits 0, 1 and 3 encode the power of two (less 1).
+        let encoded = self.bits().get_bits(0..2) | (self.bits().get_bit(3) as u32) << 2;
+        1 << ((encoded + 1) % 8)
+    }
+}
+
 pub const xAPIC_BASE_ADDR: usize = 0xFEE00000;
 pub const x2APIC_BASE_MSR_ADDR: u32 = 0x800;
 
 pub trait Mode {
     type Inner: Clone;
-    type Register: Copy;
 
-    const ID: Self::Register;
-    const VERSION: Self::Register;
-    const ERROR_STATUS: Self::Register;
-    const SPURIOUS_INTERRUPT: Self::Register;
-    const INTERRUPT_COMMAND_LOW: Self::Register;
-    const INTERRUPT_COMMAND_HIGH: Self::Register;
+    /// Whether the mode accesses the local APIC in x2APIC mode.
+    const EXTENDED: bool;
 
-    fn read_register_raw(inner: Self::Inner, register: Self::Register) -> u32;
-    fn write_register_raw(inner: Self::Inner, register: Self::Register, value: u32);
+    /// Reads `register` without checking that it is readable in this mode.
+    ///
+    /// In x2APIC mode, only the low doubleword of the 64-bit interrupt command register is
+    /// returned.
+    fn read_register_raw(inner: Self::Inner, register: Register) -> u32;
+    /// Writes `register` without checking that it is writable in this mode.
+    fn write_register_raw(inner: Self::Inner, register: Register, value: u32);
 
     fn get_id(inner: Self::Inner) -> u32;
     fn get_version(inner: Self::Inner) -> Version;
@@ -207,6 +373,14 @@ pub trait Mode {
 
     fn get_remote_read(inner: Self::Inner) -> RemoteRead;
     fn get_local_destination(inner: Self::Inner) -> LocalDestination;
+    fn set_local_destination(inner: Self::Inner, value: LocalDestination);
+
+    fn get_destination_format(inner: Self::Inner) -> DestinationFormat;
+    fn set_destination_format(inner: Self::Inner, value: DestinationFormat);
+
+    fn get_in_service(inner: Self::Inner) -> VectorBitmap;
+    fn get_trigger_mode(inner: Self::Inner) -> VectorBitmap;
+    fn get_interrupt_request(inner: Self::Inner) -> VectorBitmap;
 
     fn get_error_status(inner: Self::Inner) -> ErrorStatus;
     fn clear_error_status(inner: Self::Inner);
@@ -219,7 +393,13 @@ pub trait Mode {
     fn get_timer_divide_configuration(inner: Self::Inner) -> TimerDivideConfiguration;
     fn set_timer_divide_configuration(inner: Self::Inner, value: TimerDivideConfiguration);
 
+    /// Whether the local APIC timer supports TSC-deadline mode.
+    fn is_tsc_deadline_supported(inner: Self::Inner) -> bool;
+    fn get_tsc_deadline(inner: Self::Inner) -> u64;
+    fn set_tsc_deadline(inner: Self::Inner, deadline: u64);
+
     fn send_interrupt_command(inner: Self::Inner, interrupt_command: InterruptCommand);
+    fn send_self_ipi(inner: Self::Inner, vector: u8);
 
     fn get_spurious_vector(inner: Self::Inner) -> u8;
     fn get_spurious_apic_software_enabled(inner: Self::Inner) -> bool;
@@ -231,6 +411,8 @@ pub trait Mode {
     fn set_spurious_eoi_broadcast_suppression(inner: Self::Inner, value: bool);
 
     fn get_timer_vector(inner: Self::Inner) -> LocalVector<Timer>;
+    /// Writes the timer's entry, then fences if it selects TSC-deadline mode, so that a
+    /// subsequent write of the deadline is ordered after the mode switch.
     fn set_timer_vector(inner: Self::Inner, value: LocalVector<Timer>);
 
     fn get_cmci_vector(inner: Self::Inner) -> LocalVector<CMCI>;
@@ -257,6 +439,20 @@ pub trait Mode {
 pub struct xApic<M: Mode>(M::Inner);
 
 impl<M: Mode> xApic<M> {
+    /// How many times the delivery status of an interrupt command is polled before giving up.
+    /// Local APICs accept interrupt commands within microseconds, which is far fewer polls.
+    const DELIVERY_POLLS: u32 = 100_000;
+
+    /// Creates a new local APIC handle over `inner`.
+    ///
+    /// # Safety
+    ///
+    /// `inner` must refer to the local APIC of the executing processor, and the local APIC
+    /// must currently be operating in the mode described by `M`.
+    pub unsafe fn new(inner: M::Inner) -> Self {
+        Self(inner)
+    }
+
     pub fn get_id(&self) -> u32 {
         M::get_id(self.0.clone())
     }
@@ -265,374 +461,468 @@ impl<M: Mode> xApic<M> {
         M::get_version(self.0.clone())
     }
 
+    /// Reads the raw value of `register`.
+    ///
+    /// Fails if `register` cannot be accessed raw, or is write-only, in the current mode (see
+    /// [`Register::access`]).
+    pub fn read_register_raw(&self, register: Register) -> Result<u32, ApicError> {
+        match register.access(M::EXTENDED) {
+            Some(access) if access.is_readable() => {
+                Ok(M::read_register_raw(self.0.clone(), register))
+            }
+            _ => Err(ApicError::IllegalRead(register)),
+        }
+    }
+
+    /// Writes the raw `value` to `register`.
+    ///
+    /// Fails if `register` cannot be accessed raw, or is read-only, in the current mode (see
+    /// [`Register::access`]). In x2APIC mode, the end-of-interrupt and error status registers
+    /// only accept zero, as other values raise a general-protection fault. Writes have the side
+    /// effects of the register, such as sending an interrupt or signalling the end of an
+    /// interrupt.
+    pub fn write_register_raw(&self, register: Register, value: u32) -> Result<(), ApicError> {
+        if M::EXTENDED
+            && value != 0
+            && matches!(
+                register,
+                Register::END_OF_INTERRUPT | Register::ERROR_STATUS
+            )
+        {
+            return Err(ApicError::IllegalWrite(register));
+        }
+
+        match register.access(M::EXTENDED) {
+            Some(access) if access.is_writable() => {
+                M::write_register_raw(self.0.clone(), register, value);
+                Ok(())
+            }
+            _ => Err(ApicError::IllegalWrite(register)),
+        }
+    }
+
+    pub fn get_task_priority(&self) -> TaskPriority {
+        M::get_task_priority(self.0.clone())
+    }
+
+    /// Sets the task priority, blocking delivery of interrupts whose priority class is less
+    /// than or equal to its class.
+    pub fn set_task_priority(&self, value: TaskPriority) {
+        M::set_task_priority(self.0.clone(), value);
+    }
+
+    /// Gets the arbitration priority.
+    ///
+    /// # Panics
+    ///
+    /// The arbitration priority register does not exist in x2APIC mode.
+    pub fn get_arbitration_priority(&self) -> ArbitrationPriority {
+        M::get_arbitration_priority(self.0.clone())
+    }
+
+    /// Gets the arbitration priority, or an error in x2APIC mode.
+    pub fn try_get_arbitration_priority(&self) -> Result<ArbitrationPriority, ApicError> {
+        if M::EXTENDED {
+            return Err(ApicError::UnavailableInMode(
+                "arbitration priority register",
+            ));
+        }
+
+        Ok(self.get_arbitration_priority())
+    }
+
+    pub fn get_processor_priority(&self) -> ProcessorPriority {
+        M::get_processor_priority(self.0.clone())
+    }
+
+    /// Gets the result of the last remote read interrupt command.
+    ///
+    /// # Panics
+    ///
+    /// The remote read register does not exist in x2APIC mode.
+    pub fn get_remote_read(&self) -> RemoteRead {
+        M::get_remote_read(self.0.clone())
+    }
+
+    /// Gets the result of the last remote read interrupt command, or an error in x2APIC mode.
+    pub fn try_get_remote_read(&self) -> Result<RemoteRead, ApicError> {
+        if M::EXTENDED {
+            return Err(ApicError::UnavailableInMode("remote read register"));
+        }
+
+        Ok(self.get_remote_read())
+    }
+
+    /// Gets the logical ID of the local APIC.
+    pub fn get_local_destination(&self) -> LocalDestination {
+        M::get_local_destination(self.0.clone())
+    }
+
+    /// Sets the logical ID of the local APIC.
+    ///
+    /// # Panics
+    ///
+    /// The logical destination register is read-only in x2APIC mode.
+    pub fn set_local_destination(&self, value: LocalDestination) {
+        M::set_local_destination(self.0.clone(), value);
+    }
+
+    /// Sets the logical ID of the local APIC, or returns an error in x2APIC mode, or if the ID
+    /// does not fit in the 8 bits of xAPIC mode.
+    pub fn try_set_local_destination(&self, value: LocalDestination) -> Result<(), ApicError> {
+        if M::EXTENDED {
+            return Err(ApicError::UnavailableInMode(
+                "writable logical destination register",
+            ));
+        }
+
+        if value.0 > 0xFF {
+            return Err(ApicError::OutOfRange {
+                field: "logical ID",
+                value: value.0,
+            });
+        }
+
+        self.set_local_destination(value);
+
+        Ok(())
+    }
+
+    /// Gets the destination format (the logical destination model).
+    ///
+    /// # Panics
+    ///
+    /// The destination format register does not exist in x2APIC mode.
+    pub fn get_destination_format(&self) -> DestinationFormat {
+        M::get_destination_format(self.0.clone())
+    }
+
+    /// Gets the destination format, or an error in x2APIC mode.
+    pub fn try_get_destination_format(&self) -> Result<DestinationFormat, ApicError> {
+        if M::EXTENDED {
+            return Err(ApicError::UnavailableInMode("destination format register"));
+        }
+
+        Ok(self.get_destination_format())
+    }
+
+    /// Sets the destination format (the logical destination model).
+    ///
+    /// All local APICs in the system must use the same destination model.
+    ///
+    /// # Panics
+    ///
+    /// The destination format register does not exist in x2APIC mode.
+    pub fn set_destination_format(&self, value: DestinationFormat) {
+        M::set_destination_format(self.0.clone(), value);
+    }
+
+    /// Sets the destination format, or returns an error in x2APIC mode.
+    pub fn try_set_destination_format(&self, value: DestinationFormat) -> Result<(), ApicError> {
+        if M::EXTENDED {
+            return Err(ApicError::UnavailableInMode("destination format register"));
+        }
+
+        self.set_destination_format(value);
+
+        Ok(())
+    }
+
+    /// Selects `model` for logical destinations, and assigns this local APIC the logical ID of
+    /// its APIC ID under `model` (see [`DestinationModel`]).
+    ///
+    /// In x2APIC mode only [`DestinationModel::x2Apic`] is valid, and the logical ID is fixed.
+    /// In xAPIC mode [`DestinationModel::x2Apic`] is not valid.
+    ///
+    /// Returns [`ApicError::UnavailableInMode`] if `model` is not valid in the current mode, or
+    /// [`ApicError::OutOfRange`] if this local APIC cannot be addressed logically under
+    /// `model`.
+    pub fn set_destination_model(&self, model: DestinationModel) -> Result<(), ApicError> {
+        if M::EXTENDED {
+            if model != DestinationModel::x2Apic {
+                return Err(ApicError::UnavailableInMode("xAPIC destination model"));
+            }
+
+            return Ok(());
+        }
+
+        let destination_format = DestinationFormat::try_new(model)?;
+        let apic_id = self.get_id();
+        let local_destination =
+            LocalDestination::for_apic_id(model, apic_id).ok_or(ApicError::OutOfRange {
+                field: "logical APIC ID",
+                value: apic_id,
+            })?;
+
+        self.set_destination_format(destination_format);
+        self.set_local_destination(local_destination);
+
+        Ok(())
+    }
+
+    /// Gets the in-service register: the vectors that have been delivered to the processor
+    /// and are awaiting an end-of-interrupt.
+    pub fn get_in_service(&self) -> VectorBitmap {
+        M::get_in_service(self.0.clone())
+    }
+
+    /// Gets the trigger mode register: the vectors in the interrupt request or in-service
+    /// registers that were accepted as level-triggered, and whose end-of-interrupt will be
+    /// broadcast to the I/O APICs.
+    pub fn get_trigger_mode(&self) -> VectorBitmap {
+        M::get_trigger_mode(self.0.clone())
+    }
+
+    /// Gets the interrupt request register: the vectors that have been accepted by the local
+    /// APIC but not yet delivered to the processor.
+    pub fn get_interrupt_request(&self) -> VectorBitmap {
+        M::get_interrupt_request(self.0.clone())
+    }
+
     pub fn get_error_status(&self) -> ErrorStatus {
         M::get_error_status(self.0.clone())
     }
 
-    pub fn get_spurious_vector(&self) -> SpuriousInterrupt<M> {
+    pub fn clear_error_status(&self) {
+        M::clear_error_status(self.0.clone());
+    }
+
+    pub fn get_spurious_vector(&self) -> SpuriousInterrupt<'_, M> {
         SpuriousInterrupt(self.0.clone(), PhantomData)
     }
-}
 
-// impl Apic {
-//     pub fn new(map_xapic_fn: Option<impl FnOnce(usize) -> *mut u8>) -> Option<Self> {
-//         let ia32_apic_base = get_ia32_apic_base();
-//         let is_hw_enabled = ia32_apic_base.get_bit(11);
-//         let is_x2_mode = ia32_apic_base.get_bit(10);
-
-//         let is_xapic = is_hw_enabled && !is_x2_mode;
-//         let is_x2apic = is_hw_enabled && is_x2_mode;
-
-//         if is_x2apic {
-//             Some(Self(Type::x2APIC))
-//         } else if is_xapic {
-//             let map_xapic_fn = map_xapic_fn.expect("no mapping function provided for xAPIC");
-//             Some(Self(Type::xAPIC(map_xapic_fn(
-//                 IA32_APIC_BASE::get_base_address().try_into().unwrap(),
-//             ))))
-//         } else {
-//             None
-//         }
-//     }
-
-//     /// Reads the given register from the local APIC.
-//     fn read_register(&self, register: Register) -> u32 {
-//         match self.0 {
-//             // Safety: Address provided for xAPIC mapping is required to be valid.
-//             Type::xAPIC(xapic_ptr) => unsafe {
-//                 xapic_ptr
-//                     .add(register.xapic_offset())
-//                     .cast::<u32>()
-//                     .read_volatile()
-//             },
-
-//             // Safety: MSR addresses are known-valid from IA32 SDM.
-//             Type::x2APIC => unsafe { msr::rdmsr(register.x2apic_msr()).try_into().unwrap() },
-//         }
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Writing an invalid value to a register is undefined behaviour.
-//     unsafe fn write_register(&self, register: Register, value: u32) {
-//         match self.0 {
-//             Type::xAPIC(xapic_ptr) => xapic_ptr
-//                 .add(register.xapic_offset())
-//                 .cast::<u32>()
-//                 .write_volatile(value),
-//             Type::x2APIC => msr::wrmsr(register.x2apic_msr(), value.into()),
-//         }
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Given the amount of external contexts that could potentially rely on the APIC, enabling it
-//     /// has the oppurtunity to affect those contexts in undefined ways.
-//     #[inline]
-//     pub unsafe fn sw_enable(&self) {
-//         self.write_register(
-//             Register::SPR,
-//             *self.read_register(Register::SPR).set_bit(8, true),
-//         );
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Given the amount of external contexts that could potentially rely on the APIC, disabling it
-//     /// has the oppurtunity to affect those contexts in undefined ways.
-//     #[inline]
-//     pub unsafe fn sw_disable(&self) {
-//         self.write_register(
-//             Register::SPR,
-//             *self.read_register(Register::SPR).set_bit(8, false),
-//         );
-//     }
-
-//     pub fn get_id(&self) -> u32 {
-//         self.read_register(Register::ID).get_bits(24..32)
-//     }
-
-//     #[inline]
-//     pub fn get_version(&self) -> u32 {
-//         self.read_register(Register::VERSION)
-//     }
-
-//     // TODO maybe unsafe?
-//     #[inline]
-//     pub fn end_of_interrupt(&self) {
-//         unsafe { self.write_register(Register::EOI, 0x0) };
-//     }
-
-//     #[inline]
-//     pub fn get_error_status(&self) -> ErrorStatus {
-//         ErrorStatus::from_bits_truncate(self.read_register(Register::ERR))
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// An invalid or unexpcted interrupt command could potentially put the core in an unusable state.
-//     #[inline]
-//     pub unsafe fn send_int_cmd(&self, interrupt_command: InterruptCommand) {
-//         self.write_register(Register::ICRL, interrupt_command.destination_id());
-//         self.write_register(Register::ICRH, interrupt_command.raw_command());
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// The timer divisor directly affects the tick rate and interrupt rate of the
-//     /// internal local timer clock. Thus, changing the divisor has the potential to
-//     /// cause the same sorts of UB that [`set_timer_initial_count`] can cause.
-//     #[inline]
-//     pub unsafe fn set_timer_divisor(&self, divisor: TimerDivisor) {
-//         self.write_register(Register::TIMER_DIVISOR, divisor.as_divide_value().into());
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Setting the initial count of the timer resets its internal clock. This can lead
-//     /// to a situation where another context is awaiting a specific clock duration, but
-//     /// is instead interrupted later than expected.
-//     #[inline]
-//     pub unsafe fn set_timer_initial_count(&self, count: u32) {
-//         self.write_register(Register::TIMER_INT_CNT, count);
-//     }
-
-//     #[inline]
-//     pub fn get_timer_current_count(&self) -> u32 {
-//         self.read_register(Register::TIMER_CUR_CNT)
-//     }
-
-//     #[inline]
-//     pub fn get_timer(&self) -> LocalVector<Timer> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     #[inline]
-//     pub fn get_lint0(&self) -> LocalVector<LINT0> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     #[inline]
-//     pub fn get_lint1(&self) -> LocalVector<LINT1> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     #[inline]
-//     pub fn get_performance(&self) -> LocalVector<Performance> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     #[inline]
-//     pub fn get_thermal_sensor(&self) -> LocalVector<Thermal> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     #[inline]
-//     pub fn get_error(&self) -> LocalVector<Error> {
-//         LocalVector(self, PhantomData)
-//     }
-
-//     /// Resets the APIC module. The APIC module state is configured as follows:
-//     ///     - Module is software disabled, then enabled at function end.
-//     ///     - TPR and TIMER_INT_CNT are zeroed.
-//     ///     - Timer, Performance, Thermal, and Error local vectors are masked.
-//     ///     - LINT0 & LINT1 are unmasked and assigned to the `LINT0_VECTOR` (253) and `LINT1_VECTOR` (254), respectively.
-//     ///     - The spurious register is configured with the `SPURIOUS_VECTOR` (255).
-//     ///
-//     /// ## Safety
-//     ///
-//     /// The caller must guarantee that software is in a state that is ready to accept the APIC performing a software reset.
-//     pub unsafe fn software_reset(&self, spr_vector: u8, lint0_vector: u8, lint1_vector: u8) {
-//         self.sw_disable();
-
-//         self.write_register(Register::TPR, 0x0);
-//         let modified_spr = *self
-//             .read_register(Register::SPR)
-//             .set_bits(0..8, spr_vector.into());
-//         self.write_register(Register::SPR, modified_spr);
-
-//         self.sw_enable();
-
-//         // IA32 SDM specifies that after a software disable, all local vectors
-//         // are masked, so we need to re-enable the LINTx vectors.
-//         self.get_lint0().set_masked(false).set_vector(lint0_vector);
-//         self.get_lint1().set_masked(false).set_vector(lint1_vector);
-//     }
-// }
-
-// pub trait LocalVectorVariant {
-//     const REGISTER: Register;
-// }
-
-// pub trait GenericVectorVariant: LocalVectorVariant {}
-
-// pub struct Timer;
-// impl LocalVectorVariant for Timer {
-//     const REGISTER: Register = Register::LVT_TIMER;
-// }
-
-// pub struct LINT0;
-// impl LocalVectorVariant for LINT0 {
-//     const REGISTER: Register = Register::LVT_LINT0;
-// }
-// impl GenericVectorVariant for LINT0 {}
-
-// pub struct LINT1;
-// impl LocalVectorVariant for LINT1 {
-//     const REGISTER: Register = Register::LVT_LINT1;
-// }
-// impl GenericVectorVariant for LINT1 {}
-
-// pub struct Performance;
-// impl LocalVectorVariant for Performance {
-//     const REGISTER: Register = Register::LVT_PERF;
-// }
-// impl GenericVectorVariant for Performance {}
-
-// pub struct Thermal;
-// impl LocalVectorVariant for Thermal {
-//     const REGISTER: Register = Register::LVT_THERMAL;
-// }
-// impl GenericVectorVariant for Thermal {}
-
-// pub struct Error;
-// impl LocalVectorVariant for Error {
-//     const REGISTER: Register = Register::LVT_ERR;
-// }
-
-// #[repr(transparent)]
-// pub struct LocalVector<'a, T: LocalVectorVariant>(&'a Apic, PhantomData<T>);
-
-// impl<T: LocalVectorVariant> LocalVector<'_, T> {
-//     const INTERRUPTED_OFFSET: usize = 12;
-//     const MASKED_OFFSET: usize = 16;
-
-//     #[inline]
-//     pub fn get_interrupted(&self) -> bool {
-//         self.0
-//             .read_register(T::REGISTER)
-//             .get_bit(Self::INTERRUPTED_OFFSET)
-//     }
-
-//     #[inline]
-//     pub fn get_masked(&self) -> bool {
-//         self.0
-//             .read_register(T::REGISTER)
-//             .get_bit(Self::MASKED_OFFSET)
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Masking an interrupt may result in contexts expecting that interrupt to fire to deadlock.
-//     #[inline]
-//     pub unsafe fn set_masked(&self, masked: bool) -> &Self {
-//         self.0.write_register(
-//             T::REGISTER,
-//             *self
-//                 .0
-//                 .read_register(T::REGISTER)
-//                 .set_bit(Self::MASKED_OFFSET, masked),
-//         );
-
-//         self
-//     }
-
-//     #[inline]
-//     pub fn get_vector(&self) -> Option<u8> {
-//         match self.0.read_register(T::REGISTER).get_bits(0..8) {
-//             vector if (0..32).contains(&vector) => None,
-//             vector => Some(vector as u8),
-//         }
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Given the vector is an arbitrary >32 `u8`, all contexts must agree on what vectors
-//     /// correspond to what local interrupts.
-//     #[inline]
-//     pub unsafe fn set_vector(&self, vector: u8) -> &Self {
-//         assert!(vector >= 32, "interrupt vectors 0..32 are reserved");
-
-//         self.0.write_register(
-//             T::REGISTER,
-//             *self
-//                 .0
-//                 .read_register(T::REGISTER)
-//                 .set_bits(0..8, vector.into()),
-//         );
-
-//         self
-//     }
-// }
-
-// impl<T: LocalVectorVariant> core::fmt::Debug for LocalVector<'_, T> {
-//     fn fmt(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-//         formatter
-//             .debug_tuple("Local Vector")
-//             .field(&self.0.read_register(T::REGISTER))
-//             .finish()
-//     }
-// }
-
-// impl<T: GenericVectorVariant> LocalVector<'_, T> {
-//     /// ## Safety
-//     ///
-//     /// Setting the incorrect delivery mode may result in interrupts not being received
-//     /// correctly, or being sent to all cores at once.
-//     pub unsafe fn set_delivery_mode(&self, mode: InterruptDeliveryMode) -> &Self {
-//         self.0.write_register(
-//             T::REGISTER,
-//             *self
-//                 .0
-//                 .read_register(T::REGISTER)
-//                 .set_bits(8..11, mode as u32),
-//         );
-
-//         self
-//     }
-// }
-
-// impl LocalVector<'_, Timer> {
-//     #[inline]
-//     pub fn get_mode(&self) -> TimerMode {
-//         TimerMode::try_from(
-//             self.0
-//                 .read_register(<Timer as LocalVectorVariant>::REGISTER)
-//                 .get_bits(17..19),
-//         )
-//         .unwrap()
-//     }
-
-//     /// ## Safety
-//     ///
-//     /// Setting the mode of the timer may result in undefined behaviour if switching modes while
-//     /// the APIC is currently active and ticking (or otherwise expecting the timer to behave in
-//     /// a particular, pre-defined fashion).
-//     pub unsafe fn set_mode(&self, mode: TimerMode) -> &Self {
-//         let tsc_dl_support = core::arch::x86_64::__cpuid(0x1).ecx.get_bit(24);
-
-//         assert!(
-//             mode != TimerMode::TscDeadline || tsc_dl_support,
-//             "TSC deadline is not supported on this CPU."
-//         );
-
-//         self.0.write_register(
-//             <Timer as LocalVectorVariant>::REGISTER,
-//             *self
-//                 .0
-//                 .read_register(<Timer as LocalVectorVariant>::REGISTER)
-//                 .set_bits(17..19, mode as u32),
-//         );
-
-//         if tsc_dl_support {
-//             // IA32 SDM instructs utilizing the `mfence` instruction to ensure all writes to the IA32_TSC_DEADLINE
-//             // MSR are serialized *after* the APIC timer mode switch (`wrmsr` to `IA32_TSC_DEADLINE` is non-serializing).
-//             // Safety: `mfence` has no safety implications.
-//             unsafe {
-//                 core::arch::x86_64::_mm_mfence();
-//             }
-//         }
-
-//         self
-//     }
-// }
+    pub fn get_lint0_vector(&self) -> LocalVector<LINT0> {
+        M::get_lint0_vector(self.0.clone())
+    }
+
+    pub fn set_lint0_vector(&self, value: LocalVector<LINT0>) {
+        M::set_lint0_vector(self.0.clone(), value);
+    }
+
+    pub fn get_lint1_vector(&self) -> LocalVector<LINT1> {
+        M::get_lint1_vector(self.0.clone())
+    }
+
+    pub fn set_lint1_vector(&self, value: LocalVector<LINT1>) {
+        M::set_lint1_vector(self.0.clone(), value);
+    }
+
+    pub fn get_error_vector(&self) -> LocalVector<Error> {
+        M::get_error_vector(self.0.clone())
+    }
+
+    pub fn set_error_vector(&self, value: LocalVector<Error>) {
+        M::set_error_vector(self.0.clone(), value);
+    }
+
+    /// Signals the end of the highest-priority in-service interrupt.
+    pub fn end_of_interrupt(&self) {
+        M::end_of_interrrupt(self.0.clone());
+    }
+
+    pub fn get_timer_vector(&self) -> LocalVector<Timer> {
+        M::get_timer_vector(self.0.clone())
+    }
+
+    /// Writes the timer's local vector table entry.
+    ///
+    /// Panics if the entry selects TSC-deadline mode, and the timer does not support it.
+    pub fn set_timer_vector(&self, value: LocalVector<Timer>) {
+        assert!(
+            value.try_get_mode() != Ok(TimerMode::TscDeadline) || self.is_tsc_deadline_supported(),
+            "TSC deadline mode is not supported by this APIC"
+        );
+
+        M::set_timer_vector(self.0.clone(), value);
+    }
+
+    /// Writes the timer's local vector table entry, or returns an error if the entry selects
+    /// TSC-deadline mode, and the timer does not support it.
+    pub fn try_set_timer_vector(&self, value: LocalVector<Timer>) -> Result<(), ApicError> {
+        if value.try_get_mode() == Ok(TimerMode::TscDeadline) && !self.is_tsc_deadline_supported() {
+            return Err(ApicError::Unsupported("TSC-deadline timer mode"));
+        }
+
+        M::set_timer_vector(self.0.clone(), value);
+
+        Ok(())
+    }
+
+    pub fn get_timer_divide_configuration(&self) -> TimerDivideConfiguration {
+        M::get_timer_divide_configuration(self.0.clone())
+    }
+
+    pub fn set_timer_divide_configuration(&self, value: TimerDivideConfiguration) {
+        M::set_timer_divide_configuration(self.0.clone(), value);
+    }
+
+    pub fn get_timer_initial_count(&self) -> u32 {
+        M::get_timer_initial_count(self.0.clone())
+    }
+
+    pub fn set_timer_initial_count(&self, value: u32) {
+        M::set_timer_initial_count(self.0.clone(), value);
+    }
+
+    pub fn get_timer_current_count(&self) -> u32 {
+        M::get_timer_current_count(self.0.clone())
+    }
+
+    /// Whether the local APIC timer supports TSC-deadline mode.
+    pub fn is_tsc_deadline_supported(&self) -> bool {
+        M::is_tsc_deadline_supported(self.0.clone())
+    }
+
+    /// Reads the armed deadline from `IA32_TSC_DEADLINE`, or zero if the timer is disarmed.
+    pub fn get_tsc_deadline(&self) -> u64 {
+        M::get_tsc_deadline(self.0.clone())
+    }
+
+    /// Writes `deadline` to `IA32_TSC_DEADLINE`. Writing zero disarms the timer.
+    ///
+    /// The write is ignored unless the timer is in TSC-deadline mode. Writing the timer's
+    /// local vector table entry in TSC-deadline mode fences, so that a following deadline write
+    /// is ordered after the mode switch (see [`Mode::set_timer_vector`]).
+    pub fn set_tsc_deadline(&self, deadline: u64) {
+        M::set_tsc_deadline(self.0.clone(), deadline);
+    }
+
+    pub fn send_interrupt_command(&self, interrupt_command: InterruptCommand) {
+        M::send_interrupt_command(self.0.clone(), interrupt_command);
+    }
+
+    /// Sends `interrupt_command`, or returns an error if the local APIC cannot send it in its
+    /// current mode.
+    pub fn try_send_interrupt_command(
+        &self,
+        interrupt_command: InterruptCommand,
+    ) -> Result<(), ApicError> {
+        let delivery_mode = interrupt_command.low().get_bits(8..11);
+        if M::EXTENDED && delivery_mode == u32::from(InterruptDeliveryMode::LowPriority) {
+            return Err(ApicError::UnavailableInMode("lowest-priority delivery"));
+        }
+
+        if !M::EXTENDED && interrupt_command.high() > 0xFF {
+            return Err(ApicError::OutOfRange {
+                field: "destination",
+                value: interrupt_command.high(),
+            });
+        }
+
+        self.send_interrupt_command(interrupt_command);
+
+        Ok(())
+    }
+
+    /// Sends a fixed interrupt delivering `vector` to self.
+    ///
+    /// In x2APIC mode this uses the self IPI register, which is cheaper than the interrupt
+    /// command register. In xAPIC mode only the low doubleword of the interrupt command register
+    /// is written, with the self destination shorthand.
+    pub fn send_self_ipi(&self, vector: u8) {
+        assert!(vector > 15, "interrupts vectors 0..=15 are reserved");
+
+        M::send_self_ipi(self.0.clone(), vector);
+    }
+
+    /// Sends a fixed interrupt delivering `vector` to self, or returns an error if the vector
+    /// is reserved.
+    pub fn try_send_self_ipi(&self, vector: u8) -> Result<(), ApicError> {
+        if vector < 16 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        M::send_self_ipi(self.0.clone(), vector);
+
+        Ok(())
+    }
+
+    /// Builds and sends `ipi`.
+    pub fn send_ipi<K: ipi::Kind>(
+        &self,
+        ipi: ipi::Ipi<K, ipi::Addressed>,
+    ) -> Result<(), ipi::IpiError> {
+        self.send_interrupt_command(ipi.build::<M>()?);
+
+        Ok(())
+    }
+
+    /// Sends a fixed interrupt delivering `vector` to every processor in `targets`, with as
+    /// few interrupt commands as possible.
+    ///
+    /// - If `targets` is [`CpuSet::all`], or [`CpuSet::all`] without this processor, a single
+    ///   command is sent with the matching destination shorthand. A full set therefore
+    ///   addresses every processor in the system, including any whose APIC ID exceeds the
+    ///   capacity of the set.
+    /// - In x2APIC mode, processors that share a logical cluster are sent a single command in
+    ///   logical destination mode.
+    /// - Otherwise, a command is sent to each processor in physical destination mode. In xAPIC
+    ///   mode, each command is only sent after the previous one has been accepted; if it is not
+    ///   accepted in time, [`ipi::IpiError::DeliveryTimeout`] is returned.
+    pub fn send_ipi_many<const N: usize>(
+        &self,
+        targets: &CpuSet<N>,
+        vector: u8,
+    ) -> Result<(), ipi::IpiError> {
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        if targets.is_full() {
+            return self.send_ipi(ipi::Ipi::fixed(vector).all_including_self());
+        }
+
+        let mut others = CpuSet::all();
+        others.remove(self.get_id());
+        if *targets == others {
+            return self.send_ipi(ipi::Ipi::fixed(vector).all_excluding_self());
+        }
+
+        if M::EXTENDED {
+            let destinations = logical_destinations(DestinationModel::x2Apic, targets.iter());
+            if destinations.clone().count() < targets.len() {
+                for destination in destinations {
+                    match destination {
+                        Ok(destination) => {
+                            self.send_ipi(ipi::Ipi::fixed(vector).to_logical(destination))?;
+                        }
+
+                        Err(apic_id) => self.send_ipi(ipi::Ipi::fixed(vector).to(apic_id))?,
+                    }
+                }
+
+                return Ok(());
+            }
+        }
+
+        for apic_id in targets.iter() {
+            self.wait_for_delivery()?;
+            self.send_ipi(ipi::Ipi::fixed(vector).to(apic_id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Spins until the last interrupt command has been accepted, polling its delivery status
+    /// at most [`Self::DELIVERY_POLLS`] times.
+    fn wait_for_delivery(&self) -> Result<(), ipi::IpiError> {
+        for _ in 0..Self::DELIVERY_POLLS {
+            if !self.is_interrupt_command_pending() {
+                return Ok(());
+            }
+
+            core::hint::spin_loop();
+        }
+
+        Err(ipi::IpiError::DeliveryTimeout)
+    }
+
+    /// Whether the previously sent interrupt command has not yet been accepted by its target.
+    ///
+    /// Note: The x2APIC interrupt command register has no delivery status, so this is always
+    ///       `false` in x2APIC mode.
+    pub fn is_interrupt_command_pending(&self) -> bool {
+        !M::EXTENDED
+            && M::read_register_raw(self.0.clone(), Register::INTERRUPT_COMMAND_LOW).get_bit(12)
+    }
+}
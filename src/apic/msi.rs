@@ -0,0 +1,255 @@
+use crate::{ApicError, InterruptDeliveryMode, InterruptDestinationMode, InterruptTriggerMode};
+use bit_field::BitField;
+
+/// Fixed upper bits of every message address targeting the local APICs (bits 20..32).
+const ADDRESS_PREFIX: u64 = 0xFEE;
+
+/// The address and data words of a message signalled interrupt (MSI or MSI-X) targeting the
+/// local APICs.
+///
+/// The same message format is used by the MSI capability and by MSI-X table entries. For
+/// multiple-message MSI, the device modifies the low bits of the vector, so the vector must
+/// be the first of an aligned block (see [`crate::VectorAllocator::allocate_contiguous_aligned`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiMessage {
+    pub address: u64,
+    pub data: u32,
+}
+
+impl Default for MsiMessage {
+    /// A fixed, edge-triggered message to APIC ID 0 in physical destination mode, with vector 0.
+    fn default() -> Self {
+        Self {
+            address: ADDRESS_PREFIX << 20,
+            data: 0,
+        }
+    }
+}
+
+impl MsiMessage {
+    /// A fixed, edge-triggered message delivering `vector` to the processor with `apic_id`.
+    pub fn new(apic_id: u32, vector: u8) -> Self {
+        let mut message = Self::default();
+        message.set_destination(apic_id);
+        message.set_vector(vector);
+
+        message
+    }
+
+    /// Whether the address targets the local APICs.
+    pub fn is_valid(&self) -> bool {
+        self.address.get_bits(20..32) == ADDRESS_PREFIX && self.address.get_bits(32..64) == 0
+    }
+
+    /// Gets the destination of the message, including the extended destination ID.
+    pub fn get_destination(&self) -> u32 {
+        let destination = self.address.get_bits(12..20) | (self.address.get_bits(5..12) << 8);
+        u32::try_from(destination).unwrap()
+    }
+
+    /// Sets the destination of the message.
+    ///
+    /// Destinations above 255 are encoded in the extended destination ID (bits 5..12 of the
+    /// address), which must only be used when the platform supports it, such as under a
+    /// hypervisor that advertises extended destination ID support. Otherwise, such
+    /// destinations require interrupt remapping.
+    pub fn set_destination(&mut self, destination: u32) {
+        assert!(
+            destination < (1 << 15),
+            "message destinations are limited to 15 bits"
+        );
+
+        let destination = u64::from(destination);
+        self.address.set_bits(12..20, destination.get_bits(0..8));
+        self.address.set_bits(5..12, destination.get_bits(8..15));
+    }
+
+    /// Sets the destination of the message, or returns an error if `destination` does not fit
+    /// in the 15 bits of the destination and extended destination ID.
+    pub fn try_set_destination(&mut self, destination: u32) -> Result<(), ApicError> {
+        if destination >= (1 << 15) {
+            return Err(ApicError::OutOfRange {
+                field: "message destination",
+                value: destination,
+            });
+        }
+
+        self.set_destination(destination);
+
+        Ok(())
+    }
+
+    /// Gets whether the redirection hint is set.
+    ///
+    /// With the redirection hint set and logical destination mode, the message is delivered to
+    /// the processor executing at the lowest priority among the destinations.
+    pub fn get_redirection_hint(&self) -> bool {
+        self.address.get_bit(3)
+    }
+
+    /// Sets the redirection hint.
+    pub fn set_redirection_hint(&mut self, redirection_hint: bool) {
+        self.address.set_bit(3, redirection_hint);
+    }
+
+    /// Gets how the destination is interpreted.
+    pub fn get_destination_mode(&self) -> InterruptDestinationMode {
+        if self.address.get_bit(2) {
+            InterruptDestinationMode::Logical
+        } else {
+            InterruptDestinationMode::Physical
+        }
+    }
+
+    /// Sets how the destination is interpreted.
+    pub fn set_destination_mode(&mut self, mode: InterruptDestinationMode) {
+        self.address.set_bit(2, bool::from(mode));
+    }
+
+    /// Gets the interrupt vector number.
+    pub fn get_vector(&self) -> u8 {
+        u8::try_from(self.data.get_bits(..8)).unwrap()
+    }
+
+    /// Sets the interrupt vector number.
+    pub fn set_vector(&mut self, vector: u8) {
+        assert!(vector > 15, "interrupts vectors 0..=15 are reserved");
+
+        self.data.set_bits(..8, u32::from(vector));
+    }
+
+    /// Sets the interrupt vector number, or returns an error if `vector` is reserved.
+    pub fn try_set_vector(&mut self, vector: u8) -> Result<(), ApicError> {
+        if vector < 16 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        self.data.set_bits(..8, u32::from(vector));
+
+        Ok(())
+    }
+
+    /// Gets the type of interrupt to be sent to the processor.
+    pub fn get_delivery_mode(&self) -> InterruptDeliveryMode {
+        self.try_get_delivery_mode().unwrap()
+    }
+
+    /// Gets the type of interrupt to be sent to the processor, or an error if the message
+    /// holds a reserved delivery mode.
+    pub fn try_get_delivery_mode(&self) -> Result<InterruptDeliveryMode, ApicError> {
+        InterruptDeliveryMode::try_from(self.data.get_bits(8..11)).map_err(|value| {
+            ApicError::ReservedEncoding {
+                field: "delivery mode",
+                value,
+            }
+        })
+    }
+
+    /// Specifies the type of interrupt to be sent to the processor.
+    ///
+    /// Note: The start-up delivery mode is not supported by message signalled interrupts.
+    pub fn set_delivery_mode(&mut self, mode: InterruptDeliveryMode) {
+        assert!(
+            mode != InterruptDeliveryMode::StartUp,
+            "start-up delivery mode is not supported by message signalled interrupts"
+        );
+
+        self.data.set_bits(8..11, u32::from(mode));
+    }
+
+    /// Specifies the type of interrupt to be sent to the processor, or returns an error for
+    /// the start-up delivery mode.
+    pub fn try_set_delivery_mode(&mut self, mode: InterruptDeliveryMode) -> Result<(), ApicError> {
+        if mode == InterruptDeliveryMode::StartUp {
+            return Err(ApicError::Unsupported("start-up delivery mode"));
+        }
+
+        self.data.set_bits(8..11, u32::from(mode));
+
+        Ok(())
+    }
+
+    /// Gets the trigger mode of the message.
+    pub fn get_trigger_mode(&self) -> InterruptTriggerMode {
+        if self.data.get_bit(15) {
+            InterruptTriggerMode::Level
+        } else {
+            InterruptTriggerMode::Edge
+        }
+    }
+
+    /// Sets the trigger mode of the message. Level-triggered messages are sent as an assert.
+    pub fn set_trigger_mode(&mut self, mode: InterruptTriggerMode) {
+        let level = bool::from(mode);
+        self.data.set_bit(14, level);
+        self.data.set_bit(15, level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_messages() {
+        let mut message = MsiMessage::new(0x12, 0x41);
+        assert_eq!(message.address, 0xFEE1_2000);
+        assert_eq!(message.data, 0x41);
+        assert!(message.is_valid());
+
+        message.set_destination_mode(InterruptDestinationMode::Logical);
+        message.set_redirection_hint(true);
+        message.set_delivery_mode(InterruptDeliveryMode::LowPriority);
+        message.set_trigger_mode(InterruptTriggerMode::Level);
+        assert_eq!(message.address, 0xFEE1_200C);
+        assert_eq!(message.data, 0xC141);
+        assert_eq!(
+            message.get_delivery_mode(),
+            InterruptDeliveryMode::LowPriority
+        );
+        assert_eq!(message.get_trigger_mode(), InterruptTriggerMode::Level);
+    }
+
+    #[test]
+    fn extended_destination_ids() {
+        let message = MsiMessage::new(0x1234, 0x41);
+        assert_eq!(message.address, 0xFEE3_4240);
+        assert_eq!(message.get_destination(), 0x1234);
+        assert_eq!(
+            message.get_destination_mode(),
+            InterruptDestinationMode::Physical
+        );
+        assert!(!message.get_redirection_hint());
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let mut message = MsiMessage::new(0x12, 0x41);
+        assert_eq!(
+            message.try_set_destination(1 << 15),
+            Err(ApicError::OutOfRange {
+                field: "message destination",
+                value: 1 << 15
+            })
+        );
+        assert_eq!(
+            message.try_set_vector(0x0F),
+            Err(ApicError::ReservedVector(0x0F))
+        );
+        assert!(
+            message
+                .try_set_delivery_mode(InterruptDeliveryMode::StartUp)
+                .is_err()
+        );
+        assert_eq!(message, MsiMessage::new(0x12, 0x41));
+
+        message.data.set_bits(8..11, 0b011);
+        assert_eq!(
+            message.try_get_delivery_mode(),
+            Err(ApicError::ReservedEncoding {
+                field: "delivery mode",
+                value: 0b011
+            })
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    InterruptDeliveryMode, Mode,
+    local_vector::{LINT0, LocalVector},
+    port::PortIo,
+    xApic,
+};
+use bit_field::BitField;
+
+const MASTER_COMMAND: u16 = 0x20;
+const MASTER_DATA: u16 = 0x21;
+const SLAVE_COMMAND: u16 = 0xA0;
+const SLAVE_DATA: u16 = 0xA1;
+
+const IMCR_SELECT: u16 = 0x22;
+const IMCR_DATA: u16 = 0x23;
+
+/// Unused port, written to give the PICs time to settle between initialization words.
+const WAIT: u16 = 0x80;
+
+/// ICW1: initialization, with ICW4 to follow.
+const ICW1_INITIALIZE: u8 = 0x11;
+/// ICW4: 8086/8088 mode.
+const ICW4_8086: u8 = 0x01;
+/// OCW2: non-specific end-of-interrupt.
+const OCW2_END_OF_INTERRUPT: u8 = 0x20;
+/// OCW3: read the in-service register on the next read of the command port.
+const OCW3_READ_IN_SERVICE: u8 = 0x0B;
+
+/// The pair of cascaded legacy 8259 programmable interrupt controllers, with the slave
+/// cascaded on IRQ 2 of the master.
+pub struct Pics<P: PortIo>(P);
+
+impl<P: PortIo> Pics<P> {
+    /// Creates a new driver for the legacy PICs.
+    ///
+    /// # Safety
+    ///
+    /// No other context may access the legacy PICs, or the IMCR, while the driver exists.
+    pub unsafe fn new(ports: P) -> Self {
+        Self(ports)
+    }
+
+    fn read(&mut self, port: u16) -> u8 {
+        // Safety: The constructor requires exclusive access to the PICs.
+        unsafe { self.0.read_u8(port) }
+    }
+
+    fn write(&mut self, port: u16, value: u8) {
+        // Safety: The constructor requires exclusive access to the PICs.
+        unsafe { self.0.write_u8(port, value) }
+    }
+
+    fn write_and_wait(&mut self, port: u16, value: u8) {
+        self.write(port, value);
+        self.write(WAIT, 0);
+    }
+
+    /// Reinitializes the PICs to deliver IRQs 0..=7 at vectors `master_offset..` and IRQs
+    /// 8..=15 at vectors `slave_offset..`, preserving the interrupt masks.
+    ///
+    /// The default offsets overlap the processor exception vectors, so the PICs must be
+    /// remapped even when they are only going to be masked; a spurious IRQ 7 or IRQ 15 can be
+    /// raised regardless of the masks.
+    pub fn remap(&mut self, master_offset: u8, slave_offset: u8) {
+        assert!(
+            master_offset.is_multiple_of(8) && slave_offset.is_multiple_of(8),
+            "PIC vector offsets must be multiples of 8"
+        );
+
+        let (master_mask, slave_mask) = self.get_masks();
+
+        self.write_and_wait(MASTER_COMMAND, ICW1_INITIALIZE);
+        self.write_and_wait(SLAVE_COMMAND, ICW1_INITIALIZE);
+        self.write_and_wait(MASTER_DATA, master_offset);
+        self.write_and_wait(SLAVE_DATA, slave_offset);
+        // The slave is cascaded on IRQ 2 of the master.
+        self.write_and_wait(MASTER_DATA, 1 << 2);
+        self.write_and_wait(SLAVE_DATA, 2);
+        self.write_and_wait(MASTER_DATA, ICW4_8086);
+        self.write_and_wait(SLAVE_DATA, ICW4_8086);
+
+        self.set_masks(master_mask, slave_mask);
+    }
+
+    /// Gets the interrupt masks of the master and slave PICs.
+    pub fn get_masks(&mut self) -> (u8, u8) {
+        (self.read(MASTER_DATA), self.read(SLAVE_DATA))
+    }
+
+    /// Sets the interrupt masks of the master and slave PICs.
+    pub fn set_masks(&mut self, master_mask: u8, slave_mask: u8) {
+        self.write(MASTER_DATA, master_mask);
+        self.write(SLAVE_DATA, slave_mask);
+    }
+
+    /// Masks every IRQ.
+    pub fn mask_all(&mut self) {
+        self.set_masks(0xFF, 0xFF);
+    }
+
+    /// Routes the external interrupt and NMI signals to the local APIC rather than directly to
+    /// the processor, through the interrupt mode configuration register.
+    ///
+    /// Only required on systems that implement PIC mode, as indicated by the IMCR flag of the
+    /// MultiProcessor specification floating pointer structure.
+    pub fn disable_via_imcr(&mut self) {
+        self.write(IMCR_SELECT, 0x70);
+        self.write(IMCR_DATA, 0x01);
+    }
+
+    /// Acknowledges `irq`, for an interrupt that was delivered through the PICs.
+    pub fn end_of_interrupt(&mut self, irq: u8) {
+        assert!(irq < 16, "the PICs only have IRQs 0..=15");
+
+        if irq >= 8 {
+            self.write(SLAVE_COMMAND, OCW2_END_OF_INTERRUPT);
+        }
+
+        self.write(MASTER_COMMAND, OCW2_END_OF_INTERRUPT);
+    }
+
+    fn get_in_service(&mut self, command: u16) -> u8 {
+        self.write(command, OCW3_READ_IN_SERVICE);
+        self.read(command)
+    }
+
+    /// Whether an IRQ 7 is spurious: the master raised it, but no longer has it in service. A
+    /// spurious IRQ 7 must not be acknowledged.
+    pub fn is_spurious_irq7(&mut self) -> bool {
+        !self.get_in_service(MASTER_COMMAND).get_bit(7)
+    }
+
+    /// Whether an IRQ 15 is spurious: the slave raised it, but no longer has it in service.
+    ///
+    /// If it is spurious, the master (which did see the cascaded IRQ 2) is acknowledged, and the
+    /// slave must not be.
+    pub fn is_spurious_irq15(&mut self) -> bool {
+        let is_spurious = !self.get_in_service(SLAVE_COMMAND).get_bit(7);
+        if is_spurious {
+            self.write(MASTER_COMMAND, OCW2_END_OF_INTERRUPT);
+        }
+
+        is_spurious
+    }
+}
+
+/// How the LINT0 pin of the local APIC is programmed after transitioning to the APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint0Mode {
+    /// LINT0 delivers external (8259-compatible) interrupts, so that the PICs can still be used
+    /// in virtual wire mode.
+    External,
+
+    /// LINT0 is masked.
+    Masked,
+}
+
+/// Transitions interrupt delivery from the legacy PICs to the local APIC.
+///
+/// - The PICs are remapped to `master_offset` and `slave_offset`, so that spurious IRQs do not
+///   collide with the exception vectors, and every IRQ is masked.
+/// - If `imcr_present`, the IMCR is switched to route interrupts through the local APIC.
+/// - LINT0 is programmed according to `lint0`.
+pub fn transition_to_apic<P: PortIo, M: Mode>(
+    pics: &mut Pics<P>,
+    apic: &xApic<M>,
+    master_offset: u8,
+    slave_offset: u8,
+    imcr_present: bool,
+    lint0: Lint0Mode,
+) {
+    pics.remap(master_offset, slave_offset);
+    pics.mask_all();
+
+    if imcr_present {
+        pics.disable_via_imcr();
+    }
+
+    let mut lint0_vector: LocalVector<LINT0> = apic.get_lint0_vector();
+    match lint0 {
+        Lint0Mode::External => {
+            lint0_vector.set_delivery_mode(InterruptDeliveryMode::External);
+            lint0_vector.set_masked(false);
+        }
+
+        Lint0Mode::Masked => lint0_vector.set_masked(true),
+    }
+
+    apic.set_lint0_vector(lint0_vector);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::RegisterFile;
+
+    /// Fake port bus, recording every write (other than to the wait port).
+    struct FakePorts {
+        writes: [(u16, u8); 32],
+        write_count: usize,
+        data: [u8; 2],
+        in_service: [u8; 2],
+    }
+
+    impl FakePorts {
+        fn new() -> Self {
+            Self {
+                writes: [(0, 0); 32],
+                write_count: 0,
+                data: [0b1010_0000, 0b0000_0101],
+                in_service: [0; 2],
+            }
+        }
+
+        fn writes(&self) -> &[(u16, u8)] {
+            &self.writes[..self.write_count]
+        }
+    }
+
+    impl PortIo for FakePorts {
+        unsafe fn read_u8(&mut self, port: u16) -> u8 {
+            match port {
+                MASTER_DATA => self.data[0],
+                SLAVE_DATA => self.data[1],
+                MASTER_COMMAND => self.in_service[0],
+                SLAVE_COMMAND => self.in_service[1],
+                port => panic!("unexpected read from port {port:#X}"),
+            }
+        }
+
+        unsafe fn write_u8(&mut self, port: u16, value: u8) {
+            match port {
+                WAIT => return,
+                MASTER_DATA => self.data[0] = value,
+                SLAVE_DATA => self.data[1] = value,
+                _ => {}
+            }
+
+            self.writes[self.write_count] = (port, value);
+            self.write_count += 1;
+        }
+
+        unsafe fn read_u32(&mut self, _: u16) -> u32 {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn remaps_and_preserves_masks() {
+        // Safety: The PICs are simulated.
+        let mut pics = unsafe { Pics::new(FakePorts::new()) };
+
+        pics.remap(0x20, 0x28);
+
+        assert_eq!(
+            pics.0.writes(),
+            [
+                (MASTER_COMMAND, 0x11),
+                (SLAVE_COMMAND, 0x11),
+                (MASTER_DATA, 0x20),
+                (SLAVE_DATA, 0x28),
+                (MASTER_DATA, 0x04),
+                (SLAVE_DATA, 0x02),
+                (MASTER_DATA, 0x01),
+                (SLAVE_DATA, 0x01),
+                (MASTER_DATA, 0b1010_0000),
+                (SLAVE_DATA, 0b0000_0101),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_spurious_irqs() {
+        // Safety: The PICs are simulated.
+        let mut pics = unsafe { Pics::new(FakePorts::new()) };
+
+        assert!(pics.is_spurious_irq7());
+        pics.0.in_service[0] = 1 << 7;
+        assert!(!pics.is_spurious_irq7());
+
+        assert!(pics.is_spurious_irq15());
+        assert_eq!(
+            pics.0.writes().last(),
+            Some(&(MASTER_COMMAND, OCW2_END_OF_INTERRUPT))
+        );
+    }
+
+    #[test]
+    fn transitions_to_apic() {
+        // Safety: The PICs are simulated.
+        let mut pics = unsafe { Pics::new(FakePorts::new()) };
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+
+        transition_to_apic(&mut pics, &apic, 0x20, 0x28, true, Lint0Mode::External);
+
+        assert_eq!(pics.get_masks(), (0xFF, 0xFF));
+        assert!(pics.0.writes().ends_with(&[
+            (MASTER_DATA, 0xFF),
+            (SLAVE_DATA, 0xFF),
+            (IMCR_SELECT, 0x70),
+            (IMCR_DATA, 0x01),
+        ]));
+
+        let lint0_vector = apic.get_lint0_vector();
+        assert!(!lint0_vector.get_masked());
+        assert_eq!(u32::from(lint0_vector).get_bits(8..11), 0b111);
+    }
+}
@@ -0,0 +1,78 @@
+use core::arch::asm;
+
+/// Access to the processor's I/O port address space.
+///
+/// Abstracted so that drivers for port-mapped devices can be exercised against a fake
+/// port bus.
+pub trait PortIo {
+    /// Reads a byte from `port`.
+    ///
+    /// # Safety
+    ///
+    /// Reading from an I/O port can have arbitrary device-specific side effects.
+    unsafe fn read_u8(&mut self, port: u16) -> u8;
+
+    /// Writes `value` to `port`.
+    ///
+    /// # Safety
+    ///
+    /// Writing to an I/O port can have arbitrary device-specific side effects.
+    unsafe fn write_u8(&mut self, port: u16, value: u8);
+
+    /// Reads a doubleword from `port`.
+    ///
+    /// # Safety
+    ///
+    /// Reading from an I/O port can have arbitrary device-specific side effects.
+    unsafe fn read_u32(&mut self, port: u16) -> u32;
+}
+
+/// The processor's I/O port address space, accessed with the `in` and `out` instructions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ports;
+
+impl PortIo for Ports {
+    unsafe fn read_u8(&mut self, port: u16) -> u8 {
+        let value: u8;
+
+        // Safety: Caller is required to ensure the read has no undesired side effects.
+        unsafe {
+            asm!(
+                "in al, dx",
+                in("dx") port,
+                out("al") value,
+                options(nostack, nomem, preserves_flags)
+            );
+        }
+
+        value
+    }
+
+    unsafe fn write_u8(&mut self, port: u16, value: u8) {
+        // Safety: Caller is required to ensure the write has no undesired side effects.
+        unsafe {
+            asm!(
+                "out dx, al",
+                in("dx") port,
+                in("al") value,
+                options(nostack, nomem, preserves_flags)
+            );
+        }
+    }
+
+    unsafe fn read_u32(&mut self, port: u16) -> u32 {
+        let value: u32;
+
+        // Safety: Caller is required to ensure the read has no undesired side effects.
+        unsafe {
+            asm!(
+                "in eax, dx",
+                in("dx") port,
+                out("eax") value,
+                options(nostack, nomem, preserves_flags)
+            );
+        }
+
+        value
+    }
+}
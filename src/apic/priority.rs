@@ -0,0 +1,225 @@
+use core::fmt;
+
+use bit_field::BitField;
+
+use crate::ApicError;
+
+/// Priority class of an interrupt vector (its upper 4 bits).
+fn vector_class(vector: u8) -> u8 {
+    vector >> 4
+}
+
+fn check_nibble(field: &'static str, value: u8) -> Result<(), ApicError> {
+    if value >= 16 {
+        return Err(ApicError::OutOfRange {
+            field,
+            value: u32::from(value),
+        });
+    }
+
+    Ok(())
+}
+
+/// Task priority register value.
+///
+/// Software sets the task priority to block the delivery of interrupts whose priority class is
+/// less than or equal to the task priority class. Setting it to zero permits all interrupts.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct TaskPriority(pub(crate) u32);
+
+impl TaskPriority {
+    /// Creates a task priority from its raw 8-bit value (class and subclass).
+    pub fn new(priority: u8) -> Self {
+        Self(u32::from(priority))
+    }
+
+    /// Creates a task priority of priority class `class`, with a subclass of 0.
+    pub fn from_class(class: u8) -> Self {
+        assert!(class < 16, "priority classes are in the range 0..=15");
+
+        Self(u32::from(class) << 4)
+    }
+
+    /// Creates a task priority of priority class `class`, or returns an error if `class` is
+    /// out of range.
+    pub fn try_from_class(class: u8) -> Result<Self, ApicError> {
+        check_nibble("priority class", class)?;
+
+        Ok(Self::from_class(class))
+    }
+
+    /// Raw 8-bit value of the task priority.
+    pub fn get_priority(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..8)).unwrap()
+    }
+
+    /// Priority class of the task priority.
+    pub fn get_class(&self) -> u8 {
+        u8::try_from(self.0.get_bits(4..8)).unwrap()
+    }
+
+    /// Sets the priority class of the task priority.
+    pub fn set_class(&mut self, class: u8) {
+        assert!(class < 16, "priority classes are in the range 0..=15");
+
+        self.0.set_bits(4..8, u32::from(class));
+    }
+
+    /// Sets the priority class of the task priority, or returns an error if `class` is out of
+    /// range.
+    pub fn try_set_class(&mut self, class: u8) -> Result<(), ApicError> {
+        check_nibble("priority class", class)?;
+        self.set_class(class);
+
+        Ok(())
+    }
+
+    /// Priority subclass of the task priority.
+    pub fn get_subclass(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..4)).unwrap()
+    }
+
+    /// Sets the priority subclass of the task priority.
+    pub fn set_subclass(&mut self, subclass: u8) {
+        assert!(subclass < 16, "priority subclasses are in the range 0..=15");
+
+        self.0.set_bits(0..4, u32::from(subclass));
+    }
+
+    /// Sets the priority subclass of the task priority, or returns an error if `subclass` is
+    /// out of range.
+    pub fn try_set_subclass(&mut self, subclass: u8) -> Result<(), ApicError> {
+        check_nibble("priority subclass", subclass)?;
+        self.set_subclass(subclass);
+
+        Ok(())
+    }
+
+    /// Whether the task priority blocks delivery of `vector`.
+    pub fn blocks(&self, vector: u8) -> bool {
+        vector_class(vector) <= self.get_class()
+    }
+}
+
+impl fmt::Debug for TaskPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskPriority")
+            .field("Class", &self.get_class())
+            .field("Subclass", &self.get_subclass())
+            .finish()
+    }
+}
+
+/// Arbitration priority register value.
+///
+/// The priority used by the processor when arbitrating for lowest-priority delivery. Only
+/// present in xAPIC mode.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ArbitrationPriority(pub(crate) u32);
+
+impl ArbitrationPriority {
+    /// Raw 8-bit value of the arbitration priority.
+    pub fn get_priority(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..8)).unwrap()
+    }
+
+    /// Priority class of the arbitration priority.
+    pub fn get_class(&self) -> u8 {
+        u8::try_from(self.0.get_bits(4..8)).unwrap()
+    }
+
+    /// Priority subclass of the arbitration priority.
+    pub fn get_subclass(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..4)).unwrap()
+    }
+}
+
+impl fmt::Debug for ArbitrationPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArbitrationPriority")
+            .field("Class", &self.get_class())
+            .field("Subclass", &self.get_subclass())
+            .finish()
+    }
+}
+
+/// Processor priority register value.
+///
+/// The priority at which the processor is currently executing: the greater of the task
+/// priority and the class of the highest in-service vector. Interrupts whose priority class is
+/// less than or equal to the processor priority class are held pending.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorPriority(pub(crate) u32);
+
+impl ProcessorPriority {
+    /// Raw 8-bit value of the processor priority.
+    pub fn get_priority(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..8)).unwrap()
+    }
+
+    /// Priority class of the processor priority.
+    pub fn get_class(&self) -> u8 {
+        u8::try_from(self.0.get_bits(4..8)).unwrap()
+    }
+
+    /// Priority subclass of the processor priority.
+    pub fn get_subclass(&self) -> u8 {
+        u8::try_from(self.0.get_bits(0..4)).unwrap()
+    }
+
+    /// Whether the processor priority blocks delivery of `vector`.
+    pub fn blocks(&self, vector: u8) -> bool {
+        vector_class(vector) <= self.get_class()
+    }
+}
+
+impl fmt::Debug for ProcessorPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessorPriority")
+            .field("Class", &self.get_class())
+            .field("Subclass", &self.get_subclass())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_priority_classes() {
+        let mut task_priority = TaskPriority::from_class(0x3);
+        assert_eq!(task_priority.get_priority(), 0x30);
+        assert!(task_priority.blocks(0x3F));
+        assert!(!task_priority.blocks(0x40));
+
+        task_priority.set_subclass(0xA);
+        assert_eq!(task_priority, TaskPriority::new(0x3A));
+        assert_eq!(task_priority.get_class(), 0x3);
+
+        assert!(!TaskPriority::default().blocks(0x10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn task_priority_class_out_of_range() {
+        TaskPriority::from_class(16);
+    }
+
+    #[test]
+    fn rejects_out_of_range_nibbles() {
+        assert_eq!(
+            TaskPriority::try_from_class(16),
+            Err(ApicError::OutOfRange {
+                field: "priority class",
+                value: 16
+            })
+        );
+
+        let mut task_priority = TaskPriority::try_from_class(0x3).unwrap();
+        assert!(task_priority.try_set_class(0x10).is_err());
+        assert!(task_priority.try_set_subclass(0x10).is_err());
+        assert_eq!(task_priority.try_set_subclass(0xA), Ok(()));
+        assert_eq!(task_priority, TaskPriority::new(0x3A));
+    }
+}
@@ -0,0 +1,237 @@
+/// Whether a register can be read, written, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl RegisterAccess {
+    pub fn is_readable(self) -> bool {
+        matches!(self, Self::ReadOnly | Self::ReadWrite)
+    }
+
+    pub fn is_writable(self) -> bool {
+        matches!(self, Self::WriteOnly | Self::ReadWrite)
+    }
+}
+
+/// The architectural registers of the local APIC.
+///
+/// The 256-bit in-service, trigger mode, and interrupt request registers are each made up of
+/// eight 32-bit registers, selected by index (0..8); bits `32 * index..32 * (index + 1)` of the
+/// bank are held in register `index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    ID,
+    VERSION,
+    TASK_PRIORITY,
+    ARBITRATION_PRIORITY,
+    PROCESSOR_PRIORITY,
+    END_OF_INTERRUPT,
+    REMOTE_READ,
+    LOCAL_DESTINATION,
+    DESTINATION_FORMAT,
+    SPURIOUS_VECTOR,
+    IN_SERVICE(u8),
+    TRIGGER_MODE(u8),
+    INTERRUPT_REQUEST(u8),
+    ERROR_STATUS,
+    CMCI_VECTOR,
+    /// The interrupt command register. In x2APIC mode this is a single 64-bit register, which
+    /// does not fit in a raw 32-bit access (see [`Register::access`]); use
+    /// [`crate::xApic::send_interrupt_command`] instead.
+    INTERRUPT_COMMAND_LOW,
+    /// The destination of the interrupt command register. Only present in xAPIC mode.
+    INTERRUPT_COMMAND_HIGH,
+    TIMER_VECTOR,
+    THERMAL_SENSOR_VECTOR,
+    PERFORMANCE_MONITORS_VECTOR,
+    LINT0_VECTOR,
+    LINT1_VECTOR,
+    ERROR_VECTOR,
+    TIMER_INITIAL_COUNT,
+    TIMER_CURRENT_COUNT,
+    TIMER_DIVIDE_CONFIGURATION,
+    /// Sends a fixed interrupt to self. Only present in x2APIC mode.
+    SELF_IPI,
+}
+
+impl Register {
+    /// Offset of the register within the xAPIC register page, or `None` if the register is
+    /// not present in xAPIC mode.
+    pub const fn xapic_offset(self) -> Option<u32> {
+        let offset = match self {
+            Self::ID => 0x020,
+            Self::VERSION => 0x030,
+            Self::TASK_PRIORITY => 0x080,
+            Self::ARBITRATION_PRIORITY => 0x090,
+            Self::PROCESSOR_PRIORITY => 0x0A0,
+            Self::END_OF_INTERRUPT => 0x0B0,
+            Self::REMOTE_READ => 0x0C0,
+            Self::LOCAL_DESTINATION => 0x0D0,
+            Self::DESTINATION_FORMAT => 0x0E0,
+            Self::SPURIOUS_VECTOR => 0x0F0,
+            Self::IN_SERVICE(index) if index < 8 => 0x100 + ((index as u32) << 4),
+            Self::TRIGGER_MODE(index) if index < 8 => 0x180 + ((index as u32) << 4),
+            Self::INTERRUPT_REQUEST(index) if index < 8 => 0x200 + ((index as u32) << 4),
+            Self::ERROR_STATUS => 0x280,
+            Self::CMCI_VECTOR => 0x2F0,
+            Self::INTERRUPT_COMMAND_LOW => 0x300,
+            Self::INTERRUPT_COMMAND_HIGH => 0x310,
+            Self::TIMER_VECTOR => 0x320,
+            Self::THERMAL_SENSOR_VECTOR => 0x330,
+            Self::PERFORMANCE_MONITORS_VECTOR => 0x340,
+            Self::LINT0_VECTOR => 0x350,
+            Self::LINT1_VECTOR => 0x360,
+            Self::ERROR_VECTOR => 0x370,
+            Self::TIMER_INITIAL_COUNT => 0x380,
+            Self::TIMER_CURRENT_COUNT => 0x390,
+            Self::TIMER_DIVIDE_CONFIGURATION => 0x3E0,
+            Self::IN_SERVICE(_)
+            | Self::TRIGGER_MODE(_)
+            | Self::INTERRUPT_REQUEST(_)
+            | Self::SELF_IPI => return None,
+        };
+
+        Some(offset)
+    }
+
+    /// Address of the model-specific register of the register in x2APIC mode, or `None` if
+    /// the register is not present in x2APIC mode.
+    pub const fn x2apic_msr(self) -> Option<u32> {
+        let msr = match self {
+            Self::ARBITRATION_PRIORITY
+            | Self::REMOTE_READ
+            | Self::DESTINATION_FORMAT
+            | Self::INTERRUPT_COMMAND_HIGH => return None,
+            Self::SELF_IPI => 0x83F,
+            register => match register.xapic_offset() {
+                Some(offset) => crate::x2APIC_BASE_MSR_ADDR + (offset >> 4),
+                None => return None,
+            },
+        };
+
+        Some(msr)
+    }
+
+    /// The register at `offset` within the xAPIC register page, if any.
+    pub fn from_xapic_offset(offset: u32) -> Option<Self> {
+        let register = match offset {
+            0x020 => Self::ID,
+            0x030 => Self::VERSION,
+            0x080 => Self::TASK_PRIORITY,
+            0x090 => Self::ARBITRATION_PRIORITY,
+            0x0A0 => Self::PROCESSOR_PRIORITY,
+            0x0B0 => Self::END_OF_INTERRUPT,
+            0x0C0 => Self::REMOTE_READ,
+            0x0D0 => Self::LOCAL_DESTINATION,
+            0x0E0 => Self::DESTINATION_FORMAT,
+            0x0F0 => Self::SPURIOUS_VECTOR,
+            0x100..0x180 => Self::IN_SERVICE(u8::try_from((offset - 0x100) >> 4).unwrap()),
+            0x180..0x200 => Self::TRIGGER_MODE(u8::try_from((offset - 0x180) >> 4).unwrap()),
+            0x200..0x280 => Self::INTERRUPT_REQUEST(u8::try_from((offset - 0x200) >> 4).unwrap()),
+            0x280 => Self::ERROR_STATUS,
+            0x2F0 => Self::CMCI_VECTOR,
+            0x300 => Self::INTERRUPT_COMMAND_LOW,
+            0x310 => Self::INTERRUPT_COMMAND_HIGH,
+            0x320 => Self::TIMER_VECTOR,
+            0x330 => Self::THERMAL_SENSOR_VECTOR,
+            0x340 => Self::PERFORMANCE_MONITORS_VECTOR,
+            0x350 => Self::LINT0_VECTOR,
+            0x360 => Self::LINT1_VECTOR,
+            0x370 => Self::ERROR_VECTOR,
+            0x380 => Self::TIMER_INITIAL_COUNT,
+            0x390 => Self::TIMER_CURRENT_COUNT,
+            0x3E0 => Self::TIMER_DIVIDE_CONFIGURATION,
+            _ => return None,
+        };
+
+        (offset.is_multiple_of(0x10)).then_some(register)
+    }
+
+    /// Index of the register within the xAPIC register page, in units of 16 bytes.
+    pub(crate) const fn index(self) -> usize {
+        match self.xapic_offset() {
+            Some(offset) => (offset >> 4) as usize,
+            None => panic!("register is not present in xAPIC mode"),
+        }
+    }
+
+    /// How the register can be accessed with raw 32-bit accesses in xAPIC mode (or x2APIC
+    /// mode, if `extended`), or `None` if the register is not present in that mode.
+    ///
+    /// The 64-bit interrupt command register of x2APIC mode cannot be accessed raw, and is
+    /// reported as `None`.
+    pub fn access(self, extended: bool) -> Option<RegisterAccess> {
+        let present = if extended {
+            self.x2apic_msr().is_some() && self != Self::INTERRUPT_COMMAND_LOW
+        } else {
+            self.xapic_offset().is_some()
+        };
+
+        if !present {
+            return None;
+        }
+
+        let access = match self {
+            // The APIC ID and logical ID are assigned by hardware in x2APIC mode.
+            Self::ID | Self::LOCAL_DESTINATION if extended => RegisterAccess::ReadOnly,
+
+            Self::VERSION
+            | Self::ARBITRATION_PRIORITY
+            | Self::PROCESSOR_PRIORITY
+            | Self::REMOTE_READ
+            | Self::IN_SERVICE(_)
+            | Self::TRIGGER_MODE(_)
+            | Self::INTERRUPT_REQUEST(_)
+            | Self::TIMER_CURRENT_COUNT => RegisterAccess::ReadOnly,
+
+            Self::END_OF_INTERRUPT | Self::SELF_IPI => RegisterAccess::WriteOnly,
+
+            _ => RegisterAccess::ReadWrite,
+        };
+
+        Some(access)
+    }
+
+    /// Whether the register can be accessed raw in xAPIC mode (or x2APIC mode, if `extended`).
+    pub fn is_valid(self, extended: bool) -> bool {
+        self.access(extended).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_registers_between_modes() {
+        assert_eq!(Register::CMCI_VECTOR.x2apic_msr(), Some(0x82F));
+        assert_eq!(Register::IN_SERVICE(3).xapic_offset(), Some(0x130));
+        assert_eq!(Register::INTERRUPT_REQUEST(7).x2apic_msr(), Some(0x827));
+        assert_eq!(Register::INTERRUPT_COMMAND_LOW.x2apic_msr(), Some(0x830));
+        assert_eq!(Register::SELF_IPI.xapic_offset(), None);
+        assert_eq!(Register::IN_SERVICE(8).xapic_offset(), None);
+        assert_eq!(
+            Register::from_xapic_offset(0x190),
+            Some(Register::TRIGGER_MODE(1))
+        );
+        assert_eq!(Register::from_xapic_offset(0x194), None);
+
+        assert_eq!(Register::ID.access(false), Some(RegisterAccess::ReadWrite));
+        assert_eq!(Register::ID.access(true), Some(RegisterAccess::ReadOnly));
+        assert_eq!(Register::DESTINATION_FORMAT.access(true), None);
+        assert_eq!(Register::INTERRUPT_COMMAND_LOW.access(true), None);
+        assert_eq!(
+            Register::INTERRUPT_COMMAND_LOW.access(false),
+            Some(RegisterAccess::ReadWrite)
+        );
+        assert!(
+            !Register::END_OF_INTERRUPT
+                .access(true)
+                .unwrap()
+                .is_readable()
+        );
+    }
+}
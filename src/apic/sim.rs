@@ -0,0 +1,801 @@
+use core::{cell::Cell, marker::PhantomData};
+
+use crate::{
+    ArbitrationPriority, DestinationFormat, ErrorStatus, InterruptCommand, LocalDestination, Mode,
+    ProcessorPriority, Register, RemoteRead, TaskPriority, TimerDivideConfiguration, VectorBitmap,
+    Version,
+    local_vector::{
+        CMCI, Error, LINT0, LINT1, LocalVector, PerformanceMonitors, ThermalSensor, Timer,
+        TimerMode,
+    },
+    xApic,
+};
+use bit_field::BitField;
+
+const IN_SERVICE_BASE: usize = Register::IN_SERVICE(0).index();
+const TRIGGER_MODE_BASE: usize = Register::TRIGGER_MODE(0).index();
+const INTERRUPT_REQUEST_BASE: usize = Register::INTERRUPT_REQUEST(0).index();
+
+/// Value of every local vector table entry after reset (masked, vector 0).
+const LOCAL_VECTOR_RESET: u32 = 1 << 16;
+
+/// Software model of the xAPIC register page.
+///
+/// The register file models the behaviour that software can observe through the register
+/// interface: reserved bits read as their reset value, read-only registers ignore writes, the
+/// error status register latches errors on write, and end-of-interrupt retires the highest
+/// in-service vector. Interrupt delivery itself is not modelled; use [`RegisterFile::set_in_service`],
+/// [`RegisterFile::set_interrupt_requested`] and [`RegisterFile::raise_error`] to drive the model.
+///
+/// The timer supports TSC-deadline mode, unless disabled with
+/// [`RegisterFile::set_tsc_deadline_supported`].
+///
+/// The delivery status of the interrupt command register reports a sent interrupt as pending
+/// for as many reads as set by [`RegisterFile::set_delivery_latency`] (none by default).
+pub struct RegisterFile {
+    registers: [Cell<u32>; 64],
+    pending_errors: Cell<u32>,
+    interrupts_sent: Cell<usize>,
+    tsc_deadline: Cell<u64>,
+    tsc_deadline_supported: Cell<bool>,
+    delivery_latency: Cell<usize>,
+    pending_delivery_reads: Cell<usize>,
+}
+
+impl RegisterFile {
+    /// Creates a register file in its power-up state, with the provided APIC ID and raw
+    /// version register value.
+    pub fn new(id: u8, version: u32) -> Self {
+        let registers = core::array::from_fn(|_| Cell::new(0));
+        let register_file = Self {
+            registers,
+            pending_errors: Cell::new(0),
+            interrupts_sent: Cell::new(0),
+            tsc_deadline: Cell::new(0),
+            tsc_deadline_supported: Cell::new(true),
+            delivery_latency: Cell::new(0),
+            pending_delivery_reads: Cell::new(0),
+        };
+
+        register_file.store(Register::ID, u32::from(id) << 24);
+        register_file.store(Register::VERSION, version);
+        register_file.store(Register::DESTINATION_FORMAT, 0xFFFF_FFFF);
+        register_file.store(Register::SPURIOUS_VECTOR, 0xFF);
+        for register in [
+            Register::CMCI_VECTOR,
+            Register::TIMER_VECTOR,
+            Register::THERMAL_SENSOR_VECTOR,
+            Register::PERFORMANCE_MONITORS_VECTOR,
+            Register::LINT0_VECTOR,
+            Register::LINT1_VECTOR,
+            Register::ERROR_VECTOR,
+        ] {
+            register_file.store(register, LOCAL_VECTOR_RESET);
+        }
+
+        register_file
+    }
+
+    fn load(&self, register: Register) -> u32 {
+        self.registers[register.index()].get()
+    }
+
+    fn store(&self, register: Register, value: u32) {
+        self.registers[register.index()].set(value);
+    }
+
+    /// Bits of `register` that software is able to modify.
+    fn writable_mask(register: Register) -> u32 {
+        match register {
+            Register::TASK_PRIORITY => 0x0000_00FF,
+            Register::LOCAL_DESTINATION => 0xFF00_0000,
+            Register::DESTINATION_FORMAT => 0xF000_0000,
+            Register::SPURIOUS_VECTOR => 0x0000_13FF,
+            Register::INTERRUPT_COMMAND_LOW => 0x000C_CFFF,
+            Register::INTERRUPT_COMMAND_HIGH => 0xFF00_0000,
+            Register::TIMER_VECTOR => 0x0007_00FF,
+            Register::CMCI_VECTOR
+            | Register::THERMAL_SENSOR_VECTOR
+            | Register::PERFORMANCE_MONITORS_VECTOR => 0x0001_07FF,
+            Register::LINT0_VECTOR | Register::LINT1_VECTOR => 0x0001_A7FF,
+            Register::ERROR_VECTOR => 0x0001_00FF,
+            Register::TIMER_INITIAL_COUNT => 0xFFFF_FFFF,
+            Register::TIMER_DIVIDE_CONFIGURATION => 0x0000_000B,
+
+            Register::ID
+            | Register::VERSION
+            | Register::ARBITRATION_PRIORITY
+            | Register::PROCESSOR_PRIORITY
+            | Register::END_OF_INTERRUPT
+            | Register::REMOTE_READ
+            | Register::IN_SERVICE(_)
+            | Register::TRIGGER_MODE(_)
+            | Register::INTERRUPT_REQUEST(_)
+            | Register::ERROR_STATUS
+            | Register::TIMER_CURRENT_COUNT
+            | Register::SELF_IPI => 0,
+        }
+    }
+
+    /// Reads `register` as software would observe it.
+    pub fn read(&self, register: Register) -> u32 {
+        match register {
+            // Registers outside of the xAPIC register page read as zero, and are reported as an
+            // illegal register access.
+            register if register.xapic_offset().is_none() => {
+                self.raise_error(ErrorStatus::ILLEGAL_REGISTER_ADDRESS);
+                0
+            }
+
+            // The write-only end-of-interrupt register reads as zero.
+            Register::END_OF_INTERRUPT => 0,
+
+            Register::PROCESSOR_PRIORITY => {
+                let task_priority = self.load(Register::TASK_PRIORITY);
+                let in_service = u32::from(self.highest_in_service().unwrap_or(0));
+
+                if task_priority.get_bits(4..8) >= in_service.get_bits(4..8) {
+                    task_priority
+                } else {
+                    in_service & 0xF0
+                }
+            }
+
+            // The delivery status (bit 12) clears once the interrupt has been pending for as many
+            // reads as the delivery latency.
+            Register::INTERRUPT_COMMAND_LOW => {
+                let value = self.load(register);
+                let pending_reads = self.pending_delivery_reads.get();
+                if pending_reads > 0 {
+                    self.pending_delivery_reads.set(pending_reads - 1);
+                    if pending_reads == 1 {
+                        let mut delivered = value;
+                        delivered.set_bit(12, false);
+                        self.store(register, delivered);
+                    }
+                }
+
+                value
+            }
+
+            register => self.load(register),
+        }
+    }
+
+    /// Writes `value` to `register`, applying reserved-bit masking and side effects.
+    pub fn write(&self, register: Register, value: u32) {
+        match register {
+            register if register.xapic_offset().is_none() => {
+                self.raise_error(ErrorStatus::ILLEGAL_REGISTER_ADDRESS);
+            }
+
+            Register::END_OF_INTERRUPT => {
+                if let Some(vector) = self.highest_in_service() {
+                    self.set_vector_bit(IN_SERVICE_BASE, vector, false);
+                }
+            }
+
+            // Writing the error status register latches the errors collected since the
+            // previous write, and clears the internal error state.
+            Register::ERROR_STATUS => {
+                self.store(Register::ERROR_STATUS, self.pending_errors.replace(0));
+            }
+
+            Register::INTERRUPT_COMMAND_LOW => {
+                let latency = self.delivery_latency.get();
+                self.merge(register, value);
+                self.store(register, *self.load(register).set_bit(12, latency > 0));
+                self.pending_delivery_reads.set(latency);
+                self.interrupts_sent.set(self.interrupts_sent.get() + 1);
+            }
+
+            // Switching the timer between TSC-deadline mode and the count-down modes disarms it.
+            Register::TIMER_VECTOR => {
+                let is_tsc_deadline = |value: u32| value.get_bits(17..19) == 0b10;
+                if is_tsc_deadline(self.load(register)) != is_tsc_deadline(value) {
+                    self.store(Register::TIMER_INITIAL_COUNT, 0);
+                    self.store(Register::TIMER_CURRENT_COUNT, 0);
+                    self.tsc_deadline.set(0);
+                }
+
+                self.merge(register, value);
+            }
+
+            // Writes to the initial count are ignored in TSC-deadline mode.
+            Register::TIMER_INITIAL_COUNT => {
+                if self.load(Register::TIMER_VECTOR).get_bits(17..19) != 0b10 {
+                    self.store(register, value);
+                    self.store(Register::TIMER_CURRENT_COUNT, value);
+                }
+            }
+
+            register => self.merge(register, value),
+        }
+    }
+
+    fn merge(&self, register: Register, value: u32) {
+        let mask = Self::writable_mask(register);
+        self.store(register, (self.load(register) & !mask) | (value & mask));
+    }
+
+    fn vector_bit(&self, base: usize, vector: u8) -> bool {
+        let vector = usize::from(vector);
+        self.registers[base + (vector / 32)]
+            .get()
+            .get_bit(vector % 32)
+    }
+
+    fn set_vector_bit(&self, base: usize, vector: u8, value: bool) {
+        let vector = usize::from(vector);
+        let cell = &self.registers[base + (vector / 32)];
+        cell.set(*cell.get().set_bit(vector % 32, value));
+    }
+
+    fn bitmap(&self, base: usize) -> VectorBitmap {
+        VectorBitmap(core::array::from_fn(|index| {
+            self.registers[base + index].get()
+        }))
+    }
+
+    /// Highest vector currently marked in the in-service register.
+    pub fn highest_in_service(&self) -> Option<u8> {
+        self.bitmap(IN_SERVICE_BASE).highest()
+    }
+
+    /// Whether `vector` is marked in the in-service register.
+    pub fn is_in_service(&self, vector: u8) -> bool {
+        self.vector_bit(IN_SERVICE_BASE, vector)
+    }
+
+    /// Marks `vector` as in-service, as if the processor had accepted it.
+    pub fn set_in_service(&self, vector: u8) {
+        self.set_vector_bit(INTERRUPT_REQUEST_BASE, vector, false);
+        self.set_vector_bit(IN_SERVICE_BASE, vector, true);
+    }
+
+    /// Marks `vector` as level-triggered (or edge-triggered) in the trigger mode register.
+    pub fn set_level_triggered(&self, vector: u8, level_triggered: bool) {
+        self.set_vector_bit(TRIGGER_MODE_BASE, vector, level_triggered);
+    }
+
+    /// Whether `vector` is marked in the interrupt request register.
+    pub fn is_interrupt_requested(&self, vector: u8) -> bool {
+        self.vector_bit(INTERRUPT_REQUEST_BASE, vector)
+    }
+
+    /// Marks `vector` as requested, as if it had been accepted by the local APIC.
+    pub fn set_interrupt_requested(&self, vector: u8) {
+        self.set_vector_bit(INTERRUPT_REQUEST_BASE, vector, true);
+    }
+
+    /// Records `errors` as detected. They become visible in the error status register after
+    /// the next write to it.
+    pub fn raise_error(&self, errors: ErrorStatus) {
+        self.pending_errors
+            .set(self.pending_errors.get() | errors.bits());
+    }
+
+    /// Counts the timer down by `ticks`, saturating at zero.
+    pub fn advance_timer(&self, ticks: u32) {
+        let current_count = self.load(Register::TIMER_CURRENT_COUNT);
+        self.store(
+            Register::TIMER_CURRENT_COUNT,
+            current_count.saturating_sub(ticks),
+        );
+    }
+
+    /// The value last written to `IA32_TSC_DEADLINE`, or zero if the deadline is disarmed.
+    pub fn tsc_deadline(&self) -> u64 {
+        self.tsc_deadline.get()
+    }
+
+    /// Sets whether the timer supports TSC-deadline mode.
+    pub fn set_tsc_deadline_supported(&self, supported: bool) {
+        self.tsc_deadline_supported.set(supported);
+    }
+
+    /// Sets the number of reads of the interrupt command register for which subsequently sent
+    /// interrupts are reported as pending. `usize::MAX` models an interrupt that is never
+    /// delivered.
+    pub fn set_delivery_latency(&self, reads: usize) {
+        self.delivery_latency.set(reads);
+    }
+
+    /// The number of interrupt commands sent through the interrupt command register.
+    pub fn interrupts_sent(&self) -> usize {
+        self.interrupts_sent.get()
+    }
+
+    /// A local APIC operating on the register file.
+    pub fn apic(&self) -> xApic<sim<'_>> {
+        // Safety: The simulated local APIC has no hardware side effects.
+        unsafe { xApic::new(self) }
+    }
+}
+
+/// Simulated local APIC, backed by a [`RegisterFile`] in ordinary memory.
+pub struct sim<'a>(PhantomData<&'a RegisterFile>);
+
+impl<'a> Mode for sim<'a> {
+    type Inner = &'a RegisterFile;
+
+    const EXTENDED: bool = false;
+
+    fn read_register_raw(inner: Self::Inner, register: Register) -> u32 {
+        inner.read(register)
+    }
+
+    fn write_register_raw(inner: Self::Inner, register: Register, value: u32) {
+        inner.write(register, value);
+    }
+
+    fn get_id(inner: Self::Inner) -> u32 {
+        inner.read(Register::ID).get_bits(24..32)
+    }
+
+    fn get_version(inner: Self::Inner) -> Version {
+        Version(inner.read(Register::VERSION))
+    }
+
+    fn get_task_priority(inner: Self::Inner) -> TaskPriority {
+        TaskPriority(inner.read(Register::TASK_PRIORITY))
+    }
+
+    fn set_task_priority(inner: Self::Inner, value: TaskPriority) {
+        inner.write(Register::TASK_PRIORITY, value.0);
+    }
+
+    fn get_arbitration_priority(inner: Self::Inner) -> ArbitrationPriority {
+        ArbitrationPriority(inner.read(Register::ARBITRATION_PRIORITY))
+    }
+
+    fn get_processor_priority(inner: Self::Inner) -> ProcessorPriority {
+        ProcessorPriority(inner.read(Register::PROCESSOR_PRIORITY))
+    }
+
+    fn get_remote_read(inner: Self::Inner) -> RemoteRead {
+        RemoteRead(inner.read(Register::REMOTE_READ))
+    }
+
+    fn get_local_destination(inner: Self::Inner) -> LocalDestination {
+        LocalDestination(inner.read(Register::LOCAL_DESTINATION).get_bits(24..32))
+    }
+
+    fn set_local_destination(inner: Self::Inner, value: LocalDestination) {
+        assert!(value.0 <= 0xFF, "xAPIC logical IDs are 8 bits wide");
+
+        inner.write(Register::LOCAL_DESTINATION, value.0 << 24);
+    }
+
+    fn get_destination_format(inner: Self::Inner) -> DestinationFormat {
+        DestinationFormat(inner.read(Register::DESTINATION_FORMAT))
+    }
+
+    fn set_destination_format(inner: Self::Inner, value: DestinationFormat) {
+        inner.write(Register::DESTINATION_FORMAT, value.0);
+    }
+
+    fn get_in_service(inner: Self::Inner) -> VectorBitmap {
+        inner.bitmap(IN_SERVICE_BASE)
+    }
+
+    fn get_trigger_mode(inner: Self::Inner) -> VectorBitmap {
+        inner.bitmap(TRIGGER_MODE_BASE)
+    }
+
+    fn get_interrupt_request(inner: Self::Inner) -> VectorBitmap {
+        inner.bitmap(INTERRUPT_REQUEST_BASE)
+    }
+
+    fn get_error_status(inner: Self::Inner) -> ErrorStatus {
+        ErrorStatus::from_bits_truncate(inner.read(Register::ERROR_STATUS))
+    }
+
+    fn clear_error_status(inner: Self::Inner) {
+        inner.write(Register::ERROR_STATUS, 0x0);
+    }
+
+    fn get_timer_initial_count(inner: Self::Inner) -> u32 {
+        inner.read(Register::TIMER_INITIAL_COUNT)
+    }
+
+    fn set_timer_initial_count(inner: Self::Inner, value: u32) {
+        inner.write(Register::TIMER_INITIAL_COUNT, value);
+    }
+
+    fn get_timer_current_count(inner: Self::Inner) -> u32 {
+        inner.read(Register::TIMER_CURRENT_COUNT)
+    }
+
+    fn get_timer_divide_configuration(inner: Self::Inner) -> TimerDivideConfiguration {
+        TimerDivideConfiguration::from_bits_truncate(
+            inner.read(Register::TIMER_DIVIDE_CONFIGURATION),
+        )
+    }
+
+    fn set_timer_divide_configuration(inner: Self::Inner, value: TimerDivideConfiguration) {
+        inner.write(Register::TIMER_DIVIDE_CONFIGURATION, value.bits());
+    }
+
+    fn is_tsc_deadline_supported(inner: Self::Inner) -> bool {
+        inner.tsc_deadline_supported.get()
+    }
+
+    fn get_tsc_deadline(inner: Self::Inner) -> u64 {
+        // Accessing `IA32_TSC_DEADLINE` raises #GP when TSC-deadline mode is not supported.
+        assert!(
+            inner.tsc_deadline_supported.get(),
+            "IA32_TSC_DEADLINE is not supported"
+        );
+
+        inner.tsc_deadline.get()
+    }
+
+    fn set_tsc_deadline(inner: Self::Inner, deadline: u64) {
+        assert!(
+            inner.tsc_deadline_supported.get(),
+            "IA32_TSC_DEADLINE is not supported"
+        );
+
+        // Writes to `IA32_TSC_DEADLINE` are ignored outside of TSC-deadline mode.
+        if sim::get_timer_vector(inner).try_get_mode() == Ok(TimerMode::TscDeadline) {
+            inner.tsc_deadline.set(deadline);
+        }
+    }
+
+    fn send_interrupt_command(inner: Self::Inner, interrupt_command: InterruptCommand) {
+        inner.write(
+            Register::INTERRUPT_COMMAND_HIGH,
+            interrupt_command.high() << 24,
+        );
+        inner.write(Register::INTERRUPT_COMMAND_LOW, interrupt_command.low());
+    }
+
+    fn send_self_ipi(inner: Self::Inner, vector: u8) {
+        // A fixed, edge-triggered interrupt with the self destination shorthand; the
+        // destination is ignored with a destination shorthand.
+        let mut interrupt_command = 0x0;
+        interrupt_command.set_bits(0..8, u32::from(vector));
+        interrupt_command.set_bit(14, true);
+        interrupt_command.set_bits(18..20, 0b01);
+
+        inner.write(Register::INTERRUPT_COMMAND_LOW, interrupt_command);
+    }
+
+    fn get_spurious_vector(inner: Self::Inner) -> u8 {
+        u8::try_from(inner.read(Register::SPURIOUS_VECTOR).get_bits(..8)).unwrap()
+    }
+
+    fn get_spurious_apic_software_enabled(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(8)
+    }
+
+    fn get_spurious_focus_processor_checking(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(9)
+    }
+
+    fn get_spurious_eoi_broadcast_suppression(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(12)
+    }
+
+    fn set_spurious_vector(inner: Self::Inner, vector: u8) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner
+                .read(Register::SPURIOUS_VECTOR)
+                .set_bits(..8, u32::from(vector)),
+        );
+    }
+
+    fn set_spurious_apic_software_enabled(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(8, value),
+        );
+    }
+
+    fn set_spurious_focus_processor_checking(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(9, value),
+        );
+    }
+
+    fn set_spurious_eoi_broadcast_suppression(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(12, value),
+        );
+    }
+
+    fn get_timer_vector(inner: Self::Inner) -> LocalVector<Timer> {
+        LocalVector::<Timer>(inner.read(Register::TIMER_VECTOR), PhantomData)
+    }
+
+    fn set_timer_vector(inner: Self::Inner, value: LocalVector<Timer>) {
+        inner.write(Register::TIMER_VECTOR, u32::from(value));
+    }
+
+    fn get_cmci_vector(inner: Self::Inner) -> LocalVector<CMCI> {
+        LocalVector::<CMCI>(inner.read(Register::CMCI_VECTOR), PhantomData)
+    }
+
+    fn set_cmci_vector(inner: Self::Inner, value: LocalVector<CMCI>) {
+        inner.write(Register::CMCI_VECTOR, u32::from(value));
+    }
+
+    fn get_lint0_vector(inner: Self::Inner) -> LocalVector<LINT0> {
+        LocalVector::<LINT0>(inner.read(Register::LINT0_VECTOR), PhantomData)
+    }
+
+    fn set_lint0_vector(inner: Self::Inner, value: LocalVector<LINT0>) {
+        inner.write(Register::LINT0_VECTOR, u32::from(value));
+    }
+
+    fn get_lint1_vector(inner: Self::Inner) -> LocalVector<LINT1> {
+        LocalVector::<LINT1>(inner.read(Register::LINT1_VECTOR), PhantomData)
+    }
+
+    fn set_lint1_vector(inner: Self::Inner, value: LocalVector<LINT1>) {
+        inner.write(Register::LINT1_VECTOR, u32::from(value));
+    }
+
+    fn get_error_vector(inner: Self::Inner) -> LocalVector<Error> {
+        LocalVector::<Error>(inner.read(Register::ERROR_VECTOR), PhantomData)
+    }
+
+    fn set_error_vector(inner: Self::Inner, value: LocalVector<Error>) {
+        inner.write(Register::ERROR_VECTOR, u32::from(value));
+    }
+
+    fn get_performance_monitors_vector(inner: Self::Inner) -> LocalVector<PerformanceMonitors> {
+        LocalVector::<PerformanceMonitors>(
+            inner.read(Register::PERFORMANCE_MONITORS_VECTOR),
+            PhantomData,
+        )
+    }
+
+    fn set_performance_monitors_vector(
+        inner: Self::Inner,
+        value: LocalVector<PerformanceMonitors>,
+    ) {
+        inner.write(Register::PERFORMANCE_MONITORS_VECTOR, u32::from(value));
+    }
+
+    fn get_thermal_sensor_vector(inner: Self::Inner) -> LocalVector<ThermalSensor> {
+        LocalVector::<ThermalSensor>(inner.read(Register::THERMAL_SENSOR_VECTOR), PhantomData)
+    }
+
+    fn set_thermal_sensor_vector(inner: Self::Inner, value: LocalVector<ThermalSensor>) {
+        inner.write(Register::THERMAL_SENSOR_VECTOR, u32::from(value));
+    }
+
+    fn end_of_interrrupt(inner: Self::Inner) {
+        inner.write(Register::END_OF_INTERRUPT, 0x0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApicError;
+
+    #[test]
+    fn read_only_registers_ignore_writes() {
+        let register_file = RegisterFile::new(3, 0x0005_0014);
+        let apic = register_file.apic();
+
+        sim::write_register_raw(&register_file, Register::ID, 0x0700_0000);
+        sim::write_register_raw(&register_file, Register::VERSION, 0);
+        sim::write_register_raw(&register_file, Register::PROCESSOR_PRIORITY, 0xFF);
+
+        assert_eq!(apic.get_id(), 3);
+        assert_eq!(apic.get_version().version(), 0x14);
+        assert_eq!(apic.get_version().max_lvt_entry(), 5);
+        assert_eq!(sim::get_processor_priority(&register_file).0, 0);
+    }
+
+    #[test]
+    fn raw_accesses_are_checked() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+
+        assert_eq!(
+            apic.read_register_raw(Register::END_OF_INTERRUPT),
+            Err(ApicError::IllegalRead(Register::END_OF_INTERRUPT))
+        );
+        assert_eq!(
+            apic.write_register_raw(Register::VERSION, 0),
+            Err(ApicError::IllegalWrite(Register::VERSION))
+        );
+        assert_eq!(
+            apic.write_register_raw(Register::SELF_IPI, 0x40),
+            Err(ApicError::IllegalWrite(Register::SELF_IPI))
+        );
+        assert_eq!(
+            apic.read_register_raw(Register::IN_SERVICE(8)),
+            Err(ApicError::IllegalRead(Register::IN_SERVICE(8)))
+        );
+
+        apic.write_register_raw(Register::TASK_PRIORITY, 0x20)
+            .unwrap();
+        assert_eq!(apic.read_register_raw(Register::TASK_PRIORITY), Ok(0x20));
+        assert!(apic.get_error_status().is_empty());
+    }
+
+    #[test]
+    fn reserved_bits_are_preserved() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        sim::write_register_raw(&register_file, Register::TASK_PRIORITY, 0xFFFF_FFFF);
+        assert_eq!(register_file.read(Register::TASK_PRIORITY), 0xFF);
+
+        sim::write_register_raw(&register_file, Register::DESTINATION_FORMAT, 0);
+        assert_eq!(
+            register_file.read(Register::DESTINATION_FORMAT),
+            0x0FFF_FFFF
+        );
+
+        // Delivery status (bit 12) is read-only in the local vector table.
+        sim::write_register_raw(&register_file, Register::LINT0_VECTOR, 0xFFFF_FFFF);
+        assert_eq!(register_file.read(Register::LINT0_VECTOR), 0x0001_A7FF);
+    }
+
+    #[test]
+    fn local_vectors_reset_masked() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        assert!(sim::get_timer_vector(&register_file).get_masked());
+        assert!(sim::get_lint0_vector(&register_file).get_masked());
+        assert!(sim::get_error_vector(&register_file).get_masked());
+
+        let mut timer_vector = sim::get_timer_vector(&register_file);
+        timer_vector.set_masked(false);
+        timer_vector.set_vector(0x40);
+        sim::set_timer_vector(&register_file, timer_vector);
+
+        let timer_vector = sim::get_timer_vector(&register_file);
+        assert!(!timer_vector.get_masked());
+        assert_eq!(timer_vector.get_vector(), 0x40);
+    }
+
+    #[test]
+    fn error_status_latches_on_write() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+
+        register_file.raise_error(ErrorStatus::SEND_ACCEPT_ERROR);
+        assert!(apic.get_error_status().is_empty());
+
+        sim::clear_error_status(&register_file);
+        assert_eq!(
+            apic.get_error_status().bits(),
+            ErrorStatus::SEND_ACCEPT_ERROR.bits()
+        );
+
+        sim::clear_error_status(&register_file);
+        assert!(apic.get_error_status().is_empty());
+    }
+
+    #[test]
+    fn end_of_interrupt_retires_highest_in_service() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        register_file.set_in_service(0x31);
+        register_file.set_in_service(0xA2);
+        assert_eq!(sim::get_processor_priority(&register_file).0, 0xA0);
+
+        sim::end_of_interrrupt(&register_file);
+        assert!(!register_file.is_in_service(0xA2));
+        assert!(register_file.is_in_service(0x31));
+        assert_eq!(sim::get_processor_priority(&register_file).0, 0x30);
+
+        sim::end_of_interrrupt(&register_file);
+        assert_eq!(register_file.highest_in_service(), None);
+    }
+
+    #[test]
+    fn vector_register_banks() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        register_file.set_interrupt_requested(0x41);
+        register_file.set_interrupt_requested(0xE0);
+        register_file.set_in_service(0xE0);
+        register_file.set_level_triggered(0xE0, true);
+
+        assert!(sim::get_interrupt_request(&register_file).iter().eq([0x41]));
+        assert!(sim::get_in_service(&register_file).iter().eq([0xE0]));
+        assert_eq!(sim::get_trigger_mode(&register_file).highest(), Some(0xE0));
+
+        // The banks are read-only.
+        sim::write_register_raw(&register_file, Register::IN_SERVICE(7), 0xFFFF_FFFF);
+        assert_eq!(sim::get_in_service(&register_file).len(), 1);
+    }
+
+    #[test]
+    fn spurious_interrupt_register() {
+        let register_file = RegisterFile::new(0, 0x0105_0014);
+        let apic = register_file.apic();
+
+        let mut spurious = apic.get_spurious_vector();
+        assert_eq!(spurious.get_vector(), 0xFF);
+        assert!(!spurious.get_apic_enabled());
+
+        spurious.set_vector(0xEF);
+        spurious.set_apic_enabled(true);
+        spurious.set_eoi_broadcast_suppression(true);
+
+        assert_eq!(register_file.read(Register::SPURIOUS_VECTOR), 0x11EF);
+    }
+
+    #[test]
+    fn timer_counts_down_from_initial_count() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        sim::set_timer_divide_configuration(&register_file, TimerDivideConfiguration::DIVIDE_16);
+        sim::set_timer_initial_count(&register_file, 1000);
+        register_file.advance_timer(400);
+
+        assert_eq!(sim::get_timer_current_count(&register_file), 600);
+        assert_eq!(sim::get_timer_initial_count(&register_file), 1000);
+
+        // The current count is read-only.
+        sim::write_register_raw(&register_file, Register::TIMER_CURRENT_COUNT, 0);
+        assert_eq!(sim::get_timer_current_count(&register_file), 600);
+    }
+
+    #[test]
+    fn interrupt_command_is_sent() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+
+        sim::send_interrupt_command(&register_file, InterruptCommand::new_sipi(0x08, 2));
+
+        assert_eq!(register_file.interrupts_sent(), 1);
+        assert_eq!(
+            register_file.read(Register::INTERRUPT_COMMAND_HIGH),
+            0x0200_0000
+        );
+        assert_eq!(register_file.read(Register::INTERRUPT_COMMAND_LOW), 0x4608);
+    }
+
+    #[test]
+    fn self_ipi_uses_shorthand() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        sim::send_interrupt_command(&register_file, InterruptCommand::new_sipi(0x08, 2));
+
+        sim::send_self_ipi(&register_file, 0x40);
+
+        assert_eq!(register_file.interrupts_sent(), 2);
+        assert_eq!(
+            register_file.read(Register::INTERRUPT_COMMAND_LOW),
+            0x0004_4040
+        );
+        // Only the low doubleword is written.
+        assert_eq!(
+            register_file.read(Register::INTERRUPT_COMMAND_HIGH),
+            0x0200_0000
+        );
+    }
+
+    #[test]
+    fn delivery_status_clears_after_latency() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        register_file.set_delivery_latency(2);
+
+        sim::send_self_ipi(&register_file, 0x40);
+
+        assert!(apic.is_interrupt_command_pending());
+        assert!(apic.is_interrupt_command_pending());
+        assert!(!apic.is_interrupt_command_pending());
+        assert_eq!(
+            register_file.read(Register::INTERRUPT_COMMAND_LOW),
+            0x0004_4040
+        );
+
+        // Delivery status is read-only.
+        register_file.set_delivery_latency(0);
+        sim::write_register_raw(&register_file, Register::INTERRUPT_COMMAND_LOW, 0x1040);
+        assert!(!apic.is_interrupt_command_pending());
+    }
+}
@@ -0,0 +1,295 @@
+use core::time::Duration;
+
+use crate::{ErrorStatus, InterruptCommand, Mode, apic_error::read_and_clear_errors, xApic};
+
+/// Errors that can occur while starting an application processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartApError {
+    /// The local APIC did not accept an interrupt command before the delivery timeout.
+    DeliveryTimeout,
+
+    /// The local APIC reported errors while sending an interrupt command.
+    DeliveryError(ErrorStatus),
+
+    /// The application processor did not check in before the check-in timeout.
+    CheckInTimeout,
+
+    /// The poll interval of the configuration is zero, so polling would never time out.
+    ZeroPollInterval,
+}
+
+/// Timing of the INIT-SIPI-SIPI sequence.
+#[derive(Debug, Clone, Copy)]
+pub struct StartApConfig {
+    /// How long to wait after the INIT interrupt before sending the first start-up interrupt.
+    pub init_delay: Duration,
+
+    /// How long to wait after each start-up interrupt before checking whether the
+    /// application processor has checked in.
+    pub sipi_delay: Duration,
+
+    /// How long to wait for the local APIC to accept each interrupt command.
+    pub delivery_timeout: Duration,
+
+    /// How long to wait for the application processor to check in after the final
+    /// start-up interrupt.
+    pub check_in_timeout: Duration,
+
+    /// Interval at which delivery status and check-in are polled. Must not be zero.
+    pub poll_interval: Duration,
+}
+
+impl Default for StartApConfig {
+    /// The delays recommended by the IA32 SDM and the MultiProcessor specification.
+    fn default() -> Self {
+        Self {
+            init_delay: Duration::from_millis(10),
+            sipi_delay: Duration::from_micros(200),
+            delivery_timeout: Duration::from_millis(1),
+            check_in_timeout: Duration::from_millis(100),
+            poll_interval: Duration::from_micros(10),
+        }
+    }
+}
+
+/// Polls `condition` every `interval` until it holds, or `timeout` elapses.
+fn poll(
+    mut condition: impl FnMut() -> bool,
+    timeout: Duration,
+    interval: Duration,
+    delay: &mut impl FnMut(Duration),
+) -> bool {
+    let mut elapsed = Duration::ZERO;
+
+    loop {
+        if condition() {
+            return true;
+        }
+
+        if elapsed >= timeout {
+            return false;
+        }
+
+        delay(interval);
+        elapsed += interval;
+    }
+}
+
+fn send<M: Mode>(
+    apic: &xApic<M>,
+    interrupt_command: InterruptCommand,
+    config: &StartApConfig,
+    delay: &mut impl FnMut(Duration),
+) -> Result<(), StartApError> {
+    apic.clear_error_status();
+    apic.send_interrupt_command(interrupt_command);
+
+    if !poll(
+        || !apic.is_interrupt_command_pending(),
+        config.delivery_timeout,
+        config.poll_interval,
+        delay,
+    ) {
+        return Err(StartApError::DeliveryTimeout);
+    }
+
+    let error_status = read_and_clear_errors(apic);
+    if !error_status.is_empty() {
+        return Err(StartApError::DeliveryError(error_status));
+    }
+
+    Ok(())
+}
+
+/// Starts the application processor with local APIC ID `apic_id`, using the INIT-SIPI-SIPI
+/// sequence described by the IA32 SDM and the MultiProcessor specification.
+///
+/// - `trampoline_page` is the physical page number (address divided by 4096) of the real-mode
+///   start-up routine; it must be below 1 MiB.
+/// - `delay` is called to busy-wait for the provided duration.
+/// - `checked_in` is called to determine whether the application processor has started
+///   executing the start-up routine.
+///
+/// The second start-up interrupt is only sent if the application processor has not checked
+/// in after the first.
+///
+/// # Safety
+///
+/// - `apic_id` must identify an application processor that is in the wait-for-SIPI state.
+/// - `trampoline_page` must contain a valid start-up routine.
+pub unsafe fn start_ap<M: Mode>(
+    apic: &xApic<M>,
+    apic_id: u32,
+    trampoline_page: u8,
+    config: &StartApConfig,
+    mut delay: impl FnMut(Duration),
+    mut checked_in: impl FnMut() -> bool,
+) -> Result<(), StartApError> {
+    if config.poll_interval.is_zero() {
+        return Err(StartApError::ZeroPollInterval);
+    }
+
+    send(
+        apic,
+        InterruptCommand::new_init(apic_id),
+        config,
+        &mut delay,
+    )?;
+
+    // INIT level de-assert is not supported in x2APIC mode.
+    if !M::EXTENDED {
+        send(
+            apic,
+            InterruptCommand::new_init_deassert(),
+            config,
+            &mut delay,
+        )?;
+    }
+
+    delay(config.init_delay);
+
+    for _ in 0..2 {
+        send(
+            apic,
+            InterruptCommand::new_sipi(trampoline_page, apic_id),
+            config,
+            &mut delay,
+        )?;
+        delay(config.sipi_delay);
+
+        if checked_in() {
+            return Ok(());
+        }
+    }
+
+    if poll(
+        &mut checked_in,
+        config.check_in_timeout,
+        config.poll_interval,
+        &mut delay,
+    ) {
+        Ok(())
+    } else {
+        Err(StartApError::CheckInTimeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::RegisterFile;
+    use core::cell::Cell;
+
+    #[test]
+    fn skips_second_sipi_when_checked_in() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let elapsed = Cell::new(Duration::ZERO);
+
+        // Safety: The simulated local APIC has no hardware side effects.
+        let result = unsafe {
+            start_ap(
+                &apic,
+                1,
+                0x08,
+                &StartApConfig::default(),
+                |duration| elapsed.set(elapsed.get() + duration),
+                || true,
+            )
+        };
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(register_file.interrupts_sent(), 3);
+        assert!(elapsed.get() >= Duration::from_micros(10_200));
+    }
+
+    #[test]
+    fn times_out_when_not_checked_in() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let config = StartApConfig::default();
+        let elapsed = Cell::new(Duration::ZERO);
+
+        // Safety: The simulated local APIC has no hardware side effects.
+        let result = unsafe {
+            start_ap(
+                &apic,
+                1,
+                0x08,
+                &config,
+                |duration| elapsed.set(elapsed.get() + duration),
+                || false,
+            )
+        };
+
+        assert_eq!(result, Err(StartApError::CheckInTimeout));
+        assert_eq!(register_file.interrupts_sent(), 4);
+        assert!(elapsed.get() >= config.check_in_timeout);
+    }
+
+    #[test]
+    fn times_out_when_not_delivered() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let config = StartApConfig::default();
+        let elapsed = Cell::new(Duration::ZERO);
+        register_file.set_delivery_latency(usize::MAX);
+
+        // Safety: The simulated local APIC has no hardware side effects.
+        let result = unsafe {
+            start_ap(
+                &apic,
+                1,
+                0x08,
+                &config,
+                |duration| elapsed.set(elapsed.get() + duration),
+                || true,
+            )
+        };
+
+        assert_eq!(result, Err(StartApError::DeliveryTimeout));
+        assert_eq!(register_file.interrupts_sent(), 1);
+        assert!(elapsed.get() >= config.delivery_timeout);
+    }
+
+    #[test]
+    fn reports_delivery_errors() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        register_file.set_delivery_latency(1);
+
+        // Safety: The simulated local APIC has no hardware side effects.
+        let result = unsafe {
+            start_ap(
+                &apic,
+                1,
+                0x08,
+                &StartApConfig::default(),
+                |_| register_file.raise_error(ErrorStatus::SEND_ACCEPT_ERROR),
+                || true,
+            )
+        };
+
+        assert_eq!(
+            result,
+            Err(StartApError::DeliveryError(ErrorStatus::SEND_ACCEPT_ERROR))
+        );
+        assert_eq!(register_file.interrupts_sent(), 1);
+    }
+
+    #[test]
+    fn rejects_zero_poll_interval() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let config = StartApConfig {
+            poll_interval: Duration::ZERO,
+            ..StartApConfig::default()
+        };
+
+        // Safety: The simulated local APIC has no hardware side effects.
+        let result = unsafe { start_ap(&apic, 1, 0x08, &config, |_| {}, || false) };
+
+        assert_eq!(result, Err(StartApError::ZeroPollInterval));
+        assert_eq!(register_file.interrupts_sent(), 0);
+    }
+}
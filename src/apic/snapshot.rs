@@ -0,0 +1,400 @@
+use bit_field::BitField;
+
+use crate::{
+    DestinationFormat, LocalDestination, Mode, Register, TaskPriority, TimerDivideConfiguration,
+    local_vector::{
+        CMCI, Error, Kind, LINT0, LINT1, LocalVector, PerformanceMonitors, ThermalSensor, Timer,
+        TimerMode,
+    },
+    xApic,
+};
+use core::marker::PhantomData;
+
+/// Identifies an encoded [`ApicSnapshot`] ("LAPS", little-endian).
+const MAGIC: u32 = u32::from_le_bytes(*b"LAPS");
+
+/// Flags of the optional registers held in an encoded [`ApicSnapshot`].
+const HAS_DESTINATION_FORMAT: usize = 0;
+const HAS_THERMAL_SENSOR_VECTOR: usize = 1;
+const HAS_CMCI_VECTOR: usize = 2;
+const HAS_TSC_DEADLINE: usize = 3;
+
+/// Errors reported when decoding an [`ApicSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The encoding is not [`ApicSnapshot::ENCODED_LEN`] bytes long.
+    InvalidLength(usize),
+
+    /// The encoding does not start with the snapshot magic.
+    InvalidMagic(u32),
+
+    /// The encoding sets flags that are not defined.
+    ReservedFlags(u32),
+
+    /// The timer's local vector table entry holds the reserved timer mode, or selects
+    /// TSC-deadline mode without holding a deadline.
+    InvalidTimerMode(u32),
+}
+
+/// The software-visible configuration of a local APIC, saved so that it can be restored after
+/// the local APIC loses its state, such as across S3 suspend, processor hot-plug, or kexec.
+///
+/// Registers that are not implemented by the local APIC, or that do not exist in its mode, are
+/// `None`. Interrupt state (in-service, request, and error status) is not saved: it cannot be
+/// restored meaningfully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApicSnapshot {
+    pub task_priority: TaskPriority,
+    /// Raw value of the spurious interrupt vector register.
+    pub spurious_vector: u32,
+    pub local_destination: LocalDestination,
+    /// `None` in x2APIC mode, in which the destination format register does not exist.
+    pub destination_format: Option<DestinationFormat>,
+    pub timer_vector: LocalVector<Timer>,
+    /// `None` if the local APIC does not implement the thermal sensor entry.
+    pub thermal_sensor_vector: Option<LocalVector<ThermalSensor>>,
+    pub performance_monitors_vector: LocalVector<PerformanceMonitors>,
+    pub lint0_vector: LocalVector<LINT0>,
+    pub lint1_vector: LocalVector<LINT1>,
+    pub error_vector: LocalVector<Error>,
+    /// `None` if the local APIC does not implement the CMCI entry.
+    pub cmci_vector: Option<LocalVector<CMCI>>,
+    pub timer_divide_configuration: TimerDivideConfiguration,
+    /// Zero if the timer is in one-shot mode and has already expired, so that restoring the
+    /// snapshot does not re-arm it.
+    pub timer_initial_count: u32,
+    /// `None` if the timer does not support TSC-deadline mode, in which the deadline register
+    /// cannot be accessed.
+    pub tsc_deadline: Option<u64>,
+}
+
+impl ApicSnapshot {
+    /// Length of the binary encoding of a snapshot.
+    pub const ENCODED_LEN: usize = 68;
+
+    /// Encodes the snapshot as little-endian fields, such as to be stashed in a hand-off page.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut flags = 0u32;
+        flags.set_bit(HAS_DESTINATION_FORMAT, self.destination_format.is_some());
+        flags.set_bit(
+            HAS_THERMAL_SENSOR_VECTOR,
+            self.thermal_sensor_vector.is_some(),
+        );
+        flags.set_bit(HAS_CMCI_VECTOR, self.cmci_vector.is_some());
+        flags.set_bit(HAS_TSC_DEADLINE, self.tsc_deadline.is_some());
+
+        let fields = [
+            MAGIC,
+            flags,
+            self.task_priority.0,
+            self.spurious_vector,
+            self.local_destination.0,
+            self.destination_format.map_or(0, |value| value.0),
+            u32::from(self.timer_vector),
+            self.thermal_sensor_vector.map_or(0, u32::from),
+            u32::from(self.performance_monitors_vector),
+            u32::from(self.lint0_vector),
+            u32::from(self.lint1_vector),
+            u32::from(self.error_vector),
+            self.cmci_vector.map_or(0, u32::from),
+            self.timer_divide_configuration.bits(),
+            self.timer_initial_count,
+        ];
+
+        let mut bytes = [0; Self::ENCODED_LEN];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes[60..].copy_from_slice(&self.tsc_deadline.unwrap_or(0).to_le_bytes());
+
+        bytes
+    }
+
+    /// Decodes a snapshot encoded by [`ApicSnapshot::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(SnapshotError::InvalidLength(bytes.len()));
+        }
+
+        let field = |index: usize| {
+            u32::from_le_bytes(bytes[(index * 4)..((index + 1) * 4)].try_into().unwrap())
+        };
+
+        let magic = field(0);
+        if magic != MAGIC {
+            return Err(SnapshotError::InvalidMagic(magic));
+        }
+
+        let flags = field(1);
+        if flags.get_bits(4..) != 0 {
+            return Err(SnapshotError::ReservedFlags(flags));
+        }
+
+        let timer_vector = LocalVector::<Timer>(field(6), PhantomData);
+        match timer_vector.try_get_mode() {
+            Ok(TimerMode::TscDeadline) if !flags.get_bit(HAS_TSC_DEADLINE) => {
+                return Err(SnapshotError::InvalidTimerMode(u32::from(timer_vector)));
+            }
+            Err(_) => return Err(SnapshotError::InvalidTimerMode(u32::from(timer_vector))),
+            Ok(_) => {}
+        }
+
+        Ok(Self {
+            task_priority: TaskPriority(field(2)),
+            spurious_vector: field(3),
+            local_destination: LocalDestination(field(4)),
+            destination_format: flags
+                .get_bit(HAS_DESTINATION_FORMAT)
+                .then(|| DestinationFormat(field(5))),
+            timer_vector,
+            thermal_sensor_vector: flags
+                .get_bit(HAS_THERMAL_SENSOR_VECTOR)
+                .then(|| LocalVector(field(7), PhantomData)),
+            performance_monitors_vector: LocalVector(field(8), PhantomData),
+            lint0_vector: LocalVector(field(9), PhantomData),
+            lint1_vector: LocalVector(field(10), PhantomData),
+            error_vector: LocalVector(field(11), PhantomData),
+            cmci_vector: flags
+                .get_bit(HAS_CMCI_VECTOR)
+                .then(|| LocalVector(field(12), PhantomData)),
+            timer_divide_configuration: TimerDivideConfiguration::from_bits_retain(field(13)),
+            timer_initial_count: field(14),
+            tsc_deadline: flags
+                .get_bit(HAS_TSC_DEADLINE)
+                .then(|| u64::from_le_bytes(bytes[60..].try_into().unwrap())),
+        })
+    }
+}
+
+/// `value`, with its interrupt masked.
+fn masked<K: Kind>(mut value: LocalVector<K>) -> LocalVector<K> {
+    value.set_masked(true);
+    value
+}
+
+impl<M: Mode> xApic<M> {
+    /// Saves the configuration of the local APIC.
+    pub fn save(&self) -> ApicSnapshot {
+        let inner = || self.0.clone();
+        let max_lvt_entry = self.get_version().max_lvt_entry();
+        let timer_vector = M::get_timer_vector(inner());
+        let expired = timer_vector.try_get_mode() == Ok(TimerMode::OneShot)
+            && M::get_timer_current_count(inner()) == 0;
+
+        ApicSnapshot {
+            task_priority: M::get_task_priority(inner()),
+            spurious_vector: M::read_register_raw(inner(), Register::SPURIOUS_VECTOR),
+            local_destination: M::get_local_destination(inner()),
+            destination_format: (!M::EXTENDED).then(|| M::get_destination_format(inner())),
+            timer_vector,
+            thermal_sensor_vector: (max_lvt_entry >= 5)
+                .then(|| M::get_thermal_sensor_vector(inner())),
+            performance_monitors_vector: M::get_performance_monitors_vector(inner()),
+            lint0_vector: M::get_lint0_vector(inner()),
+            lint1_vector: M::get_lint1_vector(inner()),
+            error_vector: M::get_error_vector(inner()),
+            cmci_vector: (max_lvt_entry >= 6).then(|| M::get_cmci_vector(inner())),
+            timer_divide_configuration: M::get_timer_divide_configuration(inner()),
+            timer_initial_count: if expired {
+                0
+            } else {
+                M::get_timer_initial_count(inner())
+            },
+            tsc_deadline: M::is_tsc_deadline_supported(inner())
+                .then(|| M::get_tsc_deadline(inner())),
+        }
+    }
+
+    /// Restores the configuration of the local APIC from `snapshot`.
+    ///
+    /// The local APIC is software-enabled first, as the local vector table cannot be unmasked
+    /// while it is disabled. The local vector table is then programmed masked, and each entry
+    /// is only unmasked once every other register is restored; the timer is restored last, so
+    /// that it is not started before it is fully programmed. The timer restarts from its
+    /// initial count, rather than from its count at the time of the snapshot.
+    ///
+    /// # Safety
+    ///
+    /// - `snapshot` must have been saved from the local APIC of the executing processor, in
+    ///   the same mode, as restoring reprograms its destination and interrupt delivery.
+    /// - Interrupts should be disabled on the executing processor for the duration.
+    pub unsafe fn restore(&self, snapshot: &ApicSnapshot) {
+        let inner = || self.0.clone();
+
+        let mut spurious_vector = snapshot.spurious_vector;
+        spurious_vector.set_bit(8, true);
+        M::write_register_raw(inner(), Register::SPURIOUS_VECTOR, spurious_vector);
+
+        if let Some(destination_format) = snapshot.destination_format {
+            M::set_destination_format(inner(), destination_format);
+        }
+        // The logical ID is assigned by hardware in x2APIC mode.
+        if !M::EXTENDED {
+            M::set_local_destination(inner(), snapshot.local_destination);
+        }
+        M::set_task_priority(inner(), snapshot.task_priority);
+
+        M::set_timer_vector(inner(), masked(snapshot.timer_vector));
+        if let Some(thermal_sensor_vector) = snapshot.thermal_sensor_vector {
+            M::set_thermal_sensor_vector(inner(), masked(thermal_sensor_vector));
+        }
+        M::set_performance_monitors_vector(inner(), masked(snapshot.performance_monitors_vector));
+        M::set_lint0_vector(inner(), masked(snapshot.lint0_vector));
+        M::set_lint1_vector(inner(), masked(snapshot.lint1_vector));
+        M::set_error_vector(inner(), masked(snapshot.error_vector));
+        if let Some(cmci_vector) = snapshot.cmci_vector {
+            M::set_cmci_vector(inner(), masked(cmci_vector));
+        }
+
+        // Discard the errors detected while the local APIC was being reprogrammed.
+        M::clear_error_status(inner());
+        M::clear_error_status(inner());
+
+        if let Some(thermal_sensor_vector) = snapshot.thermal_sensor_vector {
+            M::set_thermal_sensor_vector(inner(), thermal_sensor_vector);
+        }
+        M::set_performance_monitors_vector(inner(), snapshot.performance_monitors_vector);
+        M::set_lint0_vector(inner(), snapshot.lint0_vector);
+        M::set_lint1_vector(inner(), snapshot.lint1_vector);
+        M::set_error_vector(inner(), snapshot.error_vector);
+        if let Some(cmci_vector) = snapshot.cmci_vector {
+            M::set_cmci_vector(inner(), cmci_vector);
+        }
+
+        M::set_timer_divide_configuration(inner(), snapshot.timer_divide_configuration);
+        // Writing the entry fences in TSC-deadline mode, so that the deadline is written after
+        // the mode switch.
+        M::set_timer_vector(inner(), snapshot.timer_vector);
+        match (snapshot.timer_vector.try_get_mode(), snapshot.tsc_deadline) {
+            (Ok(TimerMode::TscDeadline), Some(tsc_deadline)) => {
+                M::set_tsc_deadline(inner(), tsc_deadline);
+            }
+            (Ok(TimerMode::TscDeadline), None) => {}
+            _ => M::set_timer_initial_count(inner(), snapshot.timer_initial_count),
+        }
+
+        M::write_register_raw(inner(), Register::SPURIOUS_VECTOR, snapshot.spurious_vector);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DestinationModel, sim::RegisterFile};
+
+    #[test]
+    fn restores_saved_configuration() {
+        let register_file = RegisterFile::new(1, 0x0005_0014);
+        let apic = register_file.apic();
+
+        apic.get_spurious_vector().set_vector(0xFF);
+        apic.get_spurious_vector().set_apic_enabled(true);
+        apic.set_task_priority(TaskPriority::from_class(2));
+        apic.set_destination_model(DestinationModel::Cluster)
+            .unwrap();
+        apic.set_local_destination(LocalDestination(0x12));
+        let mut lint0_vector = apic.get_lint0_vector();
+        lint0_vector.set_vector(0x30);
+        lint0_vector.set_masked(false);
+        apic.set_lint0_vector(lint0_vector);
+        let mut timer_vector = apic.get_timer_vector();
+        timer_vector.set_vector(0x40);
+        timer_vector.set_masked(false);
+        apic.set_timer_vector(timer_vector);
+        apic.set_timer_divide_configuration(TimerDivideConfiguration::DIVIDE_16);
+        apic.set_timer_initial_count(5000);
+
+        let snapshot = apic.save();
+        assert_eq!(snapshot.cmci_vector, None);
+        assert_eq!(ApicSnapshot::from_bytes(&snapshot.to_bytes()), Ok(snapshot));
+
+        let resumed_file = RegisterFile::new(1, 0x0005_0014);
+        let resumed = resumed_file.apic();
+        // Safety: The simulated local APIC has no hardware side effects.
+        unsafe { resumed.restore(&snapshot) };
+
+        assert_eq!(resumed.save(), snapshot);
+        assert_eq!(resumed.get_timer_current_count(), 5000);
+        assert!(resumed.get_error_status().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_encodings() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let mut bytes = apic.save().to_bytes();
+
+        assert_eq!(
+            ApicSnapshot::from_bytes(&bytes[..64]),
+            Err(SnapshotError::InvalidLength(64))
+        );
+
+        let mut snapshot = apic.save();
+        snapshot.timer_vector.0.set_bits(17..19, 0b11);
+        assert_eq!(
+            ApicSnapshot::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::InvalidTimerMode(u32::from(
+                snapshot.timer_vector
+            )))
+        );
+        snapshot.timer_vector.set_mode(TimerMode::TscDeadline);
+        snapshot.tsc_deadline = None;
+        assert_eq!(
+            ApicSnapshot::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::InvalidTimerMode(u32::from(
+                snapshot.timer_vector
+            )))
+        );
+
+        bytes[4] = 0x80;
+        assert_eq!(
+            ApicSnapshot::from_bytes(&bytes),
+            Err(SnapshotError::ReservedFlags(0x80))
+        );
+
+        bytes[0] = 0;
+        assert!(matches!(
+            ApicSnapshot::from_bytes(&bytes),
+            Err(SnapshotError::InvalidMagic(_))
+        ));
+    }
+
+    #[test]
+    fn skips_unsupported_tsc_deadline() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        register_file.set_tsc_deadline_supported(false);
+        let apic = register_file.apic();
+        apic.set_timer_initial_count(5000);
+
+        let snapshot = apic.save();
+        assert_eq!(snapshot.tsc_deadline, None);
+        assert_eq!(ApicSnapshot::from_bytes(&snapshot.to_bytes()), Ok(snapshot));
+
+        let resumed_file = RegisterFile::new(0, 0x0005_0014);
+        resumed_file.set_tsc_deadline_supported(false);
+        let resumed = resumed_file.apic();
+        // Safety: The simulated local APIC has no hardware side effects.
+        unsafe { resumed.restore(&snapshot) };
+
+        assert_eq!(resumed.save(), snapshot);
+    }
+
+    #[test]
+    fn does_not_rearm_expired_one_shot_timer() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        apic.set_timer_initial_count(1000);
+        register_file.advance_timer(1000);
+        assert_eq!(apic.get_timer_current_count(), 0);
+
+        let snapshot = apic.save();
+        assert_eq!(snapshot.timer_initial_count, 0);
+
+        let resumed_file = RegisterFile::new(0, 0x0005_0014);
+        let resumed = resumed_file.apic();
+        // Safety: The simulated local APIC has no hardware side effects.
+        unsafe { resumed.restore(&snapshot) };
+
+        assert_eq!(resumed.get_timer_current_count(), 0);
+    }
+}
@@ -0,0 +1,248 @@
+use core::time::Duration;
+
+use crate::{ApicError, Mode, calibration::TimerFrequency, local_vector::TimerMode, xApic};
+use bit_field::BitField;
+
+/// Errors that can occur while arming the local APIC timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    /// The duration does not fit in the 32-bit initial count register at the timer's divide
+    /// configuration.
+    DurationOutOfRange,
+
+    /// The processor does not support TSC-deadline mode.
+    TscDeadlineUnsupported,
+}
+
+/// Whether the local APIC timer supports TSC-deadline mode.
+pub fn is_tsc_deadline_supported() -> bool {
+    core::arch::x86_64::__cpuid(0x1).ecx.get_bit(24)
+}
+
+/// Whether the local APIC timer keeps running in deep C-states (ARAT). Otherwise, an external
+/// timer such as an HPET comparator must wake the processor while it is idle.
+pub fn is_always_running() -> bool {
+    core::arch::x86_64::__cpuid(0x6).eax.get_bit(2)
+}
+
+/// Driver for the local APIC timer, delivering interrupts on a fixed vector.
+///
+/// The driver owns the ordering rules of the timer: switching between TSC-deadline mode and
+/// the count-down modes disarms the timer, writes to the initial count are ignored in
+/// TSC-deadline mode, and writes to `IA32_TSC_DEADLINE` are ignored outside of it.
+pub struct LapicTimer<'a, M: Mode> {
+    apic: &'a xApic<M>,
+    vector: u8,
+    frequency: TimerFrequency,
+}
+
+impl<'a, M: Mode> LapicTimer<'a, M> {
+    /// Creates a timer driver delivering `vector`, with count-down durations converted using
+    /// `frequency` (see [`crate::calibration`]).
+    ///
+    /// The timer is not reconfigured until it is first armed.
+    pub fn new(apic: &'a xApic<M>, vector: u8, frequency: TimerFrequency) -> Self {
+        assert!(vector > 15, "interrupts vectors 0..=15 are reserved");
+
+        Self {
+            apic,
+            vector,
+            frequency,
+        }
+    }
+
+    /// Creates a timer driver as [`Self::new`] does, or returns an error if `vector` is
+    /// reserved.
+    pub fn try_new(
+        apic: &'a xApic<M>,
+        vector: u8,
+        frequency: TimerFrequency,
+    ) -> Result<Self, ApicError> {
+        if vector <= 15 {
+            return Err(ApicError::ReservedVector(vector));
+        }
+
+        Ok(Self::new(apic, vector, frequency))
+    }
+
+    /// The vector delivered when the timer fires.
+    pub fn vector(&self) -> u8 {
+        self.vector
+    }
+
+    /// The tick frequency used to convert count-down durations.
+    pub fn frequency(&self) -> TimerFrequency {
+        self.frequency
+    }
+
+    /// Fires the timer once, after `duration`.
+    pub fn arm_oneshot(&self, duration: Duration) -> Result<(), TimerError> {
+        self.arm_count_down(TimerMode::OneShot, duration)
+    }
+
+    /// Fires the timer every `period`, until it is cancelled or re-armed.
+    pub fn start_periodic(&self, period: Duration) -> Result<(), TimerError> {
+        self.arm_count_down(TimerMode::Periodic, period)
+    }
+
+    /// Fires the timer once, when the timestamp counter reaches or passes `deadline`. A
+    /// deadline in the past fires immediately.
+    ///
+    /// A `deadline` of zero disarms the timer.
+    pub fn arm_deadline(&self, deadline: u64) -> Result<(), TimerError> {
+        if !self.apic.is_tsc_deadline_supported() {
+            return Err(TimerError::TscDeadlineUnsupported);
+        }
+
+        self.cancel();
+        // Writing the entry in TSC-deadline mode fences, so that the deadline is not written
+        // before the mode switch and ignored.
+        self.set_mode(TimerMode::TscDeadline);
+        self.apic.set_tsc_deadline(deadline);
+
+        Ok(())
+    }
+
+    /// Disarms the timer, without changing its mode.
+    ///
+    /// If the timer's local vector table entry holds the reserved mode encoding, both the
+    /// count-down and the deadline are stopped.
+    pub fn cancel(&self) {
+        match self.apic.get_timer_vector().try_get_mode() {
+            Ok(TimerMode::TscDeadline) => self.apic.set_tsc_deadline(0),
+            Ok(TimerMode::OneShot | TimerMode::Periodic) => self.apic.set_timer_initial_count(0),
+            Err(_) => {
+                self.apic.set_timer_initial_count(0);
+                if self.apic.is_tsc_deadline_supported() {
+                    self.apic.set_tsc_deadline(0);
+                }
+            }
+        }
+    }
+
+    fn arm_count_down(&self, mode: TimerMode, duration: Duration) -> Result<(), TimerError> {
+        // A zero initial count stops the timer, so the shortest duration is a single tick.
+        let count = u32::try_from(self.frequency.duration_to_counts(duration).max(1))
+            .map_err(|_| TimerError::DurationOutOfRange)?;
+
+        self.cancel();
+        self.set_mode(mode);
+        self.apic
+            .set_timer_divide_configuration(self.frequency.divide_configuration());
+
+        // Writing the initial count starts the count-down, so it must come last.
+        self.apic.set_timer_initial_count(count);
+
+        Ok(())
+    }
+
+    fn set_mode(&self, mode: TimerMode) {
+        let mut timer_vector = self.apic.get_timer_vector();
+        timer_vector.set_vector(self.vector);
+        timer_vector.set_mode(mode);
+        timer_vector.set_masked(false);
+        self.apic.set_timer_vector(timer_vector);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Register, TimerDivideConfiguration, sim::RegisterFile};
+
+    fn frequency() -> TimerFrequency {
+        TimerFrequency::new(1_000_000, TimerDivideConfiguration::DIVIDE_16)
+    }
+
+    #[test]
+    fn arms_count_down_modes() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let timer = LapicTimer::new(&apic, 0x40, frequency());
+
+        timer.arm_oneshot(Duration::from_millis(5)).unwrap();
+        let timer_vector = apic.get_timer_vector();
+        assert_eq!(timer_vector.get_mode(), TimerMode::OneShot);
+        assert_eq!(timer_vector.get_vector(), 0x40);
+        assert!(!timer_vector.get_masked());
+        assert_eq!(
+            apic.get_timer_divide_configuration(),
+            TimerDivideConfiguration::DIVIDE_16
+        );
+        assert_eq!(apic.get_timer_initial_count(), 5_000);
+
+        timer.start_periodic(Duration::ZERO).unwrap();
+        assert_eq!(apic.get_timer_vector().get_mode(), TimerMode::Periodic);
+        assert_eq!(apic.get_timer_initial_count(), 1);
+
+        timer.cancel();
+        assert_eq!(apic.get_timer_current_count(), 0);
+
+        assert_eq!(
+            timer.arm_oneshot(Duration::from_secs(5_000)),
+            Err(TimerError::DurationOutOfRange)
+        );
+    }
+
+    #[test]
+    fn switches_to_and_from_deadline_mode() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let timer = LapicTimer::new(&apic, 0x40, frequency());
+
+        timer.arm_oneshot(Duration::from_millis(5)).unwrap();
+        timer.arm_deadline(0x1234_5678).unwrap();
+        assert_eq!(apic.get_timer_vector().get_mode(), TimerMode::TscDeadline);
+        assert_eq!(apic.get_timer_current_count(), 0);
+        assert_eq!(register_file.tsc_deadline(), 0x1234_5678);
+
+        timer.cancel();
+        assert_eq!(register_file.tsc_deadline(), 0);
+
+        timer.arm_deadline(0x1234_5678).unwrap();
+        timer.start_periodic(Duration::from_millis(1)).unwrap();
+        assert_eq!(register_file.tsc_deadline(), 0);
+        assert_eq!(apic.get_timer_initial_count(), 1_000);
+    }
+
+    #[test]
+    fn rejects_unsupported_deadline_mode() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        register_file.set_tsc_deadline_supported(false);
+        let apic = register_file.apic();
+        let timer = LapicTimer::new(&apic, 0x40, frequency());
+
+        assert_eq!(
+            timer.arm_deadline(0x1234_5678),
+            Err(TimerError::TscDeadlineUnsupported)
+        );
+        assert!(matches!(
+            LapicTimer::try_new(&apic, 0x0F, frequency()),
+            Err(ApicError::ReservedVector(0x0F))
+        ));
+
+        let mut timer_vector = apic.get_timer_vector();
+        timer_vector.set_mode(TimerMode::TscDeadline);
+        assert_eq!(
+            apic.try_set_timer_vector(timer_vector),
+            Err(ApicError::Unsupported("TSC-deadline timer mode"))
+        );
+    }
+
+    #[test]
+    fn cancels_reserved_mode() {
+        let register_file = RegisterFile::new(0, 0x0005_0014);
+        let apic = register_file.apic();
+        let timer = LapicTimer::new(&apic, 0x40, frequency());
+
+        timer.arm_oneshot(Duration::from_millis(5)).unwrap();
+        apic.write_register_raw(Register::TIMER_VECTOR, 0x0006_0040)
+            .unwrap();
+
+        timer.cancel();
+        assert_eq!(apic.get_timer_current_count(), 0);
+
+        timer.start_periodic(Duration::from_millis(1)).unwrap();
+        assert_eq!(apic.get_timer_vector().get_mode(), TimerMode::Periodic);
+    }
+}
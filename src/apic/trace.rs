@@ -0,0 +1,876 @@
+use core::{cell::Cell, fmt, marker::PhantomData};
+
+use crate::{
+    ArbitrationPriority, DestinationFormat, ErrorStatus, InterruptCommand, LocalDestination, Mode,
+    ProcessorPriority, Register, RemoteRead, TaskPriority, TimerDivideConfiguration, VectorBitmap,
+    Version,
+    local_vector::{
+        CMCI, Error, LINT0, LINT1, LocalVector, PerformanceMonitors, ThermalSensor, Timer,
+    },
+};
+
+#[cfg(any(test, feature = "sim"))]
+use crate::sim::{RegisterFile, sim};
+
+/// A single access to the local APIC, recorded by [`Traced`].
+///
+/// Raw register accesses are recorded with their register; every other operation of [`Mode`]
+/// that accesses the local APIC is recorded with its argument (for writes) or its result (for
+/// reads).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    ReadRaw { register: Register, value: u32 },
+    WriteRaw { register: Register, value: u32 },
+    GetId(u32),
+    GetVersion(Version),
+    GetTaskPriority(TaskPriority),
+    SetTaskPriority(TaskPriority),
+    GetArbitrationPriority(ArbitrationPriority),
+    GetProcessorPriority(ProcessorPriority),
+    GetRemoteRead(RemoteRead),
+    GetLocalDestination(LocalDestination),
+    SetLocalDestination(LocalDestination),
+    GetDestinationFormat(DestinationFormat),
+    SetDestinationFormat(DestinationFormat),
+    GetInService(VectorBitmap),
+    GetTriggerMode(VectorBitmap),
+    GetInterruptRequest(VectorBitmap),
+    GetErrorStatus(ErrorStatus),
+    ClearErrorStatus,
+    GetTimerInitialCount(u32),
+    SetTimerInitialCount(u32),
+    GetTimerCurrentCount(u32),
+    GetTimerDivideConfiguration(TimerDivideConfiguration),
+    SetTimerDivideConfiguration(TimerDivideConfiguration),
+    GetTscDeadline(u64),
+    SetTscDeadline(u64),
+    SendInterruptCommand(InterruptCommand),
+    SendSelfIpi(u8),
+    GetSpuriousVector(u8),
+    GetSpuriousApicSoftwareEnabled(bool),
+    GetSpuriousFocusProcessorChecking(bool),
+    GetSpuriousEoiBroadcastSuppression(bool),
+    SetSpuriousVector(u8),
+    SetSpuriousApicSoftwareEnabled(bool),
+    SetSpuriousFocusProcessorChecking(bool),
+    SetSpuriousEoiBroadcastSuppression(bool),
+    GetTimerVector(LocalVector<Timer>),
+    SetTimerVector(LocalVector<Timer>),
+    GetCmciVector(LocalVector<CMCI>),
+    SetCmciVector(LocalVector<CMCI>),
+    GetLint0Vector(LocalVector<LINT0>),
+    SetLint0Vector(LocalVector<LINT0>),
+    GetLint1Vector(LocalVector<LINT1>),
+    SetLint1Vector(LocalVector<LINT1>),
+    GetErrorVector(LocalVector<Error>),
+    SetErrorVector(LocalVector<Error>),
+    GetPerformanceMonitorsVector(LocalVector<PerformanceMonitors>),
+    SetPerformanceMonitorsVector(LocalVector<PerformanceMonitors>),
+    GetThermalSensorVector(LocalVector<ThermalSensor>),
+    SetThermalSensorVector(LocalVector<ThermalSensor>),
+    EndOfInterrupt,
+}
+
+/// Direction of an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+}
+
+impl Event {
+    /// The direction, register (or field of the spurious interrupt register), and decoded
+    /// value of a non-raw event.
+    fn parts(&self) -> (Access, &'static str, Option<&dyn fmt::Debug>) {
+        use Access::{Read, Write};
+
+        match self {
+            Self::ReadRaw { .. } | Self::WriteRaw { .. } => unreachable!(),
+            Self::GetId(value) => (Read, "ID", Some(value)),
+            Self::GetVersion(value) => (Read, "VERSION", Some(value)),
+            Self::GetTaskPriority(value) => (Read, "TASK_PRIORITY", Some(value)),
+            Self::SetTaskPriority(value) => (Write, "TASK_PRIORITY", Some(value)),
+            Self::GetArbitrationPriority(value) => (Read, "ARBITRATION_PRIORITY", Some(value)),
+            Self::GetProcessorPriority(value) => (Read, "PROCESSOR_PRIORITY", Some(value)),
+            Self::GetRemoteRead(value) => (Read, "REMOTE_READ", Some(value)),
+            Self::GetLocalDestination(value) => (Read, "LOCAL_DESTINATION", Some(value)),
+            Self::SetLocalDestination(value) => (Write, "LOCAL_DESTINATION", Some(value)),
+            Self::GetDestinationFormat(value) => (Read, "DESTINATION_FORMAT", Some(value)),
+            Self::SetDestinationFormat(value) => (Write, "DESTINATION_FORMAT", Some(value)),
+            Self::GetInService(value) => (Read, "IN_SERVICE", Some(value)),
+            Self::GetTriggerMode(value) => (Read, "TRIGGER_MODE", Some(value)),
+            Self::GetInterruptRequest(value) => (Read, "INTERRUPT_REQUEST", Some(value)),
+            Self::GetErrorStatus(value) => (Read, "ERROR_STATUS", Some(value)),
+            Self::ClearErrorStatus => (Write, "ERROR_STATUS", None),
+            Self::GetTimerInitialCount(value) => (Read, "TIMER_INITIAL_COUNT", Some(value)),
+            Self::SetTimerInitialCount(value) => (Write, "TIMER_INITIAL_COUNT", Some(value)),
+            Self::GetTimerCurrentCount(value) => (Read, "TIMER_CURRENT_COUNT", Some(value)),
+            Self::GetTimerDivideConfiguration(value) => {
+                (Read, "TIMER_DIVIDE_CONFIGURATION", Some(value))
+            }
+            Self::SetTimerDivideConfiguration(value) => {
+                (Write, "TIMER_DIVIDE_CONFIGURATION", Some(value))
+            }
+            Self::GetTscDeadline(value) => (Read, "IA32_TSC_DEADLINE", Some(value)),
+            Self::SetTscDeadline(value) => (Write, "IA32_TSC_DEADLINE", Some(value)),
+            Self::SendInterruptCommand(value) => (Write, "INTERRUPT_COMMAND", Some(value)),
+            Self::SendSelfIpi(value) => (Write, "SELF_IPI", Some(value)),
+            Self::GetSpuriousVector(value) => (Read, "SPURIOUS_VECTOR.Vector", Some(value)),
+            Self::GetSpuriousApicSoftwareEnabled(value) => {
+                (Read, "SPURIOUS_VECTOR.APIC Enabled", Some(value))
+            }
+            Self::GetSpuriousFocusProcessorChecking(value) => (
+                Read,
+                "SPURIOUS_VECTOR.Focus Processor Checking",
+                Some(value),
+            ),
+            Self::GetSpuriousEoiBroadcastSuppression(value) => (
+                Read,
+                "SPURIOUS_VECTOR.EOI Broadcast Suppression",
+                Some(value),
+            ),
+            Self::SetSpuriousVector(value) => (Write, "SPURIOUS_VECTOR.Vector", Some(value)),
+            Self::SetSpuriousApicSoftwareEnabled(value) => {
+                (Write, "SPURIOUS_VECTOR.APIC Enabled", Some(value))
+            }
+            Self::SetSpuriousFocusProcessorChecking(value) => (
+                Write,
+                "SPURIOUS_VECTOR.Focus Processor Checking",
+                Some(value),
+            ),
+            Self::SetSpuriousEoiBroadcastSuppression(value) => (
+                Write,
+                "SPURIOUS_VECTOR.EOI Broadcast Suppression",
+                Some(value),
+            ),
+            Self::GetTimerVector(value) => (Read, "TIMER_VECTOR", Some(value)),
+            Self::SetTimerVector(value) => (Write, "TIMER_VECTOR", Some(value)),
+            Self::GetCmciVector(value) => (Read, "CMCI_VECTOR", Some(value)),
+            Self::SetCmciVector(value) => (Write, "CMCI_VECTOR", Some(value)),
+            Self::GetLint0Vector(value) => (Read, "LINT0_VECTOR", Some(value)),
+            Self::SetLint0Vector(value) => (Write, "LINT0_VECTOR", Some(value)),
+            Self::GetLint1Vector(value) => (Read, "LINT1_VECTOR", Some(value)),
+            Self::SetLint1Vector(value) => (Write, "LINT1_VECTOR", Some(value)),
+            Self::GetErrorVector(value) => (Read, "ERROR_VECTOR", Some(value)),
+            Self::SetErrorVector(value) => (Write, "ERROR_VECTOR", Some(value)),
+            Self::GetPerformanceMonitorsVector(value) => {
+                (Read, "PERFORMANCE_MONITORS_VECTOR", Some(value))
+            }
+            Self::SetPerformanceMonitorsVector(value) => {
+                (Write, "PERFORMANCE_MONITORS_VECTOR", Some(value))
+            }
+            Self::GetThermalSensorVector(value) => (Read, "THERMAL_SENSOR_VECTOR", Some(value)),
+            Self::SetThermalSensorVector(value) => (Write, "THERMAL_SENSOR_VECTOR", Some(value)),
+            Self::EndOfInterrupt => (Write, "END_OF_INTERRUPT", None),
+        }
+    }
+}
+
+/// Writes the name of `register`, including the index of registers within the 256-bit
+/// register banks.
+fn write_register_name(f: &mut fmt::Formatter<'_>, register: Register) -> fmt::Result {
+    match register {
+        Register::IN_SERVICE(index) => write!(f, "IN_SERVICE[{index}]"),
+        Register::TRIGGER_MODE(index) => write!(f, "TRIGGER_MODE[{index}]"),
+        Register::INTERRUPT_REQUEST(index) => write!(f, "INTERRUPT_REQUEST[{index}]"),
+        register => write!(f, "{register:?}"),
+    }
+}
+
+impl fmt::Display for Event {
+    /// Decodes the event into a line such as `write TIMER_INITIAL_COUNT: 1000`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Self::ReadRaw { register, value } | Self::WriteRaw { register, value } = *self {
+            let access = match self {
+                Self::ReadRaw { .. } => Access::Read,
+                _ => Access::Write,
+            };
+            write!(f, "{} ", access.as_str())?;
+            write_register_name(f, register)?;
+
+            return write!(f, " (raw): {value:#010X}");
+        }
+
+        let (access, register, value) = self.parts();
+        write!(f, "{} {register}", access.as_str())?;
+        match value {
+            Some(value) => write!(f, ": {value:?}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Destination of the events recorded by [`Traced`].
+pub trait TraceSink {
+    fn record(&self, event: Event);
+}
+
+/// Ring buffer of the most recent `N` events.
+pub struct TraceBuffer<const N: usize = 64> {
+    events: [Cell<Option<Event>>; N],
+    next: Cell<usize>,
+    recorded: Cell<u64>,
+}
+
+impl<const N: usize> TraceBuffer<N> {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        const { assert!(N > 0, "a trace buffer must hold at least one event") };
+
+        Self {
+            events: core::array::from_fn(|_| Cell::new(None)),
+            next: Cell::new(0),
+            recorded: Cell::new(0),
+        }
+    }
+
+    /// The number of events in the buffer.
+    pub fn len(&self) -> usize {
+        usize::try_from(self.recorded.get()).map_or(N, |recorded| recorded.min(N))
+    }
+
+    /// Whether no events have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.recorded.get() == 0
+    }
+
+    /// The number of events recorded, including those that have since been overwritten.
+    pub fn recorded(&self) -> u64 {
+        self.recorded.get()
+    }
+
+    /// Discards every event.
+    pub fn clear(&self) {
+        for event in &self.events {
+            event.set(None);
+        }
+
+        self.next.set(0);
+        self.recorded.set(0);
+    }
+
+    /// Iterates the events in the buffer, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        let oldest = if self.len() < N { 0 } else { self.next.get() };
+
+        (0..self.len()).filter_map(move |index| self.events[(oldest + index) % N].get())
+    }
+}
+
+impl<const N: usize> Default for TraceBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TraceSink for TraceBuffer<N> {
+    fn record(&self, event: Event) {
+        let next = self.next.get();
+        self.events[next].set(Some(event));
+        self.next.set((next + 1) % N);
+        self.recorded.set(self.recorded.get() + 1);
+    }
+}
+
+/// Inner value of [`Traced`]: the inner value of the traced mode, and the sink to record to.
+pub struct Tracer<'a, M: Mode, S: ?Sized> {
+    inner: M::Inner,
+    sink: &'a S,
+}
+
+impl<'a, M: Mode, S: TraceSink + ?Sized> Tracer<'a, M, S> {
+    /// Creates a tracer recording the accesses to `inner` into `sink`.
+    pub fn new(inner: M::Inner, sink: &'a S) -> Self {
+        Self { inner, sink }
+    }
+
+    fn get<T: Copy>(self, get: fn(M::Inner) -> T, event: fn(T) -> Event) -> T {
+        let value = get(self.inner);
+        self.sink.record(event(value));
+
+        value
+    }
+
+    /// Records the event before the access, so that an access that faults is still recorded.
+    fn set<T: Copy>(self, set: fn(M::Inner, T), event: fn(T) -> Event, value: T) {
+        self.sink.record(event(value));
+        set(self.inner, value);
+    }
+}
+
+impl<M: Mode, S: ?Sized> Clone for Tracer<'_, M, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            sink: self.sink,
+        }
+    }
+}
+
+/// Mode adapter that forwards every operation to `M`, recording it into a [`TraceSink`].
+///
+/// A traced local APIC is created over a [`Tracer`], such as
+/// `xApic::<Traced<x1>>::new(Tracer::new(&mmio, &trace))`. The recorded trace can be decoded
+/// with the [`Event`] `Display` implementation, and reproduced on the host with `replay` (with
+/// the `sim` feature).
+pub struct Traced<'a, M: Mode, S: ?Sized = TraceBuffer>(PhantomData<Tracer<'a, M, S>>);
+
+impl<'a, M: Mode, S: TraceSink + ?Sized> Mode for Traced<'a, M, S> {
+    type Inner = Tracer<'a, M, S>;
+
+    const EXTENDED: bool = M::EXTENDED;
+
+    fn read_register_raw(inner: Self::Inner, register: Register) -> u32 {
+        let value = M::read_register_raw(inner.inner, register);
+        inner.sink.record(Event::ReadRaw { register, value });
+
+        value
+    }
+
+    fn write_register_raw(inner: Self::Inner, register: Register, value: u32) {
+        inner.sink.record(Event::WriteRaw { register, value });
+        M::write_register_raw(inner.inner, register, value);
+    }
+
+    fn get_id(inner: Self::Inner) -> u32 {
+        inner.get(M::get_id, Event::GetId)
+    }
+
+    fn get_version(inner: Self::Inner) -> Version {
+        inner.get(M::get_version, Event::GetVersion)
+    }
+
+    fn get_task_priority(inner: Self::Inner) -> TaskPriority {
+        inner.get(M::get_task_priority, Event::GetTaskPriority)
+    }
+
+    fn set_task_priority(inner: Self::Inner, value: TaskPriority) {
+        inner.set(M::set_task_priority, Event::SetTaskPriority, value);
+    }
+
+    fn get_arbitration_priority(inner: Self::Inner) -> ArbitrationPriority {
+        inner.get(M::get_arbitration_priority, Event::GetArbitrationPriority)
+    }
+
+    fn get_processor_priority(inner: Self::Inner) -> ProcessorPriority {
+        inner.get(M::get_processor_priority, Event::GetProcessorPriority)
+    }
+
+    fn get_remote_read(inner: Self::Inner) -> RemoteRead {
+        inner.get(M::get_remote_read, Event::GetRemoteRead)
+    }
+
+    fn get_local_destination(inner: Self::Inner) -> LocalDestination {
+        inner.get(M::get_local_destination, Event::GetLocalDestination)
+    }
+
+    fn set_local_destination(inner: Self::Inner, value: LocalDestination) {
+        inner.set(M::set_local_destination, Event::SetLocalDestination, value);
+    }
+
+    fn get_destination_format(inner: Self::Inner) -> DestinationFormat {
+        inner.get(M::get_destination_format, Event::GetDestinationFormat)
+    }
+
+    fn set_destination_format(inner: Self::Inner, value: DestinationFormat) {
+        inner.set(
+            M::set_destination_format,
+            Event::SetDestinationFormat,
+            value,
+        );
+    }
+
+    fn get_in_service(inner: Self::Inner) -> VectorBitmap {
+        inner.get(M::get_in_service, Event::GetInService)
+    }
+
+    fn get_trigger_mode(inner: Self::Inner) -> VectorBitmap {
+        inner.get(M::get_trigger_mode, Event::GetTriggerMode)
+    }
+
+    fn get_interrupt_request(inner: Self::Inner) -> VectorBitmap {
+        inner.get(M::get_interrupt_request, Event::GetInterruptRequest)
+    }
+
+    fn get_error_status(inner: Self::Inner) -> ErrorStatus {
+        inner.get(M::get_error_status, Event::GetErrorStatus)
+    }
+
+    fn clear_error_status(inner: Self::Inner) {
+        inner.sink.record(Event::ClearErrorStatus);
+        M::clear_error_status(inner.inner);
+    }
+
+    fn get_timer_initial_count(inner: Self::Inner) -> u32 {
+        inner.get(M::get_timer_initial_count, Event::GetTimerInitialCount)
+    }
+
+    fn set_timer_initial_count(inner: Self::Inner, value: u32) {
+        inner.set(
+            M::set_timer_initial_count,
+            Event::SetTimerInitialCount,
+            value,
+        );
+    }
+
+    fn get_timer_current_count(inner: Self::Inner) -> u32 {
+        inner.get(M::get_timer_current_count, Event::GetTimerCurrentCount)
+    }
+
+    fn get_timer_divide_configuration(inner: Self::Inner) -> TimerDivideConfiguration {
+        inner.get(
+            M::get_timer_divide_configuration,
+            Event::GetTimerDivideConfiguration,
+        )
+    }
+
+    fn set_timer_divide_configuration(inner: Self::Inner, value: TimerDivideConfiguration) {
+        inner.set(
+            M::set_timer_divide_configuration,
+            Event::SetTimerDivideConfiguration,
+            value,
+        );
+    }
+
+    /// Forwarded without being recorded, as it does not access the local APIC.
+    fn is_tsc_deadline_supported(inner: Self::Inner) -> bool {
+        M::is_tsc_deadline_supported(inner.inner)
+    }
+
+    fn get_tsc_deadline(inner: Self::Inner) -> u64 {
+        inner.get(M::get_tsc_deadline, Event::GetTscDeadline)
+    }
+
+    fn set_tsc_deadline(inner: Self::Inner, deadline: u64) {
+        inner.set(M::set_tsc_deadline, Event::SetTscDeadline, deadline);
+    }
+
+    fn send_interrupt_command(inner: Self::Inner, interrupt_command: InterruptCommand) {
+        inner.set(
+            M::send_interrupt_command,
+            Event::SendInterruptCommand,
+            interrupt_command,
+        );
+    }
+
+    fn send_self_ipi(inner: Self::Inner, vector: u8) {
+        inner.set(M::send_self_ipi, Event::SendSelfIpi, vector);
+    }
+
+    fn get_spurious_vector(inner: Self::Inner) -> u8 {
+        inner.get(M::get_spurious_vector, Event::GetSpuriousVector)
+    }
+
+    fn get_spurious_apic_software_enabled(inner: Self::Inner) -> bool {
+        inner.get(
+            M::get_spurious_apic_software_enabled,
+            Event::GetSpuriousApicSoftwareEnabled,
+        )
+    }
+
+    fn get_spurious_focus_processor_checking(inner: Self::Inner) -> bool {
+        inner.get(
+            M::get_spurious_focus_processor_checking,
+            Event::GetSpuriousFocusProcessorChecking,
+        )
+    }
+
+    fn get_spurious_eoi_broadcast_suppression(inner: Self::Inner) -> bool {
+        inner.get(
+            M::get_spurious_eoi_broadcast_suppression,
+            Event::GetSpuriousEoiBroadcastSuppression,
+        )
+    }
+
+    fn set_spurious_vector(inner: Self::Inner, vector: u8) {
+        inner.set(M::set_spurious_vector, Event::SetSpuriousVector, vector);
+    }
+
+    fn set_spurious_apic_software_enabled(inner: Self::Inner, value: bool) {
+        inner.set(
+            M::set_spurious_apic_software_enabled,
+            Event::SetSpuriousApicSoftwareEnabled,
+            value,
+        );
+    }
+
+    fn set_spurious_focus_processor_checking(inner: Self::Inner, value: bool) {
+        inner.set(
+            M::set_spurious_focus_processor_checking,
+            Event::SetSpuriousFocusProcessorChecking,
+            value,
+        );
+    }
+
+    fn set_spurious_eoi_broadcast_suppression(inner: Self::Inner, value: bool) {
+        inner.set(
+            M::set_spurious_eoi_broadcast_suppression,
+            Event::SetSpuriousEoiBroadcastSuppression,
+            value,
+        );
+    }
+
+    fn get_timer_vector(inner: Self::Inner) -> LocalVector<Timer> {
+        inner.get(M::get_timer_vector, Event::GetTimerVector)
+    }
+
+    fn set_timer_vector(inner: Self::Inner, value: LocalVector<Timer>) {
+        inner.set(M::set_timer_vector, Event::SetTimerVector, value);
+    }
+
+    fn get_cmci_vector(inner: Self::Inner) -> LocalVector<CMCI> {
+        inner.get(M::get_cmci_vector, Event::GetCmciVector)
+    }
+
+    fn set_cmci_vector(inner: Self::Inner, value: LocalVector<CMCI>) {
+        inner.set(M::set_cmci_vector, Event::SetCmciVector, value);
+    }
+
+    fn get_lint0_vector(inner: Self::Inner) -> LocalVector<LINT0> {
+        inner.get(M::get_lint0_vector, Event::GetLint0Vector)
+    }
+
+    fn set_lint0_vector(inner: Self::Inner, value: LocalVector<LINT0>) {
+        inner.set(M::set_lint0_vector, Event::SetLint0Vector, value);
+    }
+
+    fn get_lint1_vector(inner: Self::Inner) -> LocalVector<LINT1> {
+        inner.get(M::get_lint1_vector, Event::GetLint1Vector)
+    }
+
+    fn set_lint1_vector(inner: Self::Inner, value: LocalVector<LINT1>) {
+        inner.set(M::set_lint1_vector, Event::SetLint1Vector, value);
+    }
+
+    fn get_error_vector(inner: Self::Inner) -> LocalVector<Error> {
+        inner.get(M::get_error_vector, Event::GetErrorVector)
+    }
+
+    fn set_error_vector(inner: Self::Inner, value: LocalVector<Error>) {
+        inner.set(M::set_error_vector, Event::SetErrorVector, value);
+    }
+
+    fn get_performance_monitors_vector(inner: Self::Inner) -> LocalVector<PerformanceMonitors> {
+        inner.get(
+            M::get_performance_monitors_vector,
+            Event::GetPerformanceMonitorsVector,
+        )
+    }
+
+    fn set_performance_monitors_vector(
+        inner: Self::Inner,
+        value: LocalVector<PerformanceMonitors>,
+    ) {
+        inner.set(
+            M::set_performance_monitors_vector,
+            Event::SetPerformanceMonitorsVector,
+            value,
+        );
+    }
+
+    fn get_thermal_sensor_vector(inner: Self::Inner) -> LocalVector<ThermalSensor> {
+        inner.get(M::get_thermal_sensor_vector, Event::GetThermalSensorVector)
+    }
+
+    fn set_thermal_sensor_vector(inner: Self::Inner, value: LocalVector<ThermalSensor>) {
+        inner.set(
+            M::set_thermal_sensor_vector,
+            Event::SetThermalSensorVector,
+            value,
+        );
+    }
+
+    fn end_of_interrrupt(inner: Self::Inner) {
+        inner.sink.record(Event::EndOfInterrupt);
+        M::end_of_interrrupt(inner.inner);
+    }
+}
+
+/// Where replaying a trace into the simulated local APIC diverged from the recording: a read
+/// returned a different value than the one recorded.
+#[cfg(any(test, feature = "sim"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the event within the trace.
+    pub index: usize,
+    pub recorded: Event,
+    pub replayed: Event,
+}
+
+/// Replays `event` into `register_file`, returning the event as observed in the simulation.
+///
+/// Writes are applied and returned unchanged. Reads are performed, and return the simulated
+/// value. Raw accesses to registers that are not present in xAPIC mode are skipped, except for
+/// writes to the self IPI register, which are replayed as self IPIs.
+#[cfg(any(test, feature = "sim"))]
+pub fn replay_event(register_file: &RegisterFile, event: Event) -> Event {
+    match event {
+        Event::ReadRaw { register, .. } if register.xapic_offset().is_some() => Event::ReadRaw {
+            register,
+            value: sim::read_register_raw(register_file, register),
+        },
+        Event::ReadRaw { .. } => event,
+        Event::WriteRaw {
+            register: Register::SELF_IPI,
+            value,
+        } => {
+            sim::send_self_ipi(register_file, value as u8);
+            event
+        }
+        Event::WriteRaw { register, value } => {
+            if register.xapic_offset().is_some() {
+                sim::write_register_raw(register_file, register, value);
+            }
+
+            event
+        }
+        Event::GetId(_) => Event::GetId(sim::get_id(register_file)),
+        Event::GetVersion(_) => Event::GetVersion(sim::get_version(register_file)),
+        Event::GetTaskPriority(_) => Event::GetTaskPriority(sim::get_task_priority(register_file)),
+        Event::SetTaskPriority(value) => {
+            sim::set_task_priority(register_file, value);
+            event
+        }
+        Event::GetArbitrationPriority(_) => {
+            Event::GetArbitrationPriority(sim::get_arbitration_priority(register_file))
+        }
+        Event::GetProcessorPriority(_) => {
+            Event::GetProcessorPriority(sim::get_processor_priority(register_file))
+        }
+        Event::GetRemoteRead(_) => Event::GetRemoteRead(sim::get_remote_read(register_file)),
+        Event::GetLocalDestination(_) => {
+            Event::GetLocalDestination(sim::get_local_destination(register_file))
+        }
+        Event::SetLocalDestination(value) => {
+            sim::set_local_destination(register_file, value);
+            event
+        }
+        Event::GetDestinationFormat(_) => {
+            Event::GetDestinationFormat(sim::get_destination_format(register_file))
+        }
+        Event::SetDestinationFormat(value) => {
+            sim::set_destination_format(register_file, value);
+            event
+        }
+        Event::GetInService(_) => Event::GetInService(sim::get_in_service(register_file)),
+        Event::GetTriggerMode(_) => Event::GetTriggerMode(sim::get_trigger_mode(register_file)),
+        Event::GetInterruptRequest(_) => {
+            Event::GetInterruptRequest(sim::get_interrupt_request(register_file))
+        }
+        Event::GetErrorStatus(_) => Event::GetErrorStatus(sim::get_error_status(register_file)),
+        Event::ClearErrorStatus => {
+            sim::clear_error_status(register_file);
+            event
+        }
+        Event::GetTimerInitialCount(_) => {
+            Event::GetTimerInitialCount(sim::get_timer_initial_count(register_file))
+        }
+        Event::SetTimerInitialCount(value) => {
+            sim::set_timer_initial_count(register_file, value);
+            event
+        }
+        Event::GetTimerCurrentCount(_) => {
+            Event::GetTimerCurrentCount(sim::get_timer_current_count(register_file))
+        }
+        Event::GetTimerDivideConfiguration(_) => {
+            Event::GetTimerDivideConfiguration(sim::get_timer_divide_configuration(register_file))
+        }
+        Event::SetTimerDivideConfiguration(value) => {
+            sim::set_timer_divide_configuration(register_file, value);
+            event
+        }
+        Event::GetTscDeadline(_) => Event::GetTscDeadline(sim::get_tsc_deadline(register_file)),
+        Event::SetTscDeadline(value) => {
+            sim::set_tsc_deadline(register_file, value);
+            event
+        }
+        Event::SendInterruptCommand(value) => {
+            sim::send_interrupt_command(register_file, value);
+            event
+        }
+        Event::SendSelfIpi(value) => {
+            sim::send_self_ipi(register_file, value);
+            event
+        }
+        Event::GetSpuriousVector(_) => {
+            Event::GetSpuriousVector(sim::get_spurious_vector(register_file))
+        }
+        Event::GetSpuriousApicSoftwareEnabled(_) => Event::GetSpuriousApicSoftwareEnabled(
+            sim::get_spurious_apic_software_enabled(register_file),
+        ),
+        Event::GetSpuriousFocusProcessorChecking(_) => Event::GetSpuriousFocusProcessorChecking(
+            sim::get_spurious_focus_processor_checking(register_file),
+        ),
+        Event::GetSpuriousEoiBroadcastSuppression(_) => Event::GetSpuriousEoiBroadcastSuppression(
+            sim::get_spurious_eoi_broadcast_suppression(register_file),
+        ),
+        Event::SetSpuriousVector(value) => {
+            sim::set_spurious_vector(register_file, value);
+            event
+        }
+        Event::SetSpuriousApicSoftwareEnabled(value) => {
+            sim::set_spurious_apic_software_enabled(register_file, value);
+            event
+        }
+        Event::SetSpuriousFocusProcessorChecking(value) => {
+            sim::set_spurious_focus_processor_checking(register_file, value);
+            event
+        }
+        Event::SetSpuriousEoiBroadcastSuppression(value) => {
+            sim::set_spurious_eoi_broadcast_suppression(register_file, value);
+            event
+        }
+        Event::GetTimerVector(_) => Event::GetTimerVector(sim::get_timer_vector(register_file)),
+        Event::SetTimerVector(value) => {
+            sim::set_timer_vector(register_file, value);
+            event
+        }
+        Event::GetCmciVector(_) => Event::GetCmciVector(sim::get_cmci_vector(register_file)),
+        Event::SetCmciVector(value) => {
+            sim::set_cmci_vector(register_file, value);
+            event
+        }
+        Event::GetLint0Vector(_) => Event::GetLint0Vector(sim::get_lint0_vector(register_file)),
+        Event::SetLint0Vector(value) => {
+            sim::set_lint0_vector(register_file, value);
+            event
+        }
+        Event::GetLint1Vector(_) => Event::GetLint1Vector(sim::get_lint1_vector(register_file)),
+        Event::SetLint1Vector(value) => {
+            sim::set_lint1_vector(register_file, value);
+            event
+        }
+        Event::GetErrorVector(_) => Event::GetErrorVector(sim::get_error_vector(register_file)),
+        Event::SetErrorVector(value) => {
+            sim::set_error_vector(register_file, value);
+            event
+        }
+        Event::GetPerformanceMonitorsVector(_) => {
+            Event::GetPerformanceMonitorsVector(sim::get_performance_monitors_vector(register_file))
+        }
+        Event::SetPerformanceMonitorsVector(value) => {
+            sim::set_performance_monitors_vector(register_file, value);
+            event
+        }
+        Event::GetThermalSensorVector(_) => {
+            Event::GetThermalSensorVector(sim::get_thermal_sensor_vector(register_file))
+        }
+        Event::SetThermalSensorVector(value) => {
+            sim::set_thermal_sensor_vector(register_file, value);
+            event
+        }
+        Event::EndOfInterrupt => {
+            sim::end_of_interrrupt(register_file);
+            event
+        }
+    }
+}
+
+/// Replays `trace` into `register_file`, stopping at the first read that returns a different
+/// value than recorded. Returns the number of events replayed.
+///
+/// The register file should be created in the state of the traced local APIC when recording
+/// started, typically with the same APIC ID and version.
+#[cfg(any(test, feature = "sim"))]
+pub fn replay(
+    register_file: &RegisterFile,
+    trace: impl IntoIterator<Item = Event>,
+) -> Result<usize, Divergence> {
+    let mut count = 0;
+    for (index, recorded) in trace.into_iter().enumerate() {
+        let replayed = replay_event(register_file, recorded);
+        if replayed != recorded {
+            return Err(Divergence {
+                index,
+                recorded,
+                replayed,
+            });
+        }
+
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apic::fmt_buffer::FmtBuffer, local_vector::TimerMode, xApic};
+    use core::fmt::Write;
+
+    fn record(register_file: &RegisterFile, trace: &TraceBuffer<8>) {
+        // Safety: The simulated local APIC has no hardware side effects.
+        let apic = unsafe { xApic::<Traced<sim, _>>::new(Tracer::new(register_file, trace)) };
+
+        apic.get_id();
+        let mut timer_vector = apic.get_timer_vector();
+        timer_vector.set_vector(0x40);
+        timer_vector.set_mode(TimerMode::Periodic);
+        timer_vector.set_masked(false);
+        apic.set_timer_vector(timer_vector);
+        apic.set_timer_initial_count(1000);
+        apic.get_timer_vector();
+        apic.send_self_ipi(0x41);
+    }
+
+    #[test]
+    fn records_into_ring_buffer() {
+        let register_file = RegisterFile::new(2, 0x0005_0014);
+        let trace = TraceBuffer::<8>::new();
+        record(&register_file, &trace);
+
+        assert_eq!(trace.len(), 6);
+        assert_eq!(trace.iter().next(), Some(Event::GetId(2)));
+
+        let mut line = FmtBuffer::<128>::new();
+        write!(line, "{}", Event::SetTimerInitialCount(1000)).unwrap();
+        assert_eq!(line.as_str(), "write TIMER_INITIAL_COUNT: 1000");
+
+        let mut line = FmtBuffer::<128>::new();
+        write!(
+            line,
+            "{}",
+            Event::ReadRaw {
+                register: Register::IN_SERVICE(1),
+                value: 1
+            }
+        )
+        .unwrap();
+        assert_eq!(line.as_str(), "read IN_SERVICE[1] (raw): 0x00000001");
+
+        for _ in 0..3 {
+            trace.record(Event::EndOfInterrupt);
+        }
+        assert_eq!(trace.len(), 8);
+        assert_eq!(trace.recorded(), 9);
+        assert_eq!(
+            trace.iter().next(),
+            Some(Event::GetTimerVector(LocalVector(1 << 16, PhantomData)))
+        );
+    }
+
+    #[test]
+    fn replays_into_simulation() {
+        let trace = TraceBuffer::<8>::new();
+        record(&RegisterFile::new(2, 0x0005_0014), &trace);
+
+        assert_eq!(
+            replay(&RegisterFile::new(2, 0x0005_0014), trace.iter()),
+            Ok(6)
+        );
+
+        let divergence = replay(&RegisterFile::new(3, 0x0005_0014), trace.iter()).unwrap_err();
+        assert_eq!(divergence.index, 0);
+        assert_eq!(divergence.replayed, Event::GetId(3));
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    VectorBitmap,
+    local_vector::{Kind, LocalVector},
+};
+
+/// Vectors 0..=31 are reserved for processor exceptions.
+pub const EXCEPTION_VECTORS: core::ops::Range<u8> = 0x00..0x20;
+
+/// Errors that can occur while allocating interrupt vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationError {
+    /// The vector is already allocated (or reserved).
+    AlreadyAllocated(u8),
+
+    /// No free vector satisfies the request.
+    Exhausted,
+
+    /// The priority class is not in the range 0..=15.
+    InvalidClass(u8),
+
+    /// The block size is not a power of two no greater than 32.
+    InvalidBlockSize(usize),
+
+    /// The vector is not allocated, so it cannot be freed.
+    NotAllocated(u8),
+
+    /// The vector is reserved for processor exceptions, so it cannot be freed.
+    ExceptionVector(u8),
+}
+
+/// Tracks which of the 256 interrupt vectors of a processor are in use.
+///
+/// Vectors are allocated per-processor, since each processor has its own interrupt descriptor
+/// table; use [`VectorAllocator::allocate_shared`] for vectors that must be the same on every
+/// processor, such as inter-processor interrupt vectors. The exception vectors are reserved.
+///
+/// Vectors are allocated from the lowest free vector upwards. The priority class of a vector
+/// (`vector >> 4`) determines the task priority that blocks it; use
+/// [`VectorAllocator::allocate_in_class`] to place a vector in a particular class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorAllocator(VectorBitmap);
+
+impl VectorAllocator {
+    /// Creates an allocator with only the exception vectors reserved.
+    pub fn new() -> Self {
+        Self(EXCEPTION_VECTORS.collect())
+    }
+
+    /// The vectors that are allocated or reserved.
+    pub fn allocated(&self) -> VectorBitmap {
+        self.0
+    }
+
+    /// Whether `vector` is allocated or reserved.
+    pub fn is_allocated(&self, vector: u8) -> bool {
+        self.0.contains(vector)
+    }
+
+    /// Reserves a specific `vector`, such as the spurious interrupt vector.
+    pub fn reserve(&mut self, vector: u8) -> Result<(), AllocationError> {
+        if self.0.contains(vector) {
+            return Err(AllocationError::AlreadyAllocated(vector));
+        }
+
+        self.0.insert(vector);
+
+        Ok(())
+    }
+
+    /// Finds the lowest block of `count` free vectors aligned to `align`, within `range`.
+    fn find(&self, range: core::ops::RangeInclusive<u8>, count: usize, align: usize) -> Option<u8> {
+        range
+            .filter(|&base| usize::from(base).is_multiple_of(align))
+            .filter(|&base| usize::from(base) + count <= 0x100)
+            .find(|&base| {
+                (usize::from(base)..(usize::from(base) + count))
+                    .all(|vector| !self.0.contains(u8::try_from(vector).unwrap()))
+            })
+    }
+
+    /// Allocates the lowest free vector.
+    pub fn allocate(&mut self) -> Result<u8, AllocationError> {
+        self.allocate_contiguous_aligned(1)
+    }
+
+    /// Allocates the lowest free vector in priority class `class`, which must be in the range
+    /// 0..=15.
+    pub fn allocate_in_class(&mut self, class: u8) -> Result<u8, AllocationError> {
+        if class >= 16 {
+            return Err(AllocationError::InvalidClass(class));
+        }
+
+        let vector = self
+            .find((class << 4)..=((class << 4) | 0xF), 1, 1)
+            .ok_or(AllocationError::Exhausted)?;
+        self.0.insert(vector);
+
+        Ok(vector)
+    }
+
+    /// Allocates `count` contiguous vectors, with the first vector aligned to `count`, as
+    /// required by multiple-message MSI. Returns the first vector of the block.
+    ///
+    /// `count` must be a power of two no greater than 32.
+    pub fn allocate_contiguous_aligned(&mut self, count: usize) -> Result<u8, AllocationError> {
+        if !count.is_power_of_two() || count > 32 {
+            return Err(AllocationError::InvalidBlockSize(count));
+        }
+
+        let base = self
+            .find(0..=u8::MAX, count, count)
+            .ok_or(AllocationError::Exhausted)?;
+        for offset in 0..count {
+            self.0.insert(base + u8::try_from(offset).unwrap());
+        }
+
+        Ok(base)
+    }
+
+    /// Allocates a vector and assigns it to `local_vector`. The entry must then be written back
+    /// to its local vector table register.
+    pub fn allocate_local_vector<K: Kind>(
+        &mut self,
+        local_vector: &mut LocalVector<K>,
+    ) -> Result<u8, AllocationError> {
+        let vector = self.allocate()?;
+        local_vector.set_vector(vector);
+
+        Ok(vector)
+    }
+
+    /// Allocates the lowest vector that is free in every allocator of `allocators`, optionally
+    /// in priority class `class`, and allocates it in all of them.
+    pub fn allocate_shared(
+        allocators: &mut [Self],
+        class: Option<u8>,
+    ) -> Result<u8, AllocationError> {
+        let mut union = Self(VectorBitmap::EMPTY);
+        for allocator in allocators.iter() {
+            for (word, allocated) in union.0.0.iter_mut().zip(allocator.0.0) {
+                *word |= allocated;
+            }
+        }
+
+        let vector = match class {
+            Some(class) => union.allocate_in_class(class)?,
+            None => union.allocate()?,
+        };
+
+        for allocator in allocators.iter_mut() {
+            allocator.0.insert(vector);
+        }
+
+        Ok(vector)
+    }
+
+    /// Frees `vector`, so that it can be allocated again.
+    pub fn free(&mut self, vector: u8) {
+        assert!(
+            !EXCEPTION_VECTORS.contains(&vector),
+            "exception vectors cannot be freed"
+        );
+        assert!(self.0.contains(vector), "vector is not allocated");
+
+        self.0.remove(vector);
+    }
+
+    /// Frees `vector`, or returns an error if it is an exception vector, or is not allocated.
+    pub fn try_free(&mut self, vector: u8) -> Result<(), AllocationError> {
+        if EXCEPTION_VECTORS.contains(&vector) {
+            return Err(AllocationError::ExceptionVector(vector));
+        }
+
+        if !self.0.contains(vector) {
+            return Err(AllocationError::NotAllocated(vector));
+        }
+
+        self.0.remove(vector);
+
+        Ok(())
+    }
+}
+
+impl Default for VectorAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_in_classes_and_blocks() {
+        let mut allocator = VectorAllocator::new();
+        assert_eq!(allocator.allocate(), Ok(0x20));
+        assert_eq!(allocator.allocate_in_class(0xF), Ok(0xF0));
+        assert_eq!(allocator.reserve(0xFF), Ok(()));
+        assert_eq!(
+            allocator.reserve(0xFF),
+            Err(AllocationError::AlreadyAllocated(0xFF))
+        );
+
+        assert_eq!(allocator.allocate_contiguous_aligned(8), Ok(0x28));
+        assert_eq!(allocator.allocate(), Ok(0x21));
+        assert_eq!(
+            allocator.allocate_in_class(0x1),
+            Err(AllocationError::Exhausted)
+        );
+
+        allocator.free(0x28);
+        assert_eq!(allocator.allocate(), Ok(0x22));
+        assert_eq!(allocator.allocate_contiguous_aligned(32), Ok(0x40));
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let mut allocator = VectorAllocator::new();
+
+        assert_eq!(
+            allocator.allocate_in_class(16),
+            Err(AllocationError::InvalidClass(16))
+        );
+        assert_eq!(
+            allocator.allocate_contiguous_aligned(3),
+            Err(AllocationError::InvalidBlockSize(3))
+        );
+        assert_eq!(
+            allocator.allocate_contiguous_aligned(64),
+            Err(AllocationError::InvalidBlockSize(64))
+        );
+        assert_eq!(
+            allocator.try_free(0x0E),
+            Err(AllocationError::ExceptionVector(0x0E))
+        );
+        assert_eq!(
+            allocator.try_free(0x40),
+            Err(AllocationError::NotAllocated(0x40))
+        );
+
+        assert_eq!(allocator.allocate_in_class(4), Ok(0x40));
+        assert_eq!(allocator.try_free(0x40), Ok(()));
+        assert!(!allocator.is_allocated(0x40));
+    }
+
+    #[test]
+    fn allocates_shared_vectors() {
+        let mut allocators = [VectorAllocator::new(); 2];
+        allocators[1].reserve(0x20).unwrap();
+        allocators[0].reserve(0x21).unwrap();
+
+        assert_eq!(
+            VectorAllocator::allocate_shared(&mut allocators, None),
+            Ok(0x22)
+        );
+        assert!(
+            allocators
+                .iter()
+                .all(|allocator| allocator.is_allocated(0x22))
+        );
+        assert_eq!(allocators[0].allocate(), Ok(0x20));
+        assert_eq!(
+            VectorAllocator::allocate_shared(&mut allocators, Some(16)),
+            Err(AllocationError::InvalidClass(16))
+        );
+    }
+}
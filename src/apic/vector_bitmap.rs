@@ -0,0 +1,174 @@
+use core::fmt;
+
+use bit_field::BitField;
+
+/// A set of interrupt vectors, in the layout of the 256-bit in-service, trigger mode and
+/// interrupt request registers: bit `n` of word `n / 32` represents vector `n`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct VectorBitmap(pub(crate) [u32; 8]);
+
+impl VectorBitmap {
+    /// The empty set of vectors.
+    pub const EMPTY: Self = Self([0; 8]);
+
+    /// Creates a bitmap from its eight 32-bit words, lowest vectors first.
+    pub const fn new(words: [u32; 8]) -> Self {
+        Self(words)
+    }
+
+    /// The eight 32-bit words of the bitmap, lowest vectors first.
+    pub const fn words(&self) -> [u32; 8] {
+        self.0
+    }
+
+    /// Whether `vector` is in the set.
+    pub fn contains(&self, vector: u8) -> bool {
+        let vector = usize::from(vector);
+        self.0[vector / 32].get_bit(vector % 32)
+    }
+
+    /// Adds `vector` to the set.
+    pub fn insert(&mut self, vector: u8) {
+        let vector = usize::from(vector);
+        self.0[vector / 32].set_bit(vector % 32, true);
+    }
+
+    /// Removes `vector` from the set.
+    pub fn remove(&mut self, vector: u8) {
+        let vector = usize::from(vector);
+        self.0[vector / 32].set_bit(vector % 32, false);
+    }
+
+    /// Whether the set contains no vectors.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    /// The number of vectors in the set.
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// The highest vector in the set.
+    ///
+    /// For the in-service and interrupt request registers, this is the vector with the highest
+    /// priority.
+    pub fn highest(&self) -> Option<u8> {
+        self.0.iter().enumerate().rev().find_map(|(index, &word)| {
+            (word != 0)
+                .then(|| u8::try_from((index * 32) + 31 - (word.leading_zeros() as usize)).unwrap())
+        })
+    }
+
+    /// The lowest vector in the set.
+    pub fn lowest(&self) -> Option<u8> {
+        self.0.iter().enumerate().find_map(|(index, &word)| {
+            (word != 0)
+                .then(|| u8::try_from((index * 32) + (word.trailing_zeros() as usize)).unwrap())
+        })
+    }
+
+    /// Iterates the vectors in the set, in ascending order.
+    pub fn iter(&self) -> Vectors {
+        Vectors {
+            bitmap: *self,
+            index: 0,
+        }
+    }
+}
+
+impl From<[u32; 8]> for VectorBitmap {
+    fn from(value: [u32; 8]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<VectorBitmap> for [u32; 8] {
+    fn from(value: VectorBitmap) -> Self {
+        value.0
+    }
+}
+
+impl FromIterator<u8> for VectorBitmap {
+    fn from_iter<T: IntoIterator<Item = u8>>(iter: T) -> Self {
+        let mut bitmap = Self::EMPTY;
+        for vector in iter {
+            bitmap.insert(vector);
+        }
+
+        bitmap
+    }
+}
+
+impl IntoIterator for VectorBitmap {
+    type Item = u8;
+    type IntoIter = Vectors;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for VectorBitmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the vectors in a [`VectorBitmap`], in ascending order.
+#[derive(Debug, Clone)]
+pub struct Vectors {
+    bitmap: VectorBitmap,
+    index: usize,
+}
+
+impl Iterator for Vectors {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.bitmap.0.len() {
+            let word = &mut self.bitmap.0[self.index];
+            if *word != 0 {
+                let bit = word.trailing_zeros() as usize;
+                word.set_bit(bit, false);
+
+                return Some(u8::try_from((self.index * 32) + bit).unwrap());
+            }
+
+            self.index += 1;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_operations() {
+        let mut bitmap = VectorBitmap::EMPTY;
+        assert!(bitmap.is_empty());
+        assert_eq!(bitmap.highest(), None);
+        assert_eq!(bitmap.lowest(), None);
+
+        bitmap.insert(0x20);
+        bitmap.insert(0x21);
+        bitmap.insert(0xFF);
+        bitmap.insert(0x80);
+
+        assert_eq!(bitmap.len(), 4);
+        assert!(bitmap.contains(0x80));
+        assert!(!bitmap.contains(0x7F));
+        assert_eq!(bitmap.highest(), Some(0xFF));
+        assert_eq!(bitmap.lowest(), Some(0x20));
+        assert!(bitmap.iter().eq([0x20, 0x21, 0x80, 0xFF]));
+        assert_eq!(bitmap.words()[1], 0b11);
+        assert_eq!(bitmap.words()[7], 1 << 31);
+
+        bitmap.remove(0xFF);
+        assert_eq!(bitmap.highest(), Some(0x80));
+        assert_eq!(bitmap, [0x20, 0x21, 0x80].into_iter().collect());
+    }
+}
@@ -1,89 +1,282 @@
-use crate::{LocalVector, Mode, Timer, TimerMode};
+use core::{marker::PhantomData, ptr::NonNull};
+
+use crate::{
+    ArbitrationPriority, DestinationFormat, ErrorStatus, InterruptCommand, LocalDestination, Mode,
+    ProcessorPriority, Register, RemoteRead, TaskPriority, TimerDivideConfiguration, VectorBitmap,
+    Version,
+    local_vector::{
+        CMCI, Error, LINT0, LINT1, LocalVector, PerformanceMonitors, ThermalSensor, Timer,
+        TimerMode,
+    },
+};
+use bit_field::BitField;
+use safe_mmio::{UniqueMmioPointer, field, fields::ReadWrite};
+
+/// A single xAPIC register. Registers are 32 bits wide, but are aligned to 16-byte boundaries.
+#[repr(C, align(16))]
+pub struct Slot {
+    value: ReadWrite<u32>,
+}
+
+/// The xAPIC register page, as mapped from [`crate::xAPIC_BASE_ADDR`].
+pub type Registers = [Slot; 64];
+
+/// Handle to a mapping of the xAPIC register page.
+///
+/// The handle is the only means of accessing the mapping, and is neither `Copy` nor `Clone`:
+/// local APIC handles borrow it (see [`x1`]). As it is also neither `Send` nor `Sync`, every
+/// access is made from the processor that owns it, one at a time.
+#[derive(Debug)]
+pub struct Mmio(NonNull<Registers>);
+
+impl Mmio {
+    /// Creates a new handle to the xAPIC register page at `registers`.
+    ///
+    /// # Safety
+    ///
+    /// - `registers` must point to the xAPIC register page of the executing processor, and be
+    ///   mapped as uncacheable device memory.
+    /// - The mapping must remain valid for as long as this handle is in use.
+    /// - The mapping must not be accessed other than through this handle while it exists.
+    pub const unsafe fn new(registers: NonNull<Registers>) -> Self {
+        Self(registers)
+    }
+
+    /// Gets a pointer to the register page.
+    pub const fn as_ptr(&self) -> NonNull<Registers> {
+        self.0
+    }
+
+    /// Gets a pointer to the register page, which is only used for the duration of a single
+    /// access.
+    fn registers(&self) -> UniqueMmioPointer<'_, Registers> {
+        // Safety: The constructor requires that the pointer is a valid mapping of the register
+        //         page, which is only accessed through this handle. The handle is not `Sync`,
+        //         and the pointer never outlives the access it is created for, so no two
+        //         pointers to the mapping are ever in use at once.
+        unsafe { UniqueMmioPointer::new(self.0) }
+    }
+
+    fn read(&self, register: Register) -> u32 {
+        let mut registers = self.registers();
+        let mut slot = registers.get(register.index()).unwrap();
+        field!(slot, value).read()
+    }
+
+    fn write(&self, register: Register, value: u32) {
+        let mut registers = self.registers();
+        let mut slot = registers.get(register.index()).unwrap();
+        field!(slot, value).write(value);
+    }
+
+    /// Reads the eight registers of the 256-bit register bank `bank`.
+    fn read_bitmap(&self, bank: fn(u8) -> Register) -> VectorBitmap {
+        let mut registers = self.registers();
+
+        VectorBitmap(core::array::from_fn(|index| {
+            let register = bank(u8::try_from(index).unwrap());
+            let mut slot = registers.get(register.index()).unwrap();
+            field!(slot, value).read()
+        }))
+    }
+}
+
+/// xAPIC mode, in which the registers are accessed through a borrowed [`Mmio`] handle.
+pub struct x1<'a>(PhantomData<&'a Mmio>);
+
+impl<'a> Mode for x1<'a> {
+    type Inner = &'a Mmio;
+    const EXTENDED: bool = false;
+
+    fn read_register_raw(inner: Self::Inner, register: Register) -> u32 {
+        inner.read(register)
+    }
+
+    fn write_register_raw(inner: Self::Inner, register: Register, value: u32) {
+        inner.write(register, value);
+    }
+
+    fn get_id(inner: Self::Inner) -> u32 {
+        inner.read(Register::ID).get_bits(24..32)
+    }
+
+    fn get_version(inner: Self::Inner) -> Version {
+        Version(inner.read(Register::VERSION))
+    }
+
+    fn get_task_priority(inner: Self::Inner) -> TaskPriority {
+        TaskPriority(inner.read(Register::TASK_PRIORITY))
+    }
 
-struct x1;
-impl Mode for x1 {
-    type Inner = usize;
+    fn set_task_priority(inner: Self::Inner, value: TaskPriority) {
+        inner.write(Register::TASK_PRIORITY, value.0);
+    }
+
+    fn get_arbitration_priority(inner: Self::Inner) -> ArbitrationPriority {
+        ArbitrationPriority(inner.read(Register::ARBITRATION_PRIORITY))
+    }
+
+    fn get_processor_priority(inner: Self::Inner) -> ProcessorPriority {
+        ProcessorPriority(inner.read(Register::PROCESSOR_PRIORITY))
+    }
 
-    fn get_id() -> u8 {
-        todo!()
+    fn get_remote_read(inner: Self::Inner) -> RemoteRead {
+        RemoteRead(inner.read(Register::REMOTE_READ))
     }
 
-    fn get_version() -> crate::Version {
-        todo!()
+    fn get_local_destination(inner: Self::Inner) -> LocalDestination {
+        LocalDestination(inner.read(Register::LOCAL_DESTINATION).get_bits(24..32))
     }
 
-    fn get_task_priority() -> TaskPriority {
-        todo!()
+    fn set_local_destination(inner: Self::Inner, value: LocalDestination) {
+        assert!(value.0 <= 0xFF, "xAPIC logical IDs are 8 bits wide");
+
+        inner.write(Register::LOCAL_DESTINATION, value.0 << 24);
     }
 
-    fn set_task_priority(value: TaskPriority) {
-        todo!()
+    fn get_destination_format(inner: Self::Inner) -> DestinationFormat {
+        DestinationFormat(inner.read(Register::DESTINATION_FORMAT))
     }
 
-    fn get_arbitration_priority() -> ArbitrationPriority {
-        todo!()
+    fn set_destination_format(inner: Self::Inner, value: DestinationFormat) {
+        inner.write(Register::DESTINATION_FORMAT, value.0);
     }
 
-    fn get_processor_priority() -> ProcessorPriority {
-        todo!()
+    fn get_in_service(inner: Self::Inner) -> VectorBitmap {
+        inner.read_bitmap(Register::IN_SERVICE)
     }
 
-    fn get_remote_read() -> RemoteRead {
-        todo!()
+    fn get_trigger_mode(inner: Self::Inner) -> VectorBitmap {
+        inner.read_bitmap(Register::TRIGGER_MODE)
     }
 
-    fn get_local_destination() -> LocalDestination {
-        todo!()
+    fn get_interrupt_request(inner: Self::Inner) -> VectorBitmap {
+        inner.read_bitmap(Register::INTERRUPT_REQUEST)
     }
 
-    fn get_error_status() -> crate::ErrorStatus {
-        todo!()
+    fn get_error_status(inner: Self::Inner) -> ErrorStatus {
+        ErrorStatus::from_bits_truncate(inner.read(Register::ERROR_STATUS))
     }
 
-    fn clear_error_status() {
-        todo!()
+    fn clear_error_status(inner: Self::Inner) {
+        inner.write(Register::ERROR_STATUS, 0x0);
     }
 
-    fn get_timer_initial_count() -> u32 {
-        todo!()
+    fn get_timer_initial_count(inner: Self::Inner) -> u32 {
+        inner.read(Register::TIMER_INITIAL_COUNT)
     }
 
-    fn set_timer_initial_count(value: u32) {
-        todo!()
+    fn set_timer_initial_count(inner: Self::Inner, value: u32) {
+        inner.write(Register::TIMER_INITIAL_COUNT, value);
     }
 
-    fn get_timer_current_count() -> u32 {
-        todo!()
+    fn get_timer_current_count(inner: Self::Inner) -> u32 {
+        inner.read(Register::TIMER_CURRENT_COUNT)
     }
 
-    fn get_timer_divide_configuration() -> crate::TimerDivideConfiguration {
-        todo!()
+    fn get_timer_divide_configuration(inner: Self::Inner) -> TimerDivideConfiguration {
+        TimerDivideConfiguration::from_bits_truncate(
+            inner.read(Register::TIMER_DIVIDE_CONFIGURATION),
+        )
     }
 
-    fn set_timer_divide_configuration(value: crate::TimerDivideConfiguration) {
-        todo!()
+    fn set_timer_divide_configuration(inner: Self::Inner, value: TimerDivideConfiguration) {
+        inner.write(Register::TIMER_DIVIDE_CONFIGURATION, value.bits());
     }
 
-    fn send_interrupt_command(interrupt_command: crate::InterruptCommand) {
-        todo!()
+    fn is_tsc_deadline_supported(_: Self::Inner) -> bool {
+        crate::timer::is_tsc_deadline_supported()
     }
 
-    fn get_spurious_vector() -> SpuriousInterruptVector {
-        todo!()
+    fn get_tsc_deadline(_: Self::Inner) -> u64 {
+        super::get_ia32_tsc_deadline()
     }
 
-    fn set_spurious_vector(value: SpuriousInterruptVector) {
-        todo!()
+    fn set_tsc_deadline(_: Self::Inner, deadline: u64) {
+        // Safety: `IA32_TSC_DEADLINE` only arms the local APIC timer.
+        unsafe {
+            super::set_ia32_tsc_deadline(deadline);
+        }
     }
 
-    fn get_timer_vector() -> GenericVector {
-        todo!()
+    fn send_interrupt_command(inner: Self::Inner, interrupt_command: InterruptCommand) {
+        // The interrupt is sent when the low doubleword is written, so the destination
+        // in the high doubleword must be written first.
+        inner.write(
+            Register::INTERRUPT_COMMAND_HIGH,
+            interrupt_command.high() << 24,
+        );
+        inner.write(Register::INTERRUPT_COMMAND_LOW, interrupt_command.low());
     }
 
-    fn set_timer_vector(value: LocalVector<Timer>) {
+    fn send_self_ipi(inner: Self::Inner, vector: u8) {
+        // A fixed, edge-triggered interrupt with the self destination shorthand; the
+        // destination is ignored with a destination shorthand. Vectors 0 through 15 are
+        // rejected by the local APIC, which reports a send illegal vector error.
+        let mut interrupt_command = 0x0;
+        interrupt_command.set_bits(0..8, u32::from(vector));
+        interrupt_command.set_bit(14, true);
+        interrupt_command.set_bits(18..20, 0b01);
+
+        inner.write(Register::INTERRUPT_COMMAND_LOW, interrupt_command);
+    }
+
+    fn get_spurious_vector(inner: Self::Inner) -> u8 {
+        u8::try_from(inner.read(Register::SPURIOUS_VECTOR).get_bits(..8)).unwrap()
+    }
+
+    fn get_spurious_apic_software_enabled(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(8)
+    }
+
+    fn get_spurious_focus_processor_checking(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(9)
+    }
+
+    fn get_spurious_eoi_broadcast_suppression(inner: Self::Inner) -> bool {
+        inner.read(Register::SPURIOUS_VECTOR).get_bit(12)
+    }
+
+    fn set_spurious_vector(inner: Self::Inner, vector: u8) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner
+                .read(Register::SPURIOUS_VECTOR)
+                .set_bits(..8, u32::from(vector)),
+        );
+    }
+
+    fn set_spurious_apic_software_enabled(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(8, value),
+        );
+    }
+
+    fn set_spurious_focus_processor_checking(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(9, value),
+        );
+    }
+
+    fn set_spurious_eoi_broadcast_suppression(inner: Self::Inner, value: bool) {
+        inner.write(
+            Register::SPURIOUS_VECTOR,
+            *inner.read(Register::SPURIOUS_VECTOR).set_bit(12, value),
+        );
+    }
+
+    fn get_timer_vector(inner: Self::Inner) -> LocalVector<Timer> {
+        LocalVector::<Timer>(inner.read(Register::TIMER_VECTOR), PhantomData)
+    }
+
+    fn set_timer_vector(inner: Self::Inner, value: LocalVector<Timer>) {
+        inner.write(Register::TIMER_VECTOR, u32::from(value));
+
         // IA32 SDM instructs utilizing the `mfence` instruction to ensure all writes to the IA32_TSC_DEADLINE
         // MSR are serialized *after* the APIC timer mode switch (`wrmsr` to `IA32_TSC_DEADLINE` is non-serializing).
-        if value.get_mode() == TimerMode::TscDeadline {
+        if value.try_get_mode() == Ok(TimerMode::TscDeadline) {
             // Safety: `mfence` has no safety implications.
             unsafe {
                 core::arch::x86_64::_mm_mfence();
@@ -91,55 +284,61 @@ impl Mode for x1 {
         }
     }
 
-    fn get_cmci_vector() -> GenericVector {
-        todo!()
+    fn get_cmci_vector(inner: Self::Inner) -> LocalVector<CMCI> {
+        LocalVector::<CMCI>(inner.read(Register::CMCI_VECTOR), PhantomData)
     }
 
-    fn set_cmci_vector(value: GenericVector) {
-        todo!()
+    fn set_cmci_vector(inner: Self::Inner, value: LocalVector<CMCI>) {
+        inner.write(Register::CMCI_VECTOR, u32::from(value));
     }
 
-    fn get_lint0_vector() -> LocalInterruptVector {
-        todo!()
+    fn get_lint0_vector(inner: Self::Inner) -> LocalVector<LINT0> {
+        LocalVector::<LINT0>(inner.read(Register::LINT0_VECTOR), PhantomData)
     }
 
-    fn set_lint0_vector(value: LocalInterruptVector) {
-        todo!()
+    fn set_lint0_vector(inner: Self::Inner, value: LocalVector<LINT0>) {
+        inner.write(Register::LINT0_VECTOR, u32::from(value));
     }
 
-    fn get_lint1_vector() -> LocalInterruptVector {
-        todo!()
+    fn get_lint1_vector(inner: Self::Inner) -> LocalVector<LINT1> {
+        LocalVector::<LINT1>(inner.read(Register::LINT1_VECTOR), PhantomData)
     }
 
-    fn set_lint1_vector(value: LocalInterruptVector) {
-        todo!()
+    fn set_lint1_vector(inner: Self::Inner, value: LocalVector<LINT1>) {
+        inner.write(Register::LINT1_VECTOR, u32::from(value));
     }
 
-    fn get_error_vector() -> ErrorVector {
-        todo!()
+    fn get_error_vector(inner: Self::Inner) -> LocalVector<Error> {
+        LocalVector::<Error>(inner.read(Register::ERROR_VECTOR), PhantomData)
     }
 
-    fn set_error_vector(value: ErrorVector) {
-        todo!()
+    fn set_error_vector(inner: Self::Inner, value: LocalVector<Error>) {
+        inner.write(Register::ERROR_VECTOR, u32::from(value));
     }
 
-    fn get_performance_monitors_vector() -> GenericVector {
-        todo!()
+    fn get_performance_monitors_vector(inner: Self::Inner) -> LocalVector<PerformanceMonitors> {
+        LocalVector::<PerformanceMonitors>(
+            inner.read(Register::PERFORMANCE_MONITORS_VECTOR),
+            PhantomData,
+        )
     }
 
-    fn set_performance_monitors_vector(value: GenericVector) {
-        todo!()
+    fn set_performance_monitors_vector(
+        inner: Self::Inner,
+        value: LocalVector<PerformanceMonitors>,
+    ) {
+        inner.write(Register::PERFORMANCE_MONITORS_VECTOR, u32::from(value));
     }
 
-    fn get_thermal_sensor_vector() -> GenericVector {
-        todo!()
+    fn get_thermal_sensor_vector(inner: Self::Inner) -> LocalVector<ThermalSensor> {
+        LocalVector::<ThermalSensor>(inner.read(Register::THERMAL_SENSOR_VECTOR), PhantomData)
     }
 
-    fn set_thermal_sensor_vector(value: GenericVector) {
-        todo!()
+    fn set_thermal_sensor_vector(inner: Self::Inner, value: LocalVector<ThermalSensor>) {
+        inner.write(Register::THERMAL_SENSOR_VECTOR, u32::from(value));
     }
 
-    fn end_of_interrrupt() {
-        todo!()
+    fn end_of_interrrupt(inner: Self::Inner) {
+        inner.write(Register::END_OF_INTERRUPT, 0x0);
     }
 }
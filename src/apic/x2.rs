@@ -1,7 +1,8 @@
 use core::marker::PhantomData;
 
 use crate::{
-    ErrorStatus, Mode, TimerDivideConfiguration, Version,
+    ArbitrationPriority, DestinationFormat, ErrorStatus, LocalDestination, Mode, ProcessorPriority,
+    Register, RemoteRead, TaskPriority, TimerDivideConfiguration, VectorBitmap, Version,
     local_vector::{
         CMCI, Error, LINT0, LINT1, LocalVector, PerformanceMonitors, ThermalSensor, Timer,
         TimerMode,
@@ -9,29 +10,25 @@ use crate::{
 };
 use bit_field::BitField;
 
-#[repr(u32)]
-#[derive(Debug, Clone, Copy)]
-#[allow(non_camel_case_types)]
-enum Register {
-    ID = 0x802,
-    VERSION = 0x803,
-    TASK_PRIORITY = 0x808,
-    PROCESSOR_PRIORITY = 0x80A,
-    END_OF_INTERRUPT = 0x80B,
-    LOCAL_DESTINATION = 0x80D,
-    SPURIOUS_VECTOR = 0x80F,
-    ERROR_STATUS = 0x828,
-    CMCI_VECTOR = 0x802F,
-    INTERRUPT_COMMAND = 0x830,
-    TIMER_VECTOR = 0x832,
-    THERMAL_SENSOR_VECTOR = 0x833,
-    PERFORMANCE_MONITORS_VECTOR = 0x834,
-    LINT0_VECTOR = 0x835,
-    LINT1_VECTOR = 0x836,
-    ERROR_VECTOR = 0x837,
-    TIMER_INITIAL_COUNT = 0x838,
-    TIMER_CURRENT_COUNT = 0x839,
-    TIMER_DIVIDE_CONFIGURATION = 0x83E,
+/// Reads from the model-specific register of `register`.
+#[inline(always)]
+fn read_register(register: Register) -> u64 {
+    read_msr(msr_of(register))
+}
+
+/// Reads the eight registers of the 256-bit register bank `bank`.
+fn read_bitmap(bank: fn(u8) -> Register) -> VectorBitmap {
+    VectorBitmap(core::array::from_fn(|index| {
+        let register = bank(u8::try_from(index).unwrap());
+        u32::try_from(read_register(register)).unwrap()
+    }))
+}
+
+/// The address of the model-specific register of `register`.
+fn msr_of(register: Register) -> u32 {
+    register
+        .x2apic_msr()
+        .expect("register does not exist in x2APIC mode")
 }
 
 /// Reads from the model-specific register at the provided `address`.
@@ -40,7 +37,7 @@ enum Register {
 ///
 ///
 #[inline(always)]
-fn read_register(register: Register) -> u64 {
+fn read_msr(address: u32) -> u64 {
     let value_low: u64;
     let value_high: u64;
 
@@ -48,7 +45,7 @@ fn read_register(register: Register) -> u64 {
     unsafe {
         core::arch::asm!(
             "rdmsr",
-            in("ecx") register as u32,
+            in("ecx") address,
             out("edx") value_high,
             out("eax") value_low,
             options(nostack, nomem, preserves_flags)
@@ -61,14 +58,14 @@ fn read_register(register: Register) -> u64 {
 /// Writes `value` to the model-specific register at the provided `address`.
 #[inline(always)]
 fn write_register(register: Register, value: u64) {
-    let value_low = value & 0xFFFF;
+    let value_low = value & 0xFFFF_FFFF;
     let value_high = value >> 32;
 
     // Safety: Writing to x2 APIC model-specific registers cannot create undefined behaviour.
     unsafe {
         core::arch::asm!(
             "wrmsr",
-            in("ecx") register as u32,
+            in("ecx") msr_of(register),
             in("edx") value_high,
             in("eax") value_low,
             options(nostack, nomem, preserves_flags)
@@ -76,10 +73,21 @@ fn write_register(register: Register, value: u64) {
     }
 }
 
-struct x2;
+pub struct x2;
 
 impl Mode for x2 {
     type Inner = ();
+    const EXTENDED: bool = true;
+
+    fn read_register_raw(_: Self::Inner, register: Register) -> u32 {
+        // The interrupt command register is the only 64-bit register; its destination (the
+        // high doubleword) is truncated, and only accessible through `send_interrupt_command`.
+        read_register(register) as u32
+    }
+
+    fn write_register_raw(_: Self::Inner, register: Register, value: u32) {
+        write_register(register, u64::from(value));
+    }
 
     fn get_id(_: Self::Inner) -> u32 {
         u32::try_from(read_register(Register::ID)).unwrap()
@@ -91,27 +99,51 @@ impl Mode for x2 {
     }
 
     fn get_task_priority(_: Self::Inner) -> TaskPriority {
-        todo!()
+        TaskPriority(u32::try_from(read_register(Register::TASK_PRIORITY)).unwrap())
     }
 
     fn set_task_priority(_: Self::Inner, value: TaskPriority) {
-        todo!()
+        write_register(Register::TASK_PRIORITY, u64::from(value.0));
     }
 
     fn get_arbitration_priority(_: Self::Inner) -> ArbitrationPriority {
-        todo!()
+        panic!("the arbitration priority register does not exist in x2APIC mode")
     }
 
     fn get_processor_priority(_: Self::Inner) -> ProcessorPriority {
-        todo!()
+        ProcessorPriority(u32::try_from(read_register(Register::PROCESSOR_PRIORITY)).unwrap())
     }
 
     fn get_remote_read(_: Self::Inner) -> RemoteRead {
-        todo!()
+        panic!("the remote read register does not exist in x2APIC mode")
     }
 
     fn get_local_destination(_: Self::Inner) -> LocalDestination {
-        todo!()
+        LocalDestination(u32::try_from(read_register(Register::LOCAL_DESTINATION)).unwrap())
+    }
+
+    fn set_local_destination(_: Self::Inner, _: LocalDestination) {
+        panic!("the logical destination register is read-only in x2APIC mode")
+    }
+
+    fn get_destination_format(_: Self::Inner) -> DestinationFormat {
+        panic!("the destination format register does not exist in x2APIC mode")
+    }
+
+    fn set_destination_format(_: Self::Inner, _: DestinationFormat) {
+        panic!("the destination format register does not exist in x2APIC mode")
+    }
+
+    fn get_in_service(_: Self::Inner) -> VectorBitmap {
+        read_bitmap(Register::IN_SERVICE)
+    }
+
+    fn get_trigger_mode(_: Self::Inner) -> VectorBitmap {
+        read_bitmap(Register::TRIGGER_MODE)
+    }
+
+    fn get_interrupt_request(_: Self::Inner) -> VectorBitmap {
+        read_bitmap(Register::INTERRUPT_REQUEST)
     }
 
     fn get_error_status(_: Self::Inner) -> ErrorStatus {
@@ -147,6 +179,21 @@ impl Mode for x2 {
         );
     }
 
+    fn is_tsc_deadline_supported(_: Self::Inner) -> bool {
+        crate::timer::is_tsc_deadline_supported()
+    }
+
+    fn get_tsc_deadline(_: Self::Inner) -> u64 {
+        super::get_ia32_tsc_deadline()
+    }
+
+    fn set_tsc_deadline(_: Self::Inner, deadline: u64) {
+        // Safety: `IA32_TSC_DEADLINE` only arms the local APIC timer.
+        unsafe {
+            super::set_ia32_tsc_deadline(deadline);
+        }
+    }
+
     fn send_interrupt_command(_: Self::Inner, interrupt_command: crate::InterruptCommand) {
         let high = u64::from(interrupt_command.high());
         let low = u64::from(interrupt_command.low());
@@ -156,7 +203,11 @@ impl Mode for x2 {
             "x2 APIC does not support low priority delivery mode"
         );
 
-        write_register(Register::INTERRUPT_COMMAND, (high << 32) | low);
+        write_register(Register::INTERRUPT_COMMAND_LOW, (high << 32) | low);
+    }
+
+    fn send_self_ipi(_: Self::Inner, vector: u8) {
+        write_register(Register::SELF_IPI, u64::from(vector));
     }
 
     fn get_spurious_vector(_: Self::Inner) -> u8 {
@@ -209,16 +260,16 @@ impl Mode for x2 {
     }
 
     fn set_timer_vector(_: Self::Inner, value: LocalVector<Timer>) {
+        write_register(Register::TIMER_VECTOR, u64::from(value));
+
         // IA32 SDM instructs utilizing the `mfence` instruction to ensure all writes to the IA32_TSC_DEADLINE
         // MSR are serialized *after* the APIC timer mode switch (`wrmsr` to `IA32_TSC_DEADLINE` is non-serializing).
-        if value.get_mode() == TimerMode::TscDeadline {
+        if value.try_get_mode() == Ok(TimerMode::TscDeadline) {
             // Safety: `mfence` has no safety implications.
             unsafe {
                 core::arch::x86_64::_mm_mfence();
             }
         }
-
-        write_register(Register::TIMER_VECTOR, u64::from(value));
     }
 
     fn get_cmci_vector(_: Self::Inner) -> LocalVector<CMCI> {